@@ -0,0 +1,38 @@
+// les poignées passées en données ne quittent pas le thread du tas
+#![allow(clippy::arc_with_non_send_sync)]
+
+use std::any::Any;
+use std::sync::Arc;
+use mem_allocator::utils::io::format_heterogeneous_list;
+use mem_allocator::vm_types::type_info::{ProductType, ReferenceType};
+use mem_allocator::vm_types::type_sig::TypeSig;
+use mem_allocator::vm_types::type_tokens;
+use mem_allocator::{Heap, HeapError};
+
+fn main() -> Result<(), HeapError> {
+    let mut heap = Heap::new();
+
+    // (Int, Double) <- (Int, &(Int, Double))
+    let leaf_type = ProductType(vec![Arc::new(type_tokens::INT), Arc::new(type_tokens::DOUBLE)]);
+    let node_type = ProductType(vec![Arc::new(type_tokens::INT), Arc::new(ReferenceType(TypeSig::PRODUCT))]);
+    let leaf = heap.alloc_product(&leaf_type, &[Arc::new(1i64), Arc::new(2.5f64)])?;
+    let mut node = heap.alloc_product(&node_type, &[Arc::new(2i64), Arc::new(leaf.erase())])?;
+
+    let garbage = (0..100).map(|i| heap.alloc(i as i64)).collect::<Result<Vec<_>, _>>()?;
+    let mut counter = heap.alloc(42u64)?;
+    println!("alloué avant la collection: {} bytes", heap.allocated());
+
+    heap.collect(&mut [&mut node, &mut counter])?;
+    println!("alloué après la collection: {} bytes", heap.allocated());
+
+    let other_leaf = heap.alloc_product(&leaf_type, &[Arc::new(3i64), Arc::new(4.5f64)])?;
+    heap.set_field(node, 0, Arc::new(3i64))?;
+    heap.set_field(node, 1, Arc::new(other_leaf.erase()))?;
+    println!("champ de type incorrect: {:?}", heap.set_field(node, 0, Arc::new(true)).err());
+
+    let (ty, data) = heap.read(node)?;
+    println!("{}: {}", ty.name(), format_heterogeneous_list(data.downcast_ref::<Vec<Arc<dyn Any>>>().unwrap()));
+    println!("compteur: {}", heap.get(counter)?);
+    println!("handle non-racine: {:?}", heap.get(garbage[0]).err());
+    Ok(())
+}
@@ -0,0 +1,131 @@
+// `#[derive(HostType)]`, see `mem_allocator::allocator::host_type::HostType`. The generated code only goes through
+// the helpers of that module, so that the types describing records, products and sums can change freely.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Fields, Index};
+
+#[proc_macro_derive(HostType)]
+pub fn derive_host_type(input: TokenStream) -> TokenStream {
+    let mut input = parse_macro_input!(input as DeriveInput);
+    let host = quote!(::mem_allocator::allocator::host_type);
+    for param in input.generics.type_params_mut() {
+        param.bounds.push(parse_quote!(#host::HostType));
+    }
+    let body = match &input.data {
+        Data::Struct(data) => derive_struct(&data.fields),
+        Data::Enum(data) => derive_enum(data.variants.iter().map(|variant| (&variant.ident, &variant.fields))),
+        Data::Union(_) => Err(syn::Error::new_spanned(&input.ident, "HostType cannot be derived for unions"))
+    };
+    let (type_info, to_value, from_value) = match body {
+        Ok(body) => body,
+        Err(error) => return error.to_compile_error().into()
+    };
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    quote! {
+        impl #impl_generics #host::HostType for #name #type_generics #where_clause {
+            fn type_info() -> ::std::sync::Arc<dyn ::mem_allocator::vm_types::type_info::TypeInfo> {
+                #type_info
+            }
+
+            fn to_value(&self) -> ::mem_allocator::allocator::value::Value {
+                #to_value
+            }
+
+            fn from_value(value: &::mem_allocator::allocator::value::Value) -> ::std::result::Result<Self, ::mem_allocator::AllocatorError> {
+                #from_value
+            }
+        }
+    }.into()
+}
+
+// The type info, `to_value` and `from_value` bodies of a struct. Named fields make a record, the other structs
+// are products.
+fn derive_struct(fields: &Fields) -> syn::Result<(TokenStream2, TokenStream2, TokenStream2)> {
+    let host = quote!(::mem_allocator::allocator::host_type);
+    let types = fields.iter().map(|field| &field.ty).collect::<Vec<_>>();
+    Ok(match fields {
+        Fields::Named(named) => {
+            let idents = named.named.iter().map(|field| field.ident.as_ref().unwrap()).collect::<Vec<_>>();
+            let names = idents.iter().map(|ident| ident.to_string()).collect::<Vec<_>>();
+            (
+                quote!(#host::record_type(vec![#((#names, <#types as #host::HostType>::type_info())),*])),
+                quote!(#host::record_value(vec![#((#names, #host::HostType::to_value(&self.#idents))),*])),
+                quote!(Ok(Self { #(#idents: <#types as #host::HostType>::from_value(#host::record_field(value, #names)?)?),* }))
+            )
+        },
+        Fields::Unnamed(_) | Fields::Unit => {
+            let count = types.len();
+            let indices = (0..count).map(Index::from).collect::<Vec<_>>();
+            let bind = bind_fields(count);
+            let construct = match fields {
+                Fields::Unit => quote!(Self),
+                _ => quote!(Self(#(<#types as #host::HostType>::from_value(&fields[#indices])?),*))
+            };
+            (
+                quote!(#host::product_type(vec![#(<#types as #host::HostType>::type_info()),*])),
+                quote!(::mem_allocator::allocator::value::Value::Product(vec![#(#host::HostType::to_value(&self.#indices)),*])),
+                quote! {
+                    #bind #host::product_fields(value, #count)?;
+                    Ok(#construct)
+                }
+            )
+        }
+    })
+}
+
+// the checked fields are only bound if there are fields to read
+fn bind_fields(count: usize) -> TokenStream2 {
+    if count == 0 { quote!() } else { quote!(let fields =) }
+}
+
+// An enum is a sum, each variant is a case whose fields are those of the variant in declaration order.
+fn derive_enum<'a>(variants: impl Iterator<Item = (&'a Ident, &'a Fields)>) -> syn::Result<(TokenStream2, TokenStream2, TokenStream2)> {
+    let host = quote!(::mem_allocator::allocator::host_type);
+    let (mut cases, mut to_arms, mut from_arms) = (vec![], vec![], vec![]);
+    for (ident, fields) in variants {
+        let case = ident.to_string();
+        let types = fields.iter().map(|field| &field.ty).collect::<Vec<_>>();
+        let count = types.len();
+        let bindings = (0..count).map(|index| Ident::new(&format!("field_{}", index), Span::call_site())).collect::<Vec<_>>();
+        let indices = 0..count;
+        let bind = bind_fields(count);
+        let (pattern, construct) = match fields {
+            Fields::Named(named) => {
+                let idents = named.named.iter().map(|field| field.ident.as_ref().unwrap()).collect::<Vec<_>>();
+                (
+                    quote!(Self::#ident { #(#idents: #bindings),* }),
+                    quote!(Self::#ident { #(#idents: <#types as #host::HostType>::from_value(&fields[#indices])?),* })
+                )
+            },
+            Fields::Unnamed(_) => (
+                quote!(Self::#ident(#(#bindings),*)),
+                quote!(Self::#ident(#(<#types as #host::HostType>::from_value(&fields[#indices])?),*))
+            ),
+            Fields::Unit => (quote!(Self::#ident), quote!(Self::#ident))
+        };
+        cases.push(quote!((#case, vec![#(<#types as #host::HostType>::type_info()),*])));
+        to_arms.push(quote! {
+            #pattern => ::mem_allocator::allocator::value::Value::Sum(#case.to_string(), vec![#(#host::HostType::to_value(#bindings)),*])
+        });
+        from_arms.push(quote! {
+            #case => {
+                #bind #host::case_fields(fields, #count)?;
+                Ok(#construct)
+            }
+        });
+    }
+    Ok((
+        quote!(#host::sum_type(vec![#(#cases),*])),
+        quote!(match self { #(#to_arms),* }),
+        quote! {
+            let (case, fields) = #host::sum_case(value)?;
+            match case {
+                #(#from_arms),*
+                _ => Err(#host::unknown_case(case))
+            }
+        }
+    ))
+}
@@ -0,0 +1,13 @@
+use crate::allocator::object_allocator::ObjectHeader;
+
+// A hook run by `ObjectAllocator::set_reference` before a reference slot of a live object is overwritten.
+// `offset` is relative to the data start of `obj`, `old` is the value being overwritten.
+pub trait WriteBarrier {
+    unsafe fn on_reference_store(&mut self, obj: *mut ObjectHeader, offset: usize, old: *mut ObjectHeader, new: *mut ObjectHeader);
+}
+
+pub struct NoBarrier;
+
+impl WriteBarrier for NoBarrier {
+    unsafe fn on_reference_store(&mut self, _obj: *mut ObjectHeader, _offset: usize, _old: *mut ObjectHeader, _new: *mut ObjectHeader) {}
+}
\ No newline at end of file
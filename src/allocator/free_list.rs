@@ -0,0 +1,99 @@
+use std::mem::{align_of, size_of};
+use crate::allocator::object_allocator::ObjectHeader;
+
+// size classes of the segregated free lists, a free chunk is filed under the largest class that does
+// not exceed its size, so every chunk larger than the last class ends up in the last list.
+const SIZE_CLASSES: [usize; 10] = [16, 32, 48, 64, 96, 128, 256, 512, 1024, 2048];
+
+// the smallest hole a filler can cover, a compact filler takes a single word
+const MIN_FILLER_SIZE: usize = size_of::<usize>();
+
+// chunks smaller than this cannot hold any object, they are dropped until the next sweep finds them again
+pub const MIN_CHUNK_SIZE: usize = SIZE_CLASSES[0];
+
+#[derive(Copy, Clone, Debug)]
+pub struct FreeChunk {
+    pub start: *mut u8,
+    pub size: usize
+}
+
+pub struct FreeLists {
+    classes: Vec<Vec<FreeChunk>>,
+    free_bytes: usize
+}
+
+impl Default for FreeLists {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FreeLists {
+    pub fn new() -> Self {
+        FreeLists {
+            classes: vec![vec![]; SIZE_CLASSES.len()],
+            free_bytes: 0
+        }
+    }
+
+    fn class_of(size: usize) -> usize {
+        SIZE_CLASSES.iter().rposition(|class| *class <= size).unwrap_or(0)
+    }
+
+    pub fn push(&mut self, start: *mut u8, size: usize) {
+        if size < MIN_CHUNK_SIZE {
+            return;
+        }
+        self.classes[Self::class_of(size)].push(FreeChunk { start, size });
+        self.free_bytes += size;
+    }
+
+    // take a chunk that fits `size` bytes aligned to `align`, the search starts at the class of `size`
+    // since every chunk of a lower class is too small. The remainder of the chunk is filed again, the padding
+    // and the remainder are covered by fillers so that the block stays walkable.
+    pub unsafe fn take(&mut self, size: usize, align: usize) -> Option<*mut u8> {
+        for class in Self::class_of(size)..SIZE_CLASSES.len() {
+            let found = self.classes[class].iter().rposition(|chunk| Self::split(chunk, size, align).is_some());
+            if let Some(index) = found {
+                let chunk = self.classes[class].swap_remove(index);
+                self.free_bytes -= chunk.size;
+                let (ptr, rest, rest_size) = Self::split(&chunk, size, align).unwrap();
+                if ptr != chunk.start {
+                    ObjectHeader::write_filler(chunk.start.cast(), ptr as usize - chunk.start as usize);
+                }
+                if rest_size > 0 {
+                    ObjectHeader::write_filler(rest.cast(), rest_size);
+                }
+                self.push(rest, rest_size);
+                return Some(ptr);
+            }
+        }
+        None
+    }
+
+    // where an object of `size` bytes aligned to `align` goes in `chunk`, with the start and the size of the
+    // remainder, which begins at the word that follows the object. A chunk whose padding or remainder could not
+    // hold a filler is not split.
+    fn split(chunk: &FreeChunk, size: usize, align: usize) -> Option<(*mut u8, *mut u8, usize)> {
+        let padding = (!(chunk.start as usize) + 1) & (align - 1);
+        let end = chunk.start as usize + padding + size;
+        let rest = end + ((!end + 1) & (align_of::<usize>() - 1));
+        let rest_size = (chunk.start as usize + chunk.size).checked_sub(rest)?;
+        let fillable = |gap: usize| gap == 0 || gap >= MIN_FILLER_SIZE;
+        (fillable(padding) && fillable(rest_size))
+            .then_some(((chunk.start as usize + padding) as *mut u8, rest as *mut u8, rest_size))
+    }
+
+    pub fn clear(&mut self) {
+        self.classes.iter_mut().for_each(|class| class.clear());
+        self.free_bytes = 0;
+    }
+
+    pub fn free_bytes(&self) -> usize {
+        self.free_bytes
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = &FreeChunk> {
+        self.classes.iter().flatten()
+    }
+}
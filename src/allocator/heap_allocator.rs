@@ -1,3 +1,4 @@
+use crate::allocator::free_list::FreeLists;
 use crate::utils::errors::AllocatorError;
 use crate::utils::func_ext::identity_once;
 use linked_hash_map::LinkedHashMap;
@@ -59,6 +60,11 @@ pub struct HeapAllocator {
     pub committed_regions: LinkedHashMap<Layout, HeapBlock>,
     pub expand_callback: Box<dyn FnMut(HeapBlock)>,
     pub available: bool,
+    // holes handed back by a sweep, `alloc` only consults them when `use_free_lists` is set
+    pub free_lists: FreeLists,
+    pub use_free_lists: bool,
+    // when set, the allocator never commits more than this many bytes and reports `OutOfMemory` instead
+    pub max_size: Option<usize>,
 }
 
 impl Default for HeapAllocator {
@@ -74,6 +80,9 @@ impl HeapAllocator {
             committed_regions: LinkedHashMap::new(),
             expand_callback: Box::new(|_| ()),
             available: true,
+            free_lists: FreeLists::new(),
+            use_free_lists: false,
+            max_size: None,
         }
     }
 
@@ -83,9 +92,27 @@ impl HeapAllocator {
             committed_regions: LinkedHashMap::new(),
             expand_callback: callback,
             available: true,
+            free_lists: FreeLists::new(),
+            use_free_lists: false,
+            max_size: None,
         }
     }
 
+    pub fn new_bounded(max_size: usize) -> Self {
+        HeapAllocator {
+            max_size: Some(max_size),
+            ..Self::new()
+        }
+    }
+
+    // a single block of exactly `size` bytes that never grows
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn new_fixed(size: usize) -> Result<Self, AllocatorError> {
+        let mut allocator = Self::new_bounded(size);
+        allocator.commit(size)?;
+        Ok(allocator)
+    }
+
     pub fn block_index(&self, block: &HeapBlock) -> Option<usize> {
         self.committed_regions
             .iter()
@@ -118,12 +145,29 @@ impl HeapAllocator {
         if new_layout_size < desired_size {
             new_layout_size = (desired_size + ((!desired_size + 1) & (align - 1))) * EXPAND_FACTOR;
         }
+        if let Some(max_size) = self.max_size {
+            new_layout_size = new_layout_size.min(max_size.saturating_sub(self.size));
+            if new_layout_size < desired_size + align {
+                return Err(AllocatorError::OutOfMemory);
+            }
+        }
+        self.commit(new_layout_size)
+    }
+
+    unsafe fn commit(&mut self, new_layout_size: usize) -> Result<(), AllocatorError> {
+        // allocating a zero-sized layout is undefined behaviour
+        if new_layout_size == 0 {
+            return Err(AllocatorError::FailedToCreateLayout);
+        }
         let new_layout = match Layout::array::<u8>(new_layout_size) {
             Ok(l) => l,
             Err(_) => return Err(AllocatorError::FailedToCreateLayout),
         };
-        self.size += new_layout.size();
         let ptr = alloc::alloc_zeroed(new_layout);
+        if ptr.is_null() {
+            return Err(AllocatorError::OutOfMemory);
+        }
+        self.size += new_layout.size();
         let region = HeapBlock {
             start: ptr,
             unallocated_start: ptr,
@@ -146,6 +190,10 @@ impl HeapAllocator {
             return Err(AllocatorError::AllocatorClosed);
         }
 
+        if self.use_free_lists && let Some(ptr) = self.free_lists.take(size, align) {
+            return Ok(ptr);
+        }
+
         // find the first region that has enough space
         let first = self
             .committed_regions
@@ -174,11 +222,20 @@ impl HeapAllocator {
         self.committed_regions
             .iter()
             .map(|(_, tracker)| tracker.allocated_size())
-            .sum()
+            .sum::<usize>() - self.free_lists.free_bytes()
+    }
+
+    // forget every allocation, the committed blocks are kept for reuse
+    pub fn reset(&mut self) {
+        self.committed_regions.iter_mut().for_each(|(_, block)| block.unallocated_start = block.start);
+        self.free_lists.clear();
     }
 
     #[allow(clippy::missing_safety_doc)]
     pub unsafe fn free(&mut self) {
+        if !self.available {
+            return;
+        }
         for (layout, tracker) in self.committed_regions.iter() {
             alloc::dealloc(tracker.start, *layout);
         }
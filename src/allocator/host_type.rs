@@ -0,0 +1,102 @@
+use std::sync::Arc;
+use linked_hash_map::LinkedHashMap;
+use crate::allocator::value::Value;
+use crate::utils::errors::AllocatorError;
+use crate::utils::func_ext::OptionExt;
+use crate::vm_types::type_info::*;
+use crate::vm_types::type_tokens;
+
+pub use mem_allocator_derive::HostType;
+
+// A Rust type with a heap representation, see `ObjectAllocator::write_host` and `read_host`. `#[derive(HostType)]`
+// maps a struct with named fields to a record, a tuple or unit struct to a product and an enum to a sum whose
+// cases are the products of the fields of its variants. Every field has to implement `HostType` as well.
+pub trait HostType: Sized {
+    fn type_info() -> Arc<dyn TypeInfo>;
+
+    fn to_value(&self) -> Value;
+
+    fn from_value(value: &Value) -> Result<Self, AllocatorError>;
+}
+
+fn mismatch(expected: &str, value: &Value) -> AllocatorError {
+    AllocatorError::TypeMismatch(format!("Expected {}, found {:?}", expected, value))
+}
+
+macro_rules! scalar_host_type {
+    ($rust:ty, $variant:ident, $token:expr) => {
+        impl HostType for $rust {
+            fn type_info() -> Arc<dyn TypeInfo> {
+                Arc::new($token)
+            }
+
+            fn to_value(&self) -> Value {
+                Value::$variant(*self)
+            }
+
+            fn from_value(value: &Value) -> Result<Self, AllocatorError> {
+                match value {
+                    Value::$variant(value) => Ok(*value),
+                    _ => Err(mismatch(stringify!($variant), value))
+                }
+            }
+        }
+    };
+}
+
+scalar_host_type!(u64, Nat, type_tokens::NAT);
+scalar_host_type!(i64, Int, type_tokens::INT);
+scalar_host_type!(f64, Double, type_tokens::DOUBLE);
+scalar_host_type!(char, Char, type_tokens::CHAR);
+scalar_host_type!(bool, Bool, type_tokens::BOOL);
+
+// The helpers the derived implementations are built on, they keep the derived code free of the types used to
+// describe records and sums.
+
+pub fn record_type(fields: Vec<(&str, Arc<dyn TypeInfo>)>) -> Arc<dyn TypeInfo> {
+    Arc::new(RecordType(Arc::new(fields.into_iter().map(|(name, field)| (name.to_string(), field)).collect())))
+}
+
+pub fn product_type(fields: Vec<Arc<dyn TypeInfo>>) -> Arc<dyn TypeInfo> {
+    Arc::new(ProductType(fields))
+}
+
+pub fn sum_type(cases: Vec<(&str, Vec<Arc<dyn TypeInfo>>)>) -> Arc<dyn TypeInfo> {
+    Arc::new(SumType(cases.into_iter().map(|(name, fields)| (name.to_string(), Arc::new(ProductType(fields)))).collect()))
+}
+
+pub fn record_value(fields: Vec<(&str, Value)>) -> Value {
+    Value::Record(fields.into_iter().map(|(name, value)| (name.to_string(), value)).collect::<LinkedHashMap<_, _>>())
+}
+
+pub fn record_field<'a>(value: &'a Value, name: &str) -> Result<&'a Value, AllocatorError> {
+    match value {
+        Value::Record(fields) => fields.get(name)
+            .to_result(|| AllocatorError::UnknownField(format!("No field {} in the record {:?}", name, value))),
+        _ => Err(mismatch("a record", value))
+    }
+}
+
+pub fn product_fields(value: &Value, count: usize) -> Result<&[Value], AllocatorError> {
+    match value {
+        Value::Product(fields) if fields.len() == count => Ok(fields),
+        Value::Product(_) => Err(AllocatorError::ProductSizeMismatch),
+        _ => Err(mismatch("a product", value))
+    }
+}
+
+// the case of a sum and its fields, the number of fields is checked once the case is known
+pub fn sum_case(value: &Value) -> Result<(&str, &[Value]), AllocatorError> {
+    match value {
+        Value::Sum(case, fields) => Ok((case, fields)),
+        _ => Err(mismatch("a sum", value))
+    }
+}
+
+pub fn case_fields(fields: &[Value], count: usize) -> Result<&[Value], AllocatorError> {
+    if fields.len() == count { Ok(fields) } else { Err(AllocatorError::ProductSizeMismatch) }
+}
+
+pub fn unknown_case(case: &str) -> AllocatorError {
+    AllocatorError::TypeMismatch(format!("Unknown case {}", case))
+}
@@ -1,2 +1,6 @@
-pub(crate) mod heap_allocator;
-pub(crate) mod object_allocator;
\ No newline at end of file
+pub mod barrier;
+pub mod free_list;
+pub mod heap_allocator;
+pub mod object_allocator;
+pub mod value;
+pub mod host_type;
\ No newline at end of file
@@ -1,352 +1,759 @@
-use std::{alloc, ptr};
-use std::alloc::Layout;
+use std::ptr;
 use std::any::Any;
 use std::mem::size_of;
 use std::sync::Arc;
 use linked_hash_map::LinkedHashMap;
+use crate::allocator::barrier::{NoBarrier, WriteBarrier};
 use crate::allocator::heap_allocator::HeapAllocator;
+use crate::allocator::host_type::HostType;
+use crate::allocator::value::Value;
 use crate::utils::errors::AllocatorError;
 use crate::utils::func_ext::OptionExt;
 use crate::utils::io::object_size;
+use crate::vm_types::layout::Layout;
 use crate::vm_types::type_info::*;
 use crate::vm_types::type_kind::TypeKind;
+use crate::vm_types::type_registry::TypeRegistry;
 use crate::vm_types::type_sig::TypeSig;
 use crate::vm_types::type_tokens;
 
 pub struct ObjectAllocator {
     pub allocator: HeapAllocator,
-    pub allocated_objects: Vec<*mut ObjectHeader>
+    pub allocated_objects: Vec<*mut ObjectHeader>,
+    // the types of the objects, the headers only store their index in the registry
+    pub types: Arc<TypeRegistry>,
+    // called with every object once it has been completely written
+    pub allocation_callback: AllocationCallback,
+    // run on every reference store into a live object, see `set_reference`
+    pub barrier: Box<dyn WriteBarrier>,
+    // write a single-word header for every object that fits in one, see `ObjectHeader::compact`
+    pub compact_headers: bool,
+    // objects of at least this many bytes are reserved by the hook instead of `allocator`, the hook registers
+    // them in the heap it allocates from
+    pub large_objects: Option<(usize, LargeObjectHook)>
 }
 
+// The full header takes three words. The fields are private since an object may start with a compact header
+// instead, they are read through `ObjectHeaderHelper` which decodes both.
 #[repr(C)]
 #[derive(Copy, Clone)]
 pub struct ObjectHeader {
-    pub type_sig: usize,
-    pub size: usize,
-    pub ptr_to_type_info: *mut dyn TypeInfo
+    type_sig: usize,
+    size: usize,
+    type_id: usize
 }
 
+// A compact header is a single word. Its top bit tells it apart from the first word of a full header, which is
+// a small type signature. Below the flag, from the top: the type signature (4 bits), a mark bit reserved for the
+// collectors, the age (4 bits), the size in words (22 bits) and the type id (32 bits). A forwarded compact
+// header keeps the flag and the signature, the new address takes the low 54 bits instead.
+const COMPACT_FLAG: usize = 1 << 63;
+const COMPACT_SIG_SHIFT: u32 = 59;
+const COMPACT_SIG_MASK: usize = 0xF;
+const COMPACT_AGE_SHIFT: u32 = 54;
+const COMPACT_AGE_MASK: usize = 0xF;
+const COMPACT_SIZE_SHIFT: u32 = 32;
+const COMPACT_SIZE_MASK: usize = (1 << 22) - 1;
+const COMPACT_TYPE_ID_MASK: usize = u32::MAX as usize;
+const COMPACT_ADDRESS_MASK: usize = (1 << 54) - 1;
+// the oldest age a compact header can hold
+pub const MAX_COMPACT_AGE: usize = COMPACT_AGE_MASK;
+
 impl ObjectHeader {
-    pub fn new(type_sig: usize, size: usize, ptr_to_type_info: *mut dyn TypeInfo) -> Self {
+    pub fn new(type_sig: usize, size: usize, type_id: usize) -> Self {
         ObjectHeader {
             type_sig,
             size,
-            ptr_to_type_info
+            type_id
+        }
+    }
+
+    // The word of a compact header, `None` if the size is not a whole number of words or if the size or the type
+    // id is too large to be packed. The largest type id stands for `TypeRegistry::UNTYPED`.
+    pub fn compact(type_sig: usize, size: usize, type_id: usize) -> Option<usize> {
+        let words = size / size_of::<usize>();
+        let type_id = if type_id == TypeRegistry::UNTYPED { COMPACT_TYPE_ID_MASK } else { type_id };
+        (size % size_of::<usize>() == 0 && type_sig <= COMPACT_SIG_MASK && words <= COMPACT_SIZE_MASK && type_id <= COMPACT_TYPE_ID_MASK)
+            .then_some(COMPACT_FLAG | (type_sig << COMPACT_SIG_SHIFT) | (words << COMPACT_SIZE_SHIFT) | type_id)
+    }
+
+    // Fill a hole of `size` bytes so that the heap stays walkable, any hole of at least a word can be filled.
+    pub unsafe fn write_filler(p: *mut ObjectHeader, size: usize) {
+        match ObjectHeader::compact(TypeSig::FILLER, size, TypeRegistry::UNTYPED) {
+            Some(word) => p.cast::<usize>().write(word),
+            None => p.write(ObjectHeader::new(TypeSig::FILLER, size, TypeRegistry::UNTYPED))
         }
     }
 
     pub fn type_sig_within_valid_range(i: usize) -> bool {
-        (TypeSig::INT..=TypeSig::SUM).contains(&i)
+        (TypeSig::NAT..=TypeSig::STRING).contains(&i)
     }
 }
 
 pub trait ObjectHeaderHelper {
     unsafe fn to_data_start<T>(&self) -> *mut T;
+
+    unsafe fn is_compact(&self) -> bool;
+
+    unsafe fn header_size(&self) -> usize;
+
+    unsafe fn type_sig(&self) -> usize;
+
+    // the size of the object, including its header
+    unsafe fn size(&self) -> usize;
+
+    // the id of the type in the registry of the heap
+    unsafe fn type_index(&self) -> usize;
+
+    // the number of collections survived, only compact headers keep it
+    unsafe fn age(&self) -> Option<usize>;
+
+    // does nothing on a full header, the age saturates at `MAX_COMPACT_AGE`
+    unsafe fn set_age(&self, age: usize);
+
+    // Replace the header by a `FORWARDED` header pointing to `new`, the rest of the object is left untouched.
+    unsafe fn forward_to(&self, new: *mut ObjectHeader);
+
+    unsafe fn forwarding_address(&self) -> *mut ObjectHeader;
 }
 
 impl ObjectHeaderHelper for *mut ObjectHeader {
     unsafe fn to_data_start<T>(&self) -> *mut T {
-        self.cast::<u8>().add(size_of::<ObjectHeader>()) as *mut T
+        self.cast::<u8>().add(self.header_size()) as *mut T
+    }
+
+    unsafe fn is_compact(&self) -> bool {
+        self.cast::<usize>().read() & COMPACT_FLAG != 0
+    }
+
+    unsafe fn header_size(&self) -> usize {
+        if self.is_compact() { size_of::<usize>() } else { size_of::<ObjectHeader>() }
+    }
+
+    unsafe fn type_sig(&self) -> usize {
+        let word = self.cast::<usize>().read();
+        if word & COMPACT_FLAG != 0 { (word >> COMPACT_SIG_SHIFT) & COMPACT_SIG_MASK } else { word }
+    }
+
+    unsafe fn size(&self) -> usize {
+        if self.is_compact() {
+            ((self.cast::<usize>().read() >> COMPACT_SIZE_SHIFT) & COMPACT_SIZE_MASK) * size_of::<usize>()
+        } else {
+            (**self).size
+        }
+    }
+
+    unsafe fn type_index(&self) -> usize {
+        if !self.is_compact() {
+            return (**self).type_id;
+        }
+        match self.cast::<usize>().read() & COMPACT_TYPE_ID_MASK {
+            COMPACT_TYPE_ID_MASK => TypeRegistry::UNTYPED,
+            type_id => type_id
+        }
+    }
+
+    unsafe fn age(&self) -> Option<usize> {
+        self.is_compact().then(|| (self.cast::<usize>().read() >> COMPACT_AGE_SHIFT) & COMPACT_AGE_MASK)
+    }
+
+    unsafe fn set_age(&self, age: usize) {
+        if self.is_compact() {
+            let word = self.cast::<usize>().read() & !(COMPACT_AGE_MASK << COMPACT_AGE_SHIFT);
+            self.cast::<usize>().write(word | (age.min(MAX_COMPACT_AGE) << COMPACT_AGE_SHIFT));
+        }
+    }
+
+    unsafe fn forward_to(&self, new: *mut ObjectHeader) {
+        if self.is_compact() {
+            self.cast::<usize>().write(COMPACT_FLAG | (TypeSig::FORWARDED << COMPACT_SIG_SHIFT) | (new as usize & COMPACT_ADDRESS_MASK));
+        } else {
+            (**self).type_sig = TypeSig::FORWARDED;
+            (**self).size = new as usize;
+        }
+    }
+
+    unsafe fn forwarding_address(&self) -> *mut ObjectHeader {
+        if self.is_compact() {
+            (self.cast::<usize>().read() & COMPACT_ADDRESS_MASK) as *mut ObjectHeader
+        } else {
+            (**self).size as *mut ObjectHeader
+        }
+    }
+}
+
+// addresses a field of a live object, records accept both their field names and their declaration order
+#[derive(Copy, Clone, Debug)]
+pub enum FieldRef<'a> {
+    Index(usize),
+    Name(&'a str)
+}
+
+impl From<usize> for FieldRef<'_> {
+    fn from(index: usize) -> Self {
+        FieldRef::Index(index)
     }
 }
 
-pub static USE_COMPACT_LAYOUT: bool = false;
+impl<'a> From<&'a str> for FieldRef<'a> {
+    fn from(name: &'a str) -> Self {
+        FieldRef::Name(name)
+    }
+}
+
+// The data of a sum value: the name of the selected case and the fields of that case.
+#[derive(Clone, Debug)]
+pub struct Variant {
+    pub case: String,
+    pub fields: Vec<Arc<dyn Any>>
+}
+
+// called with every newly allocated object, e.g. to shade it during an incremental marking
+pub type AllocationCallback = Box<dyn FnMut(*mut ObjectHeader)>;
+
+// reserves `size` bytes for a large object outside of the heap, see `ObjectAllocator::large_objects`
+pub type LargeObjectHook = Box<dyn FnMut(usize) -> Result<*mut u8, AllocatorError>>;
+
+impl Variant {
+    pub fn new(case: impl Into<String>, fields: Vec<Arc<dyn Any>>) -> Self {
+        Variant { case: case.into(), fields }
+    }
+}
+
+impl Default for ObjectAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl ObjectAllocator {
     pub fn new() -> Self {
         ObjectAllocator {
             allocator: HeapAllocator::new(),
-            allocated_objects: Vec::new()
+            allocated_objects: Vec::new(),
+            types: TypeRegistry::new(),
+            allocation_callback: Box::new(|_| ()),
+            barrier: Box::new(NoBarrier),
+            compact_headers: false,
+            large_objects: None
         }
     }
 
+    pub fn new_with_allocator(allocator: HeapAllocator) -> Self {
+        ObjectAllocator {
+            allocator,
+            allocated_objects: Vec::new(),
+            types: TypeRegistry::new(),
+            allocation_callback: Box::new(|_| ()),
+            barrier: Box::new(NoBarrier),
+            compact_headers: false,
+            large_objects: None
+        }
+    }
+
+    // The layout of the data of the objects, it comes with the type registry shared with the other heaps.
+    pub fn layout(&self) -> Layout {
+        self.types.layout()
+    }
+
+    // Only an empty heap can switch layouts, the objects already written would be misread. The heap gets a new
+    // type registry, heaps sharing the previous one have to be given the new one.
+    pub fn set_layout(&mut self, layout: Layout) -> Result<(), AllocatorError> {
+        if !self.allocated_objects.is_empty() {
+            return Err(AllocatorError::HeapNotEmpty);
+        }
+        self.types = TypeRegistry::with_layout(layout);
+        Ok(())
+    }
+
+    // Allocate an object with `data_size` bytes of data and write its header. The header is compact if the heap
+    // asks for it and the object fits in one, the object is then rounded up to a whole number of words.
+    unsafe fn alloc_object(&mut self, type_sig: usize, data_size: usize, type_id: usize) -> Result<*mut ObjectHeader, AllocatorError> {
+        let compact_size = (size_of::<usize>() + data_size).next_multiple_of(size_of::<usize>());
+        match ObjectHeader::compact(type_sig, compact_size, type_id).filter(|_| self.compact_headers) {
+            Some(word) => {
+                let p = self.alloc(compact_size)?.cast::<ObjectHeader>();
+                p.cast::<usize>().write(word);
+                Ok(p)
+            },
+            None => {
+                let size_required = object_size(data_size);
+                let p = self.alloc(size_required)?.cast::<ObjectHeader>();
+                p.write(ObjectHeader::new(type_sig, size_required, type_id));
+                Ok(p)
+            }
+        }
+    }
+
+    unsafe fn alloc(&mut self, size: usize) -> Result<*mut u8, AllocatorError> {
+        match &mut self.large_objects {
+            Some((min_size, hook)) if size >= *min_size => hook(size),
+            _ => self.allocator.alloc(size, size_of::<usize>())
+        }
+    }
+
+    fn is_large(&self, p: *mut ObjectHeader) -> bool {
+        self.large_objects.as_ref().is_some_and(|(min_size, _)| unsafe { p.size() } >= *min_size)
+    }
+
+    fn register(&mut self, p: *mut ObjectHeader) {
+        // a large object has already been registered by the hook that reserved it
+        if !self.is_large(p) {
+            self.allocated_objects.push(p);
+        }
+        (self.allocation_callback)(p);
+    }
+
     pub unsafe fn write_int(&mut self, value: i64) -> Result<*mut ObjectHeader, AllocatorError> {
-        let size_required = object_size(size_of::<i64>());
-        let p = self.allocator.alloc(size_required, size_of::<usize>())? as *mut ObjectHeader;
-        p.write(ObjectHeader::new(TypeSig::INT, size_required, &type_tokens::INT as *const IntType as usize as *mut IntType));
+        let p = self.alloc_object(TypeSig::INT, size_of::<i64>(), TypeRegistry::INT)?;
         p.to_data_start::<i64>().write(value);
-        self.allocated_objects.push(p);
+        self.register(p);
         Ok(p)
     }
 
     pub unsafe fn write_nat(&mut self, value: u64) -> Result<*mut ObjectHeader, AllocatorError> {
-        let size_required = object_size(size_of::<u64>());
-        let p = self.allocator.alloc(size_required, size_of::<usize>())? as *mut ObjectHeader;
-        p.write(ObjectHeader::new(TypeSig::NAT, size_required, &type_tokens::NAT as *const NatType as usize as *mut IntType));
+        let p = self.alloc_object(TypeSig::NAT, size_of::<u64>(), TypeRegistry::NAT)?;
         p.to_data_start::<u64>().write(value);
-        self.allocated_objects.push(p);
+        self.register(p);
         Ok(p)
     }
 
     pub unsafe fn write_reference(&mut self, value: usize, type_info: &ReferenceType) -> Result<*mut ObjectHeader, AllocatorError> {
-        let size_required = object_size(size_of::<u64>());
-        let p = self.allocator.alloc(size_required, size_of::<usize>())? as *mut ObjectHeader;
-        p.write(ObjectHeader::new(TypeSig::REFERENCE, size_required, self.heap_allocated_type_info(type_info) as *mut dyn TypeInfo));
+        let p = self.alloc_object(TypeSig::REFERENCE, size_of::<u64>(), self.types.intern(type_info))?;
+        p.to_data_start::<usize>().write(value);
+        self.register(p);
+        Ok(p)
+    }
+
+    pub unsafe fn write_weak_reference(&mut self, value: usize, type_info: &WeakReferenceType) -> Result<*mut ObjectHeader, AllocatorError> {
+        let p = self.alloc_object(TypeSig::WEAK_REFERENCE, size_of::<usize>(), self.types.intern(type_info))?;
         p.to_data_start::<usize>().write(value);
-        self.allocated_objects.push(p);
+        self.register(p);
         Ok(p)
     }
 
     pub unsafe fn write_double(&mut self, value: f64) -> Result<*mut ObjectHeader, AllocatorError> {
-        let size_required = object_size(size_of::<f64>());
-        let p = self.allocator.alloc(size_required, size_of::<usize>())? as *mut ObjectHeader;
-        p.write(ObjectHeader::new(TypeSig::DOUBLE, size_required, &type_tokens::DOUBLE as *const DoubleType as usize as *mut IntType));
+        let p = self.alloc_object(TypeSig::DOUBLE, size_of::<f64>(), TypeRegistry::DOUBLE)?;
         p.to_data_start::<f64>().write(value);
-        self.allocated_objects.push(p);
+        self.register(p);
         Ok(p)
     }
 
     pub unsafe fn write_char(&mut self, value: char) -> Result<*mut ObjectHeader, AllocatorError> {
-        let size_required = object_size(if USE_COMPACT_LAYOUT { 1 } else { 8 });
-        let p = self.allocator.alloc(size_required, size_of::<usize>())?.cast::<ObjectHeader>();
-        p.write(ObjectHeader::new(TypeSig::CHAR, size_required, &type_tokens::CHAR as *const CharType as usize as *mut IntType));
+        let p = self.alloc_object(TypeSig::CHAR, type_tokens::CHAR.size(self.layout()), TypeRegistry::CHAR)?;
         p.to_data_start::<char>().write(value);
-        self.allocated_objects.push(p);
+        self.register(p);
         Ok(p)
     }
 
     pub unsafe fn write_bool(&mut self, value: bool) -> Result<*mut ObjectHeader, AllocatorError> {
-        let size_required = object_size(if USE_COMPACT_LAYOUT { 1 } else { 8 });
-        let p = self.allocator.alloc(size_required, size_of::<usize>())?.cast::<ObjectHeader>();
-        p.write(ObjectHeader::new(TypeSig::BOOL, size_required, &type_tokens::BOOL as *const BoolType as usize as *mut IntType));
+        let p = self.alloc_object(TypeSig::BOOL, type_tokens::BOOL.size(self.layout()), TypeRegistry::BOOL)?;
         p.to_data_start::<bool>().write(value);
-        self.allocated_objects.push(p);
+        self.register(p);
+        Ok(p)
+    }
+
+    // The byte length is written before the UTF-8 bytes, there is no terminating nul.
+    pub unsafe fn write_string(&mut self, value: &str) -> Result<*mut ObjectHeader, AllocatorError> {
+        let p = self.alloc_object(TypeSig::STRING, size_of::<usize>() + value.len(), TypeRegistry::STRING)?;
+        p.to_data_start::<usize>().write(value.len());
+        ptr::copy_nonoverlapping(value.as_ptr(), p.to_data_start::<u8>().add(size_of::<usize>()), value.len());
+        self.register(p);
         Ok(p)
     }
 
+    // The string borrows the object, it is only valid until the next collection.
+    pub unsafe fn read_string<'a>(&self, p: *mut ObjectHeader) -> Result<&'a str, AllocatorError> {
+        if p.type_sig() != TypeSig::STRING {
+            return Err(AllocatorError::TypeMismatch(format!("The object at {:?} is not a string", p)));
+        }
+        let bytes = std::slice::from_raw_parts(p.to_data_start::<u8>().add(size_of::<usize>()), *p.to_data_start::<usize>());
+        std::str::from_utf8(bytes).map_err(|error| AllocatorError::ReadObjectFailed(format!("Invalid UTF-8 in the string at {:?}: {}", p, error)))
+    }
+
     // noinspection ALL
     pub unsafe fn write_record(&mut self, data: &LinkedHashMap<String, Arc<dyn Any>>, type_info: &RecordType) -> Result<*mut ObjectHeader, AllocatorError> {
-        let size_required = object_size(type_info.size());
-        let p = self.allocator.alloc(size_required, size_of::<usize>())?.cast::<ObjectHeader>();
-        p.write(ObjectHeader::new(TypeSig::RECORD, size_required, self.heap_allocated_type_info(type_info) as *mut dyn TypeInfo));
-        for (name, field) in type_info.0.iter() {
-            let field_ptr = p.to_data_start::<u8>().add(type_info.alignment_table()[name]);
-            // NOTE: the cast to u8 is necessary because the pointer arithmetic is done in bytes
-            // if this is not done, the pointer arithmetic will be done in the size of the usize,
-            // that is, a two byte alignment now becomes a 16 byte alignment. A BIG LEAP FORWARD!
-            match field.kind() {
-                TypeKind::Nat => {
-                    let nat = data[name].downcast_ref::<u64>()
-                        .to_result(|| AllocatorError::FailedToReadData(format!("Failed to read data for field {} at {:?}", name, field_ptr)))?;
-                    (field_ptr as *mut u64).write(*nat);
-                },
-                TypeKind::Int => {
-                    let int = data[name].downcast_ref::<i64>()
-                        .to_result(|| AllocatorError::FailedToReadData(format!("Failed to read data for field {} at {:?}", name, field_ptr)))?;
-                    (field_ptr as *mut i64).write(*int);
-                },
-                TypeKind::Double => {
-                    let double = data[name].downcast_ref::<f64>()
-                        .to_result(|| AllocatorError::FailedToReadData(format!("Failed to read data for field {} at {:?}", name, field_ptr)))?;
-                    (field_ptr as *mut f64).write(*double);
-                },
-                TypeKind::Char => {
-                    let char = data[name].downcast_ref::<char>()
-                        .to_result(|| AllocatorError::FailedToReadData(format!("Failed to read data for field {} at {:?}", name, field_ptr)))?;
-                    (field_ptr as *mut char).write(*char);
-                },
-                TypeKind::Bool => {
-                    let bool = data[name].downcast_ref::<bool>()
-                        .to_result(|| AllocatorError::FailedToReadData(format!("Failed to read data for field {} at {:?}", name, field_ptr)))?;
-                    (field_ptr as *mut bool).write(*bool);
-                },
-                TypeKind::Reference => {
-                    let reference = data[name].downcast_ref::<usize>()
-                        .to_result(|| AllocatorError::FailedToReadData(format!("Failed to read data for field {} at {:?}", name, field_ptr)))?;
-                    (field_ptr as *mut usize).write(*reference);
-                },
-                _ => return Err(AllocatorError::ObjectAllocationFailed("Only primitive types are supported in Product Type".to_string()))
-            }
-        }
-        self.allocated_objects.push(p);
+        let p = self.alloc_object(TypeSig::RECORD, type_info.size(self.layout()), self.types.intern(type_info))?;
+        self.write_record_data(data, type_info, p.to_data_start())?;
+        self.register(p);
         Ok(p)
     }
 
     // noinspection ALL
     pub unsafe fn write_product(&mut self, data: &[Arc<dyn Any>], type_info: &ProductType) -> Result<*mut ObjectHeader, AllocatorError> {
-        let size_required = object_size(type_info.size());
-        let p = self.allocator.alloc(size_required, size_of::<usize>())?.cast::<ObjectHeader>();
-        p.write(ObjectHeader::new(TypeSig::PRODUCT, size_required, self.heap_allocated_type_info(type_info) as *mut dyn TypeInfo));
-        self.write_product_data(data, type_info, &type_info.alignment_table(), p.to_data_start())?;
-        self.allocated_objects.push(p);
+        let p = self.alloc_object(TypeSig::PRODUCT, type_info.size(self.layout()), self.types.intern(type_info))?;
+        self.write_product_data(data, type_info, &type_info.alignment_table(self.layout()), p.to_data_start())?;
+        self.register(p);
         Ok(p)
     }
 
-    pub unsafe fn write_sum(&mut self, data: &[Arc<dyn Any>], type_info: &SumType) -> Result<*mut ObjectHeader, AllocatorError> {
-        let size_required = object_size(type_info.size());
-        let p = self.allocator.alloc(size_required, size_of::<usize>())?.cast::<ObjectHeader>();
-        p.write(ObjectHeader::new(TypeSig::SUM, size_required, self.heap_allocated_type_info(type_info) as *mut dyn TypeInfo));
-        self.write_product_data(data, type_info.0.get(&type_info.1).unwrap(), &type_info.alignment_table(), p.to_data_start())?;
-        self.allocated_objects.push(p);
+    // The object is as large as the largest case of the sum, so that `set_variant` never has to move it.
+    pub unsafe fn write_sum(&mut self, case: &str, data: &[Arc<dyn Any>], type_info: &SumType) -> Result<*mut ObjectHeader, AllocatorError> {
+        let tag = Self::tag_of(type_info, case)?;
+        let p = self.alloc_object(TypeSig::SUM, type_info.size(self.layout()), self.types.intern(type_info))?;
+        self.write_sum_data(tag, data, type_info, p.to_data_start())?;
+        self.register(p);
         Ok(p)
     }
 
+    pub unsafe fn read_tag(&self, p: *mut ObjectHeader) -> Result<usize, AllocatorError> {
+        if p.type_sig() != TypeSig::SUM {
+            return Err(AllocatorError::TypeMismatch(format!("The object at {:?} is not a sum", p)));
+        }
+        Ok(*p.to_data_start::<usize>())
+    }
+
+    // Switch a live sum to another case in place. The references of the previous case are reported to the barrier
+    // as overwritten with null, the references of the new case as stored into null slots.
+    pub unsafe fn set_variant(&mut self, p: *mut ObjectHeader, case: &str, data: &[Arc<dyn Any>]) -> Result<(), AllocatorError> {
+        self.read_tag(p)?;
+        let sum_type = self.type_of(p)?;
+        let sum_type = downcast::<SumType>(&sum_type)?;
+        let tag = Self::tag_of(sum_type, case)?;
+        if sum_type.case(tag).unwrap().1.0.len() != data.len() {
+            return Err(AllocatorError::ProductSizeMismatch);
+        }
+        let types = self.types.clone();
+        let references = |p: *mut ObjectHeader| -> Result<Vec<(*mut ObjectHeader, usize)>, AllocatorError> {
+            let strong = crate::gc::reachability::pointers(&types, p);
+            let weak = crate::gc::reachability::weak_pointers(&types, p);
+            strong.and_then(|strong| weak.map(|weak| strong.into_iter().chain(weak).collect()))
+                .map_err(|error| AllocatorError::ReadObjectFailed(format!("{:?}", error)))
+        };
+        for (old, offset) in references(p)? {
+            self.barrier.on_reference_store(p, offset, old, ptr::null_mut());
+        }
+        self.write_sum_data(tag, data, sum_type, p.to_data_start())?;
+        for (new, offset) in references(p)? {
+            self.barrier.on_reference_store(p, offset, ptr::null_mut(), new);
+        }
+        Ok(())
+    }
+
+    fn tag_of(type_info: &SumType, case: &str) -> Result<usize, AllocatorError> {
+        type_info.tag_of(case).to_result(|| AllocatorError::ObjectAllocationFailed(format!("Unknown case {} of the sum {}", case, type_info.name())))
+    }
+
+    // The tag is written first, the fields of the case follow it at the offsets given by the sum.
+    unsafe fn write_sum_data(&mut self, tag: usize, data: &[Arc<dyn Any>], type_info: &SumType, data_ptr: *mut u8) -> Result<(), AllocatorError> {
+        let (_, case) = type_info.case(tag).to_result(|| AllocatorError::ObjectAllocationFailed(format!("Unknown tag {} of the sum {}", tag, type_info.name())))?;
+        self.write_product_data(data, case, &type_info.alignment_table(tag, self.layout()), data_ptr)?;
+        data_ptr.cast::<usize>().write_unaligned(tag);
+        Ok(())
+    }
+
+    unsafe fn read_sum_data(&self, type_info: &SumType, data_ptr: *mut u8) -> Result<Variant, AllocatorError> {
+        let tag = data_ptr.cast::<usize>().read_unaligned();
+        let (name, case) = type_info.case(tag).to_result(|| AllocatorError::ReadObjectFailed(format!("Invalid tag {} of the sum {} at {:?}", tag, type_info.name(), data_ptr)))?;
+        let offsets = self.types.nested_offsets(type_info, tag).to_result(|| AllocatorError::ReadObjectFailed(format!("Invalid tag {} of the sum {} at {:?}", tag, type_info.name(), data_ptr)))?;
+        Ok(Variant::new(name.clone(), self.read_product(&case.0, &offsets, data_ptr)?))
+    }
+
+    // The length is written before the elements, the elements are laid out like the fields of a product whose
+    // fields all have the element type.
+    pub unsafe fn write_array(&mut self, elements: &[Arc<dyn Any>], type_info: &ArrayType) -> Result<*mut ObjectHeader, AllocatorError> {
+        let p = self.alloc_object(TypeSig::ARRAY, type_info.data_size(elements.len(), self.layout()), self.types.intern(type_info))?;
+        p.to_data_start::<usize>().write(elements.len());
+        let offsets = (0..elements.len()).map(|index| type_info.element_offset(index, self.layout())).collect::<Vec<_>>();
+        self.write_product_data(elements, &ProductType(vec![type_info.0.clone(); elements.len()]), &offsets, p.to_data_start())?;
+        self.register(p);
+        Ok(p)
+    }
+
+    pub unsafe fn array_length(&self, p: *mut ObjectHeader) -> Result<usize, AllocatorError> {
+        if p.type_sig() != TypeSig::ARRAY {
+            return Err(AllocatorError::TypeMismatch(format!("The object at {:?} is not an array", p)));
+        }
+        Ok(*p.to_data_start::<usize>())
+    }
+
+    pub unsafe fn read_array(&self, p: *mut ObjectHeader) -> Result<Vec<Arc<dyn Any>>, AllocatorError> {
+        let length = self.array_length(p)?;
+        let array_type = self.type_of(p)?;
+        let array_type = downcast::<ArrayType>(&array_type)?;
+        let offsets = (0..length).map(|index| array_type.element_offset(index, self.layout())).collect::<Vec<_>>();
+        self.read_product(&vec![array_type.0.clone(); length], &offsets, p.to_data_start())
+    }
+
     // noinspection all
     unsafe fn write_product_data(&mut self, data: &[Arc<dyn Any>], type_info: &ProductType, alignments: &[usize], data_ptr: *mut u8) -> Result<(), AllocatorError> {
         if data.len() != alignments.len() {
             return Err(AllocatorError::ProductSizeMismatch);
         }
-        if data.is_empty() {
-            return Ok(());
-        }
         for (index, field) in type_info.0.iter().enumerate() {
-            let field_ptr = data_ptr.add(alignments[index]);
-            match field.kind() {
-                TypeKind::Nat => {
-                    let nat = data[index].downcast_ref::<u64>()
-                        .to_result(|| AllocatorError::FailedToReadData(format!("Failed to read data for {}-th field at {:?}", index, field_ptr)))?;
-                    (field_ptr as *mut u64).write(*nat);
-                },
-                TypeKind::Int => {
-                    let int = data[index].downcast_ref::<i64>()
-                        .to_result(|| AllocatorError::FailedToReadData(format!("Failed to read data for {}-th field at {:?}", index, field_ptr)))?;
-                    (field_ptr as *mut i64).write(*int);
-                },
-                TypeKind::Double => {
-                    let double = data[index].downcast_ref::<f64>()
-                        .to_result(|| AllocatorError::FailedToReadData(format!("Failed to read data for {}-th field at {:?}", index, field_ptr)))?;
-                    (field_ptr as *mut f64).write(*double);
-                },
-                TypeKind::Char => {
-                    let char = data[index].downcast_ref::<char>()
-                        .to_result(|| AllocatorError::FailedToReadData(format!("Failed to read data for {}-th field at {:?}", index, field_ptr)))?;
-                    (field_ptr as *mut char).write(*char);
-                },
-                TypeKind::Bool => {
-                    let bool = data[index].downcast_ref::<bool>()
-                        .to_result(|| AllocatorError::FailedToReadData(format!("Failed to read data for {}-th field at {:?}", index, field_ptr)))?;
-                    (field_ptr as *mut bool).write(*bool);
-                },
-                TypeKind::Reference => {
-                    let reference = data[index].downcast_ref::<usize>()
-                        .to_result(|| AllocatorError::FailedToReadData(format!("Failed to read data for {}-th field at {:?}", index, field_ptr)))?;
-                    (field_ptr as *mut usize).write(*reference);
-                },
-                _ => return Err(AllocatorError::ObjectAllocationFailed("Only primitive types are supported in Product Type".to_string()))
-            }
+            self.write_field(field, &data[index], data_ptr.add(alignments[index]), &|| format!("{}-th field", index))?;
+        }
+        Ok(())
+    }
+
+    unsafe fn write_record_data(&mut self, data: &LinkedHashMap<String, Arc<dyn Any>>, type_info: &RecordType, data_ptr: *mut u8) -> Result<(), AllocatorError> {
+        let alignment_table = type_info.alignment_table(self.layout());
+        for (name, field) in type_info.0.iter() {
+            // NOTE: the cast to u8 is necessary because the pointer arithmetic is done in bytes
+            // if this is not done, the pointer arithmetic will be done in the size of the usize,
+            // that is, a two byte alignment now becomes a 16 byte alignment. A BIG LEAP FORWARD!
+            let field_ptr = data_ptr.add(alignment_table[name]);
+            let value = data.get(name)
+                .to_result(|| AllocatorError::FailedToReadData(format!("Missing data for field {}", name)))?;
+            self.write_field(field, value, field_ptr, &|| format!("field {}", name))?;
+        }
+        Ok(())
+    }
+
+    // Write a single field at `field_ptr`. Nested products, records and sums are written inline with the same
+    // layout as a standalone object of their type, their data is given like the data of `allocate_general`.
+    // noinspection all
+    unsafe fn write_field(&mut self, field: &Arc<dyn TypeInfo>, value: &Arc<dyn Any>, field_ptr: *mut u8, describe: &dyn Fn() -> String) -> Result<(), AllocatorError> {
+        let failed = || AllocatorError::FailedToReadData(format!("Failed to read data for {} at {:?}", describe(), field_ptr));
+        match field.kind() {
+            TypeKind::Nat => (field_ptr as *mut u64).write(*value.downcast_ref::<u64>().to_result(failed)?),
+            TypeKind::Int => (field_ptr as *mut i64).write(*value.downcast_ref::<i64>().to_result(failed)?),
+            TypeKind::Double => (field_ptr as *mut f64).write(*value.downcast_ref::<f64>().to_result(failed)?),
+            TypeKind::Char => (field_ptr as *mut char).write(*value.downcast_ref::<char>().to_result(failed)?),
+            TypeKind::Bool => (field_ptr as *mut bool).write(*value.downcast_ref::<bool>().to_result(failed)?),
+            TypeKind::Reference | TypeKind::WeakReference => (field_ptr as *mut usize).write(*value.downcast_ref::<usize>().to_result(failed)?),
+            TypeKind::Product => {
+                let product_type = field.as_any().downcast_ref::<ProductType>().to_result(failed)?;
+                let data = value.downcast_ref::<Vec<Arc<dyn Any>>>().to_result(failed)?;
+                self.write_product_data(data, product_type, &product_type.alignment_table(self.layout()), field_ptr)?
+            },
+            TypeKind::Record => {
+                let record_type = field.as_any().downcast_ref::<RecordType>().to_result(failed)?;
+                let data = value.downcast_ref::<LinkedHashMap<String, Arc<dyn Any>>>().to_result(failed)?;
+                self.write_record_data(data, record_type, field_ptr)?
+            },
+            TypeKind::Sum => {
+                let sum_type = field.as_any().downcast_ref::<SumType>().to_result(failed)?;
+                let variant = value.downcast_ref::<Variant>().to_result(failed)?;
+                self.write_sum_data(Self::tag_of(sum_type, &variant.case)?, &variant.fields, sum_type, field_ptr)?
+            },
+            TypeKind::Array | TypeKind::String =>
+                return Err(AllocatorError::ObjectAllocationFailed(format!("The {} has a variable size, it cannot be inlined", describe())))
+        }
+        Ok(())
+    }
+
+    // Resolve a field of a live object to its type and its offset relative to the data start. Scalar and
+    // reference objects have a single field at index 0. The offsets come from the registry, which computes the
+    // alignment table of a type once.
+    pub unsafe fn field_slot(&self, p: *mut ObjectHeader, field: FieldRef) -> Result<(Arc<dyn TypeInfo>, usize), AllocatorError> {
+        let type_info = self.type_of(p)?;
+        let unknown = || AllocatorError::UnknownField(format!("No field {:?} in the object at {:?}", field, p));
+        let offset = |index: usize, tag: usize| self.types.field_offsets(p.type_index(), tag).and_then(|offsets| offsets.get(index).copied()).to_result(unknown);
+        match (p.type_sig(), field) {
+            (TypeSig::PRODUCT, FieldRef::Index(index)) => {
+                let product_type = downcast::<ProductType>(&type_info)?;
+                let ty = product_type.0.get(index).to_result(unknown)?;
+                Ok((ty.clone(), offset(index, 0)?))
+            },
+            (TypeSig::SUM, FieldRef::Index(index)) => {
+                let sum_type = downcast::<SumType>(&type_info)?;
+                let tag = self.read_tag(p)?;
+                let ty = sum_type.case(tag).to_result(unknown)?.1.0.get(index).to_result(unknown)?;
+                Ok((ty.clone(), offset(index, tag)?))
+            },
+            (TypeSig::RECORD, _) => {
+                let record_type = downcast::<RecordType>(&type_info)?;
+                let index = match field {
+                    FieldRef::Index(index) => index,
+                    FieldRef::Name(name) => self.types.field_index(p.type_index(), name).to_result(unknown)?
+                };
+                let ty = record_type.0.values().nth(index).to_result(unknown)?;
+                Ok((ty.clone(), offset(index, 0)?))
+            },
+            (TypeSig::ARRAY, FieldRef::Index(index)) => {
+                let array_type = downcast::<ArrayType>(&type_info)?;
+                if index >= self.array_length(p)? {
+                    return Err(unknown());
+                }
+                Ok((array_type.0.clone(), array_type.element_offset(index, self.layout())))
+            },
+            (TypeSig::REFERENCE | TypeSig::WEAK_REFERENCE | TypeSig::NAT | TypeSig::INT | TypeSig::DOUBLE | TypeSig::CHAR | TypeSig::BOOL, FieldRef::Index(0)) =>
+                Ok((type_info, 0)),
+            _ => Err(unknown())
+        }
+    }
+
+    // Read a single field of a live object without reading the rest of it, nested aggregates are read whole.
+    pub unsafe fn get_field<'a>(&self, p: *mut ObjectHeader, field: impl Into<FieldRef<'a>>) -> Result<Value, AllocatorError> {
+        let (ty, offset) = self.field_slot(p, field.into())?;
+        let data = self.read_field(&ty, p.to_data_start::<u8>().add(offset))?;
+        Value::from_data(&data, ty.as_ref())
+    }
+
+    // noinspection ALL
+    pub unsafe fn set_field<'a>(&mut self, p: *mut ObjectHeader, field: impl Into<FieldRef<'a>>, value: &Arc<dyn Any>) -> Result<(), AllocatorError> {
+        let field = field.into();
+        let (ty, offset) = self.field_slot(p, field)?;
+        let field_ptr = p.to_data_start::<u8>().add(offset);
+        let mismatch = || AllocatorError::TypeMismatch(format!("Expected a value of type {} for the field {:?}", ty.name(), field));
+        match ty.kind() {
+            TypeKind::Nat => (field_ptr as *mut u64).write(*value.downcast_ref::<u64>().to_result(mismatch)?),
+            TypeKind::Int => (field_ptr as *mut i64).write(*value.downcast_ref::<i64>().to_result(mismatch)?),
+            TypeKind::Double => (field_ptr as *mut f64).write(*value.downcast_ref::<f64>().to_result(mismatch)?),
+            TypeKind::Char => (field_ptr as *mut char).write(*value.downcast_ref::<char>().to_result(mismatch)?),
+            TypeKind::Bool => (field_ptr as *mut bool).write(*value.downcast_ref::<bool>().to_result(mismatch)?),
+            TypeKind::Reference | TypeKind::WeakReference => {
+                let target = *value.downcast_ref::<usize>().to_result(mismatch)?;
+                self.store_reference(p, offset, target as *mut ObjectHeader)
+            },
+            _ => return Err(AllocatorError::ObjectAllocationFailed("Only primitive fields can be updated".to_string()))
         }
         Ok(())
     }
 
-    unsafe fn heap_allocated_type_info<T: TypeInfo + Clone>(&self, product_type: &T) -> *mut T {
-        let type_info_layout = Layout::new::<T>();
-        let memory = alloc::alloc_zeroed(type_info_layout);
-        let type_info_ptr = memory as *mut T;
-        type_info_ptr.write(product_type.clone());
-        type_info_ptr
+    pub unsafe fn set_reference<'a>(&mut self, p: *mut ObjectHeader, field: impl Into<FieldRef<'a>>, target: *mut ObjectHeader) -> Result<(), AllocatorError> {
+        let field = field.into();
+        let (ty, offset) = self.field_slot(p, field)?;
+        if !ty.kind().is_reference() {
+            return Err(AllocatorError::TypeMismatch(format!("The field {:?} of type {} is not a reference", field, ty.name())));
+        }
+        self.store_reference(p, offset, target);
+        Ok(())
+    }
+
+    unsafe fn store_reference(&mut self, p: *mut ObjectHeader, offset: usize, target: *mut ObjectHeader) {
+        let slot = p.to_data_start::<u8>().add(offset).cast::<*mut ObjectHeader>();
+        self.barrier.on_reference_store(p, offset, slot.read_unaligned(), target);
+        slot.write_unaligned(target);
+    }
+
+    pub unsafe fn type_of(&self, p: *mut ObjectHeader) -> Result<Arc<dyn TypeInfo>, AllocatorError> {
+        self.types.get(p.type_index())
+            .to_result(|| AllocatorError::ReadObjectFailed(format!("Unknown type id {} of the object at {:?}", p.type_index(), p)))
     }
 
     // noinspection ALL
     #[allow(clippy::type_complexity)]
     pub unsafe fn read_obj(&mut self, p: *mut ObjectHeader) -> Result<(Arc<dyn TypeInfo>, Arc<dyn Any>), AllocatorError> {
-        let header = &*p;
-        match header.type_sig {
-            TypeSig::INT =>
-                Ok((Arc::new(*(header.ptr_to_type_info.cast::<IntType>())), Arc::new(*p.to_data_start::<i64>()))),
-            TypeSig::NAT =>
-                Ok((Arc::new(*header.ptr_to_type_info.cast::<NatType>()), Arc::new(*p.to_data_start::<u64>()))),
-            TypeSig::REFERENCE =>
-                Ok((Arc::new(*header.ptr_to_type_info.cast::<ReferenceType>()), Arc::new(*p.to_data_start::<usize>()))),
-            TypeSig::DOUBLE =>
-                Ok((Arc::new(*header.ptr_to_type_info.cast::<DoubleType>()), Arc::new(*p.to_data_start::<f64>()))),
-            TypeSig::CHAR =>
-                Ok((Arc::new(*header.ptr_to_type_info.cast::<CharType>()), Arc::new(*p.to_data_start::<char>()))),
-            TypeSig::BOOL =>
-                Ok((Arc::new(*header.ptr_to_type_info.cast::<BoolType>()), Arc::new(*p.to_data_start::<bool>()))),
+        let type_info = self.type_of(p)?;
+        match p.type_sig() {
+            TypeSig::INT => Ok((type_info, Arc::new(*p.to_data_start::<i64>()))),
+            TypeSig::NAT => Ok((type_info, Arc::new(*p.to_data_start::<u64>()))),
+            TypeSig::REFERENCE | TypeSig::WEAK_REFERENCE => Ok((type_info, Arc::new(*p.to_data_start::<usize>()))),
+            TypeSig::DOUBLE => Ok((type_info, Arc::new(*p.to_data_start::<f64>()))),
+            TypeSig::CHAR => Ok((type_info, Arc::new(*p.to_data_start::<char>()))),
+            TypeSig::BOOL => Ok((type_info, Arc::new(*p.to_data_start::<bool>()))),
+            TypeSig::STRING => Ok((type_info, Arc::new(self.read_string(p)?.to_string()))),
             TypeSig::PRODUCT => {
-                let product_type = header.ptr_to_type_info as *const ProductType; // type data
-                let fields = &(*product_type).0;
-                let alignment_table = (*product_type).alignment_table();
-                let res = self.read_product(fields, &alignment_table, p)?;
-                Ok((Arc::new((*product_type).clone()), Arc::new(res)))
+                let product_type = downcast::<ProductType>(&type_info)?;
+                let res = self.read_product(&product_type.0, &product_type.alignment_table(self.layout()), p.to_data_start())?;
+                Ok((type_info, Arc::new(res)))
             },
             TypeSig::RECORD => {
-                let record_type = header.ptr_to_type_info as *const RecordType; // type data
-                let fields = &(*record_type).0;
-                let alignment_table = (*record_type).alignment_table();
-                let mut map = LinkedHashMap::<String, Arc<dyn Any>>::new();
-                for (name, field) in fields.iter() { // data fields
-                    // NOTE: the cast to u8 is necessary because the pointer arithmetic is done in bytes
-                    // if this is not done, the pointer arithmetic will be done in the size of the usize,
-                    // that is, a two byte alignment now becomes a 16 byte alignment. A BIG LEAP FORWARD!
-                    let field_ptr = p.to_data_start::<u8>().add(alignment_table[name]);
-                    let value: Arc<dyn Any> = match field.kind() {
-                        TypeKind::Nat => Arc::new(ptr::read_unaligned(field_ptr.cast::<u64>())),
-                        TypeKind::Int => Arc::new(ptr::read_unaligned(field_ptr.cast::<i64>())),
-                        TypeKind::Double => Arc::new(ptr::read_unaligned(field_ptr.cast::<f64>())),
-                        TypeKind::Char => Arc::new(ptr::read_unaligned(field_ptr) as char),
-                        TypeKind::Bool => Arc::new(ptr::read_unaligned(field_ptr.cast::<bool>())),
-                        TypeKind::Reference => Arc::new(ptr::read_unaligned(field_ptr.cast::<usize>())),
-                        _ => return Err(AllocatorError::ObjectAllocationFailed("Only primitive types are supported in Product Type".to_string()))
-                    };
-                    map.insert(name.clone(), value);
-                }
-                Ok((Arc::new((*record_type).clone()), Arc::new(map)))
+                let offsets = self.types.field_offsets(p.type_index(), 0).to_result(|| AllocatorError::ReadObjectFailed(format!("Failed to read the record at {:?}", p)))?;
+                let map = self.read_record(downcast::<RecordType>(&type_info)?, &offsets, p.to_data_start())?;
+                Ok((type_info, Arc::new(map)))
             }
             TypeSig::SUM => {
-                let sum_type = header.ptr_to_type_info as *const SumType;
-                let cases = &(*sum_type).0;
-                let selected_case = &(*sum_type).1;
-                let product_type = cases.get(selected_case).unwrap();
-                let res = self.read_product(&(product_type.0), &product_type.alignment_table(), p)?;
-                Ok((Arc::new((*sum_type).clone()), Arc::new(res)))
+                let variant = self.read_sum_data(downcast::<SumType>(&type_info)?, p.to_data_start())?;
+                Ok((type_info, Arc::new(variant)))
+            }
+            TypeSig::ARRAY => {
+                let elements = self.read_array(p)?;
+                Ok((type_info, Arc::new(elements)))
             }
-            _ => Err(AllocatorError::ReadObjectFailed(format!("Unknown type signature {}", header.type_sig)))
+            _ => Err(AllocatorError::ReadObjectFailed(format!("Unknown type signature {}", p.type_sig())))
         }
     }
 
     // noinspection all
-    unsafe fn read_product(&self, fields: &[Arc<dyn TypeInfo>], alignment: &[usize], p: *mut ObjectHeader) -> Result<Vec<Arc<dyn Any>>, AllocatorError> {
-        let mut vec = Vec::<Arc<dyn Any>>::new();
-        for (i, field) in fields.iter().enumerate() { // data fields
-            // NOTE: the cast to u8 is necessary because the pointer arithmetic is done in bytes
-            // if this is not done, the pointer arithmetic will be done in the size of the usize,
-            // that is, a two byte alignment now becomes a 16 byte alignment. A BIG LEAP FORWARD!
-            let field_ptr = p.to_data_start::<u8>().add(alignment[i]);
-            let value: Arc<dyn Any> = match field.kind() {
-                TypeKind::Nat => Arc::new(ptr::read_unaligned(field_ptr.cast::<u64>())),
-                TypeKind::Int => Arc::new(ptr::read_unaligned(field_ptr.cast::<i64>())),
-                TypeKind::Double => Arc::new(ptr::read_unaligned(field_ptr.cast::<f64>())),
-                TypeKind::Char => Arc::new(ptr::read_unaligned(field_ptr) as char),
-                TypeKind::Bool => Arc::new(ptr::read_unaligned(field_ptr.cast::<bool>())),
-                TypeKind::Reference => Arc::new(ptr::read_unaligned(field_ptr.cast::<usize>())),
-                _ => return Err(AllocatorError::ObjectAllocationFailed("Only primitive types are supported in Product Type".to_string()))
-            };
-            vec.push(value);
+    unsafe fn read_product(&self, fields: &[Arc<dyn TypeInfo>], alignment: &[usize], data_ptr: *mut u8) -> Result<Vec<Arc<dyn Any>>, AllocatorError> {
+        fields.iter().enumerate()
+            .map(|(i, field)| self.read_field(field, data_ptr.add(alignment[i])))
+            .collect()
+    }
+
+    // `offsets` are the offsets of the fields in declaration order, as given by the registry
+    unsafe fn read_record(&self, type_info: &RecordType, offsets: &[usize], data_ptr: *mut u8) -> Result<LinkedHashMap<String, Arc<dyn Any>>, AllocatorError> {
+        let mut map = LinkedHashMap::<String, Arc<dyn Any>>::new();
+        for ((name, field), offset) in type_info.0.iter().zip(offsets) { // data fields
+            map.insert(name.clone(), self.read_field(field, data_ptr.add(*offset))?);
         }
-        Ok(vec)
+        Ok(map)
     }
 
-    pub unsafe fn allocate_general(&mut self, tuple: &(Arc<dyn TypeInfo>, Arc<dyn Any>)) -> Result<*mut ObjectHeader, AllocatorError> {
-        let (ty, data) = tuple;
-        match ty.kind() {
-            TypeKind::Nat => self.write_nat(*data.downcast_ref_unchecked::<u64>()),
-            TypeKind::Reference => self.write_reference(*data.downcast_ref_unchecked::<usize>(), ty.as_any().downcast_ref_unchecked::<ReferenceType>()),
-            TypeKind::Int => self.write_int(*data.downcast_ref_unchecked::<i64>()),
-            TypeKind::Double => self.write_double(*data.downcast_ref::<f64>()
-                .to_result(|| AllocatorError::FailedToReadData(format!("Failed to allocate data {:?}", data)))?),
-            TypeKind::Char => self.write_char(*data.downcast_ref::<char>()
-                .to_result(|| AllocatorError::FailedToReadData(format!("Failed to allocate data {:?}", data)))?),
-            TypeKind::Bool => self.write_bool(*data.downcast_ref::<bool>()
-                .to_result(|| AllocatorError::FailedToReadData(format!("Failed to allocate data {:?}", data)))?),
+    // Read a single field, nested aggregates are read back as the data `write_field` expects. Their offsets are
+    // cached by the registry, so reading the same nested type again does not recompute its alignment table.
+    // noinspection all
+    unsafe fn read_field(&self, field: &Arc<dyn TypeInfo>, field_ptr: *mut u8) -> Result<Arc<dyn Any>, AllocatorError> {
+        let failed = || AllocatorError::ReadObjectFailed(format!("Failed to reify the type info {} at {:?}", field.name(), field_ptr));
+        Ok(match field.kind() {
+            TypeKind::Nat => Arc::new(ptr::read_unaligned(field_ptr.cast::<u64>())),
+            TypeKind::Int => Arc::new(ptr::read_unaligned(field_ptr.cast::<i64>())),
+            TypeKind::Double => Arc::new(ptr::read_unaligned(field_ptr.cast::<f64>())),
+            TypeKind::Char => Arc::new(ptr::read_unaligned(field_ptr.cast::<char>())),
+            TypeKind::Bool => Arc::new(ptr::read_unaligned(field_ptr.cast::<bool>())),
+            TypeKind::Reference | TypeKind::WeakReference => Arc::new(ptr::read_unaligned(field_ptr.cast::<usize>())),
             TypeKind::Product => {
-                let prod = ty.as_any().downcast_ref::<ProductType>()
-                    .to_result(|| AllocatorError::FailedToReadData(format!("Failed to reify product type info {:?}", ty.as_any())))?;
-                self.write_product(data.downcast_ref::<Vec<Arc<dyn Any>>>()
-                                       .to_result(|| AllocatorError::FailedToReadData(format!("Failed to allocate data {:?}", data)))?, prod)
+                let product_type = field.as_any().downcast_ref::<ProductType>().to_result(failed)?;
+                Arc::new(self.read_product(&product_type.0, &self.types.nested_offsets(product_type, 0).to_result(failed)?, field_ptr)?)
             },
             TypeKind::Record => {
-                let record = ty.as_any().downcast_ref::<RecordType>()
-                    .to_result(|| AllocatorError::FailedToReadData(format!("Failed to reify record type info {:?}", ty.as_any())))?;
-                self.write_record(data.downcast_ref::<LinkedHashMap<String, Arc<dyn Any>>>()
-                                      .to_result(|| AllocatorError::FailedToReadData(format!("Failed to allocate data {:?}", data)))?, record)
+                let record_type = field.as_any().downcast_ref::<RecordType>().to_result(failed)?;
+                Arc::new(self.read_record(record_type, &self.types.nested_offsets(record_type, 0).to_result(failed)?, field_ptr)?)
             },
             TypeKind::Sum => {
-                let sum = ty.as_any().downcast_ref::<SumType>()
-                    .to_result(|| AllocatorError::FailedToReadData(format!("Failed to reify sum type info {:?}", ty.as_any())))?;
-                self.write_sum(data.downcast_ref::<Vec<Arc<dyn Any>>>()
-                                   .to_result(|| AllocatorError::FailedToReadData(format!("Failed to allocate data {:?}", data)))?, sum)
-            }
+                let sum_type = field.as_any().downcast_ref::<SumType>().to_result(failed)?;
+                Arc::new(self.read_sum_data(sum_type, field_ptr)?)
+            },
+            TypeKind::Array | TypeKind::String => return Err(failed())
+        })
+    }
+
+    pub unsafe fn allocate_general(&mut self, tuple: &(Arc<dyn TypeInfo>, Arc<dyn Any>)) -> Result<*mut ObjectHeader, AllocatorError> {
+        let (ty, data) = tuple;
+        match ty.kind() {
+            TypeKind::Nat => self.write_nat(*data_as::<u64>(data)?),
+            TypeKind::Reference => self.write_reference(*data_as::<usize>(data)?, downcast::<ReferenceType>(ty)?),
+            TypeKind::WeakReference => self.write_weak_reference(*data_as::<usize>(data)?, downcast::<WeakReferenceType>(ty)?),
+            TypeKind::Int => self.write_int(*data_as::<i64>(data)?),
+            TypeKind::Double => self.write_double(*data_as::<f64>(data)?),
+            TypeKind::Char => self.write_char(*data_as::<char>(data)?),
+            TypeKind::Bool => self.write_bool(*data_as::<bool>(data)?),
+            TypeKind::String => self.write_string(data_as::<String>(data)?),
+            TypeKind::Product => self.write_product(data_as::<Vec<Arc<dyn Any>>>(data)?, downcast::<ProductType>(ty)?),
+            TypeKind::Record => self.write_record(data_as::<LinkedHashMap<String, Arc<dyn Any>>>(data)?, downcast::<RecordType>(ty)?),
+            TypeKind::Sum => {
+                let variant = data_as::<Variant>(data)?;
+                self.write_sum(&variant.case, &variant.fields, downcast::<SumType>(ty)?)
+            },
+            TypeKind::Array => self.write_array(data_as::<Vec<Arc<dyn Any>>>(data)?, downcast::<ArrayType>(ty)?)
+        }
+    }
+
+    // Read an object back as a `Value`, its data is checked against the type recorded in its header.
+    pub unsafe fn read_value(&mut self, p: *mut ObjectHeader) -> Result<Value, AllocatorError> {
+        let (type_info, data) = self.read_obj(p)?;
+        Value::from_data(&data, type_info.as_ref())
+    }
+
+    pub unsafe fn write_host<T: HostType>(&mut self, value: &T) -> Result<*mut ObjectHeader, AllocatorError> {
+        self.write_value(&value.to_value(), &T::type_info())
+    }
+
+    // Read an object written from a `T`, or any object whose type is structurally equal to the type of `T`.
+    pub unsafe fn read_host<T: HostType>(&mut self, p: *mut ObjectHeader) -> Result<T, AllocatorError> {
+        let (expected, found) = (T::type_info(), self.type_of(p)?);
+        if expected.kind() != found.kind() || expected.name() != found.name() {
+            return Err(AllocatorError::TypeMismatch(format!("Expected an object of type {}, found {}", expected.name(), found.name())));
         }
+        T::from_value(&self.read_value(p)?)
     }
+
+    // Write `value` as a new object of type `type_info`. Nothing is allocated if the value does not match the type.
+    pub unsafe fn write_value(&mut self, value: &Value, type_info: &Arc<dyn TypeInfo>) -> Result<*mut ObjectHeader, AllocatorError> {
+        let data = value.to_data(type_info.as_ref())?;
+        self.allocate_general(&(type_info.clone(), data))
+    }
+}
+
+fn downcast<T: 'static>(type_info: &Arc<dyn TypeInfo>) -> Result<&T, AllocatorError> {
+    type_info.as_any().downcast_ref::<T>()
+        .to_result(|| AllocatorError::TypeMismatch(format!("Unexpected type {}", type_info.name())))
+}
+
+fn data_as<T: 'static>(data: &Arc<dyn Any>) -> Result<&T, AllocatorError> {
+    data.downcast_ref::<T>()
+        .to_result(|| AllocatorError::FailedToReadData(format!("Failed to allocate data {:?}", data)))
 }
\ No newline at end of file
@@ -0,0 +1,127 @@
+use std::any::Any;
+use std::sync::Arc;
+use linked_hash_map::LinkedHashMap;
+use crate::allocator::object_allocator::{ObjectHeader, Variant};
+use crate::utils::errors::AllocatorError;
+use crate::utils::func_ext::OptionExt;
+use crate::vm_types::type_info::*;
+use crate::vm_types::type_kind::TypeKind;
+
+// The data of an object, or of a field of an object, checked against its type. Nested products, records and sums
+// are values themselves, references are the addresses of their targets.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Nat(u64),
+    Int(i64),
+    Double(f64),
+    Char(char),
+    Bool(bool),
+    Ref(*mut ObjectHeader),
+    WeakRef(*mut ObjectHeader),
+    Str(String),
+    Product(Vec<Value>),
+    Record(LinkedHashMap<String, Value>),
+    Sum(String, Vec<Value>),
+    Array(Vec<Value>)
+}
+
+fn mismatch(type_info: &dyn TypeInfo, value: &Value) -> AllocatorError {
+    AllocatorError::TypeMismatch(format!("Expected a value of type {}, found {:?}", type_info.name(), value))
+}
+
+fn type_as<T: 'static>(type_info: &dyn TypeInfo) -> Result<&T, AllocatorError> {
+    type_info.as_any().downcast_ref::<T>()
+        .to_result(|| AllocatorError::TypeMismatch(format!("Unexpected type {}", type_info.name())))
+}
+
+impl Value {
+    // The data `allocate_general` and the other write paths expect for a value of type `type_info`. The whole
+    // value is checked before anything is written, a mismatch anywhere in it is reported as an error.
+    pub fn to_data(&self, type_info: &dyn TypeInfo) -> Result<Arc<dyn Any>, AllocatorError> {
+        let fields = |values: &[Value], types: &[Arc<dyn TypeInfo>]| -> Result<Vec<Arc<dyn Any>>, AllocatorError> {
+            if values.len() != types.len() {
+                return Err(AllocatorError::ProductSizeMismatch);
+            }
+            values.iter().zip(types).map(|(value, field)| value.to_data(field.as_ref())).collect()
+        };
+        Ok(match (type_info.kind(), self) {
+            (TypeKind::Nat, Value::Nat(value)) => Arc::new(*value),
+            (TypeKind::Int, Value::Int(value)) => Arc::new(*value),
+            (TypeKind::Double, Value::Double(value)) => Arc::new(*value),
+            (TypeKind::Char, Value::Char(value)) => Arc::new(*value),
+            (TypeKind::Bool, Value::Bool(value)) => Arc::new(*value),
+            (TypeKind::Reference, Value::Ref(target)) | (TypeKind::WeakReference, Value::WeakRef(target)) => Arc::new(*target as usize),
+            (TypeKind::String, Value::Str(value)) => Arc::new(value.clone()),
+            (TypeKind::Product, Value::Product(values)) => Arc::new(fields(values, &type_as::<ProductType>(type_info)?.0)?),
+            (TypeKind::Record, Value::Record(values)) => {
+                let record_type = type_as::<RecordType>(type_info)?;
+                if let Some(name) = values.keys().find(|name| !record_type.0.contains_key(*name)) {
+                    return Err(AllocatorError::UnknownField(format!("No field {} in the record {}", name, record_type.name())));
+                }
+                let mut data = LinkedHashMap::<String, Arc<dyn Any>>::new();
+                for (name, field) in record_type.0.iter() {
+                    let value = values.get(name)
+                        .to_result(|| AllocatorError::FailedToReadData(format!("Missing data for field {}", name)))?;
+                    data.insert(name.clone(), value.to_data(field.as_ref())?);
+                }
+                Arc::new(data)
+            },
+            (TypeKind::Sum, Value::Sum(case, values)) => {
+                let sum_type = type_as::<SumType>(type_info)?;
+                let (_, case_type) = sum_type.tag_of(case).and_then(|tag| sum_type.case(tag))
+                    .to_result(|| AllocatorError::TypeMismatch(format!("Unknown case {} of the sum {}", case, sum_type.name())))?;
+                Arc::new(Variant::new(case.clone(), fields(values, &case_type.0)?))
+            },
+            (TypeKind::Array, Value::Array(values)) => {
+                let element = &type_as::<ArrayType>(type_info)?.0;
+                Arc::new(values.iter().map(|value| value.to_data(element.as_ref())).collect::<Result<Vec<_>, _>>()?)
+            },
+            _ => return Err(mismatch(type_info, self))
+        })
+    }
+
+    // The value of data read back by `read_obj` for an object or a field of type `type_info`.
+    pub fn from_data(data: &Arc<dyn Any>, type_info: &dyn TypeInfo) -> Result<Value, AllocatorError> {
+        fn read<'a, T: 'static>(data: &'a Arc<dyn Any>, type_info: &dyn TypeInfo) -> Result<&'a T, AllocatorError> {
+            data.downcast_ref::<T>()
+                .to_result(|| AllocatorError::ReadObjectFailed(format!("Unexpected data for a value of type {}", type_info.name())))
+        }
+        let fields = |data: &[Arc<dyn Any>], types: &[Arc<dyn TypeInfo>]| -> Result<Vec<Value>, AllocatorError> {
+            data.iter().zip(types).map(|(data, field)| Value::from_data(data, field.as_ref())).collect()
+        };
+        Ok(match type_info.kind() {
+            TypeKind::Nat => Value::Nat(*read::<u64>(data, type_info)?),
+            TypeKind::Int => Value::Int(*read::<i64>(data, type_info)?),
+            TypeKind::Double => Value::Double(*read::<f64>(data, type_info)?),
+            TypeKind::Char => Value::Char(*read::<char>(data, type_info)?),
+            TypeKind::Bool => Value::Bool(*read::<bool>(data, type_info)?),
+            TypeKind::Reference => Value::Ref(*read::<usize>(data, type_info)? as *mut ObjectHeader),
+            TypeKind::WeakReference => Value::WeakRef(*read::<usize>(data, type_info)? as *mut ObjectHeader),
+            TypeKind::String => Value::Str(read::<String>(data, type_info)?.clone()),
+            TypeKind::Product => Value::Product(fields(read::<Vec<Arc<dyn Any>>>(data, type_info)?, &type_as::<ProductType>(type_info)?.0)?),
+            TypeKind::Record => {
+                let record_type = type_as::<RecordType>(type_info)?;
+                let data = read::<LinkedHashMap<String, Arc<dyn Any>>>(data, type_info)?;
+                let mut values = LinkedHashMap::new();
+                for (name, field) in record_type.0.iter() {
+                    let data = data.get(name)
+                        .to_result(|| AllocatorError::ReadObjectFailed(format!("Missing data for field {}", name)))?;
+                    values.insert(name.clone(), Value::from_data(data, field.as_ref())?);
+                }
+                Value::Record(values)
+            },
+            TypeKind::Sum => {
+                let sum_type = type_as::<SumType>(type_info)?;
+                let variant = read::<Variant>(data, type_info)?;
+                let (_, case_type) = sum_type.tag_of(&variant.case).and_then(|tag| sum_type.case(tag))
+                    .to_result(|| AllocatorError::ReadObjectFailed(format!("Unknown case {} of the sum {}", variant.case, sum_type.name())))?;
+                Value::Sum(variant.case.clone(), fields(&variant.fields, &case_type.0)?)
+            },
+            TypeKind::Array => {
+                let element = &type_as::<ArrayType>(type_info)?.0;
+                Value::Array(read::<Vec<Arc<dyn Any>>>(data, type_info)?.iter()
+                    .map(|data| Value::from_data(data, element.as_ref())).collect::<Result<_, _>>()?)
+            }
+        })
+    }
+}
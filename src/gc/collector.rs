@@ -0,0 +1,188 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use crate::allocator::object_allocator::{ObjectAllocator, ObjectHeader};
+use crate::gc::gc::{GarbageCollector, SweepMode};
+use crate::gc::generational::GenerationalCollector;
+use crate::gc::semispace::SemispaceCollector;
+use crate::utils::errors::AllocatorError;
+use crate::vm_types::layout::Layout;
+
+// Les statistiques cumulées d'un ramasse-miettes depuis sa création
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CollectorStats {
+    pub collections: usize,
+    // les bytes rendus par toutes les collections
+    pub bytes_reclaimed: usize,
+    // les bytes encore alloués après la dernière collection
+    pub live_bytes: usize,
+    // le temps passé dans toutes les collections
+    pub pause_time: Duration
+}
+
+impl CollectorStats {
+    pub fn record(&mut self, allocated_before: usize, allocated_after: usize, started: Instant) {
+        self.collections += 1;
+        self.bytes_reclaimed += allocated_before.saturating_sub(allocated_after);
+        self.live_bytes = allocated_after;
+        self.pause_time += started.elapsed();
+    }
+}
+
+// Un algorithme de ramasse-miettes. Les nouveaux objets sont alloués dans `heap`, les racines passées à
+// `collect` sont les seules adresses qui restent valables après la collection, les autres objets accessibles
+// sont peut-être déplacés.
+pub trait Collector {
+    // l'allocateur dans lequel les nouveaux objets sont alloués
+    fn heap(&mut self) -> &mut ObjectAllocator;
+
+    // les bytes alloués dans tous les espaces du ramasse-miettes
+    unsafe fn allocated(&self) -> usize;
+
+    // Trouver les objets accessibles depuis `roots` sans rien récupérer ni déplacer.
+    unsafe fn mark(&mut self, roots: &[*mut ObjectHeader]) -> HashSet<*mut ObjectHeader>;
+
+    // Récupérer les objets inaccessibles depuis `roots`, retourne les nouvelles adresses des racines.
+    unsafe fn collect(&mut self, roots: &mut [*mut ObjectHeader]) -> Result<HashMap<*mut ObjectHeader, *mut ObjectHeader>, AllocatorError>;
+
+    fn stats(&self) -> CollectorStats;
+
+    // Choisir la disposition des données de tous les espaces, qui doivent encore être vides.
+    fn set_layout(&mut self, layout: Layout) -> Result<(), AllocatorError> {
+        self.heap().set_layout(layout)
+    }
+
+    // le ramasse-miettes à bitmap sur lequel ce ramasse-miettes est construit, s'il y en a un, il donne accès
+    // au marquage incrémental et au marquage parallèle
+    fn garbage_collector(&self) -> Option<&Rc<RefCell<GarbageCollector>>> {
+        None
+    }
+}
+
+// L'algorithme choisi à la construction d'un tas
+#[derive(Copy, Clone, Debug)]
+pub enum CollectorKind {
+    // le "Compressor" de `gc.rs`, les objets vivants sont compactés
+    MarkCompact,
+    // le même marquage, suivi d'un balayage qui ne déplace aucun objet
+    MarkSweep(SweepMode),
+    Semispace { semispace_size: usize },
+    Generational { nursery_size: usize, promotion_threshold: usize }
+}
+
+impl CollectorKind {
+    pub unsafe fn build(self) -> Result<Box<dyn Collector>, AllocatorError> {
+        Ok(match self {
+            CollectorKind::MarkCompact => Box::new(MarkCompact::new()),
+            CollectorKind::MarkSweep(mode) => Box::new(MarkSweep::new(mode)),
+            CollectorKind::Semispace { semispace_size } => Box::new(SemispaceCollector::new(semispace_size)?),
+            CollectorKind::Generational { nursery_size, promotion_threshold } => Box::new(GenerationalCollector::new(nursery_size, promotion_threshold)?)
+        })
+    }
+}
+
+// `GarbageCollector` vit dans un `Rc<RefCell<..>>` puisque son callback d'agrandissement le référence, on prête
+// son allocateur directement: aucun autre emprunt n'est actif pendant que le tas alloue.
+#[allow(clippy::mut_from_ref)]
+unsafe fn heap_of(gc: &Rc<RefCell<GarbageCollector>>) -> &mut ObjectAllocator {
+    &mut (*gc.as_ptr()).heap
+}
+
+pub struct MarkCompact {
+    gc: Rc<RefCell<GarbageCollector>>,
+    stats: CollectorStats
+}
+
+impl MarkCompact {
+    pub unsafe fn new() -> Self {
+        MarkCompact { gc: GarbageCollector::new(), stats: CollectorStats::default() }
+    }
+}
+
+impl Collector for MarkCompact {
+    fn heap(&mut self) -> &mut ObjectAllocator {
+        unsafe { heap_of(&self.gc) }
+    }
+
+    unsafe fn allocated(&self) -> usize {
+        self.gc.borrow().heap.allocator.allocated()
+    }
+
+    unsafe fn mark(&mut self, roots: &[*mut ObjectHeader]) -> HashSet<*mut ObjectHeader> {
+        let mut gc = self.gc.borrow_mut();
+        gc.mark_living(&mut roots.to_vec());
+        gc.all_marked_bits().into_iter().collect()
+    }
+
+    unsafe fn collect(&mut self, roots: &mut [*mut ObjectHeader]) -> Result<HashMap<*mut ObjectHeader, *mut ObjectHeader>, AllocatorError> {
+        let (before, started) = (self.allocated(), Instant::now());
+        let forwarding = self.gc.borrow_mut().collect(roots);
+        self.stats.record(before, self.allocated(), started);
+        Ok(forwarding)
+    }
+
+    fn stats(&self) -> CollectorStats {
+        self.stats
+    }
+
+    fn garbage_collector(&self) -> Option<&Rc<RefCell<GarbageCollector>>> {
+        Some(&self.gc)
+    }
+}
+
+impl Drop for MarkCompact {
+    fn drop(&mut self) {
+        unsafe { self.gc.borrow_mut().heap.allocator.free() }
+    }
+}
+
+pub struct MarkSweep {
+    gc: Rc<RefCell<GarbageCollector>>,
+    mode: SweepMode,
+    stats: CollectorStats
+}
+
+impl MarkSweep {
+    pub unsafe fn new(mode: SweepMode) -> Self {
+        MarkSweep { gc: GarbageCollector::new(), mode, stats: CollectorStats::default() }
+    }
+}
+
+impl Collector for MarkSweep {
+    fn heap(&mut self) -> &mut ObjectAllocator {
+        unsafe { heap_of(&self.gc) }
+    }
+
+    unsafe fn allocated(&self) -> usize {
+        self.gc.borrow().heap.allocator.allocated()
+    }
+
+    unsafe fn mark(&mut self, roots: &[*mut ObjectHeader]) -> HashSet<*mut ObjectHeader> {
+        let mut gc = self.gc.borrow_mut();
+        gc.mark_living(&mut roots.to_vec());
+        gc.all_marked_bits().into_iter().collect()
+    }
+
+    unsafe fn collect(&mut self, roots: &mut [*mut ObjectHeader]) -> Result<HashMap<*mut ObjectHeader, *mut ObjectHeader>, AllocatorError> {
+        let (before, started) = (self.allocated(), Instant::now());
+        self.gc.borrow_mut().collect_non_moving(roots, self.mode);
+        self.stats.record(before, self.allocated(), started);
+        // aucun objet n'a bougé
+        Ok(roots.iter().map(|root| (*root, *root)).collect())
+    }
+
+    fn stats(&self) -> CollectorStats {
+        self.stats
+    }
+
+    fn garbage_collector(&self) -> Option<&Rc<RefCell<GarbageCollector>>> {
+        Some(&self.gc)
+    }
+}
+
+impl Drop for MarkSweep {
+    fn drop(&mut self) {
+        unsafe { self.gc.borrow_mut().heap.allocator.free() }
+    }
+}
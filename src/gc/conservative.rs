@@ -0,0 +1,50 @@
+use std::collections::HashSet;
+use std::mem::align_of;
+use std::ptr;
+use crate::allocator::object_allocator::{ObjectAllocator, ObjectHeader};
+
+// Le parcours conservatif des racines. Quand le code natif qui embarque le tas ne sait pas énumérer précisément
+// ses racines, on considère chaque mot de sa pile ou de sa copie des registres comme une adresse possible. Un mot
+// n'est retenu que s'il tombe dans un bloc réservé du tas et s'il est exactement le début d'un objet alloué: les
+// pointeurs intérieurs et les entiers qui ressemblent par hasard à une adresse du tas sont ignorés. Un entier qui
+// coïncide avec le début d'un objet le garde en vie à tort, c'est le prix du conservatisme.
+pub struct ObjectStarts {
+    starts: HashSet<usize>
+}
+
+impl ObjectStarts {
+    // la table des débuts d'objets, construite à partir des objets alloués de `heap`
+    pub fn new(heap: &ObjectAllocator) -> ObjectStarts {
+        ObjectStarts { starts: heap.allocated_objects.iter().map(|obj| *obj as usize).collect() }
+    }
+
+    pub unsafe fn is_object_start(&self, heap: &ObjectAllocator, word: usize) -> bool {
+        word % align_of::<usize>() == 0
+            && heap.allocator.get_block(word as *mut u8).is_some()
+            && self.starts.contains(&word)
+    }
+
+    // les objets désignés par `words`, chacun n'apparaît qu'une fois
+    pub unsafe fn find(&self, heap: &ObjectAllocator, words: impl IntoIterator<Item = usize>) -> Vec<*mut ObjectHeader> {
+        let mut seen = HashSet::new();
+        words.into_iter()
+            .filter(|word| self.is_object_start(heap, *word) && seen.insert(*word))
+            .map(|word| word as *mut ObjectHeader)
+            .collect()
+    }
+}
+
+// Parcourir les mots entre `start` et `end`, les bornes peuvent être données dans n'importe quel ordre puisque la
+// pile croît souvent vers le bas. Tous les mots de l'intervalle doivent être lisibles.
+pub unsafe fn scan_range(heap: &ObjectAllocator, start: *const usize, end: *const usize) -> Vec<*mut ObjectHeader> {
+    let (low, high) = if start <= end { (start, end) } else { (end, start) };
+    // le premier mot aligné de l'intervalle
+    let first = low.byte_add((!(low as usize) + 1) & (align_of::<usize>() - 1));
+    let count = (high as usize).saturating_sub(first as usize) / align_of::<usize>();
+    ObjectStarts::new(heap).find(heap, (0..count).map(|i| ptr::read_volatile(first.add(i))))
+}
+
+// Parcourir une copie des registres, ou n'importe quelle suite de mots
+pub unsafe fn scan_words(heap: &ObjectAllocator, words: &[usize]) -> Vec<*mut ObjectHeader> {
+    ObjectStarts::new(heap).find(heap, words.iter().copied())
+}
@@ -0,0 +1,55 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::allocator::object_allocator::ObjectHeader;
+
+// Une table d'éphémérons: chaque valeur n'est gardée en vie que tant que sa clé est accessible autrement que par
+// la table, une valeur qui référence sa propre clé ne la garde donc pas en vie. Les clés sont comparées par
+// identité, `GarbageCollector::collect` retire les entrées dont la clé est morte et rehache la table quand le
+// compactage déplace les clés. Les clés et les valeurs doivent appartenir au tas du ramasse-miettes qui a créé
+// la table, les entrées dont la clé vient d'un autre tas sont retirées à la première collection.
+#[derive(Default)]
+pub struct EphemeronTable {
+    entries: HashMap<*mut ObjectHeader, *mut ObjectHeader>
+}
+
+impl EphemeronTable {
+    pub fn new() -> Rc<RefCell<EphemeronTable>> {
+        Rc::new(RefCell::new(EphemeronTable::default()))
+    }
+
+    pub fn insert(&mut self, key: *mut ObjectHeader, value: *mut ObjectHeader) -> Option<*mut ObjectHeader> {
+        self.entries.insert(key, value)
+    }
+
+    pub fn get(&self, key: *mut ObjectHeader) -> Option<*mut ObjectHeader> {
+        self.entries.get(&key).copied()
+    }
+
+    pub fn remove(&mut self, key: *mut ObjectHeader) -> Option<*mut ObjectHeader> {
+        self.entries.remove(&key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (*mut ObjectHeader, *mut ObjectHeader)> + '_ {
+        self.entries.iter().map(|(key, value)| (*key, *value))
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(*mut ObjectHeader, *mut ObjectHeader) -> bool) {
+        self.entries.retain(|key, value| keep(*key, *value));
+    }
+
+    // les clés et les valeurs qui ne sont pas dans la table sont restées en place
+    pub fn forward(&mut self, forwarding: &HashMap<*mut ObjectHeader, *mut ObjectHeader>) {
+        self.entries = self.entries.iter()
+            .map(|(key, value)| (*forwarding.get(key).unwrap_or(key), *forwarding.get(value).unwrap_or(value)))
+            .collect();
+    }
+}
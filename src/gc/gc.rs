@@ -1,25 +1,95 @@
 use std::cell::RefCell;
-use std::collections::HashMap;
-use std::hash::{Hash, Hasher};
-use std::mem::{align_of, size_of};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+use std::mem::{align_of, replace, size_of};
 use std::ptr;
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
 use linked_hash_map::LinkedHashMap;
 use maplit::{hashmap, hashset};
+use crate::allocator::barrier::{NoBarrier, WriteBarrier};
 use crate::allocator::heap_allocator::HeapBlock;
 use crate::allocator::heap_allocator::HeapSpan;
-use crate::allocator::object_allocator::{ObjectAllocator, ObjectHeader, ObjectHeaderHelper};
+use crate::allocator::object_allocator::{AllocationCallback, ObjectAllocator, ObjectHeader, ObjectHeaderHelper};
+use crate::gc::conservative;
+use crate::gc::ephemeron::EphemeronTable;
+use crate::gc::reachability;
 use crate::gc::reachability::ObjectAllocatorExt;
+use crate::gc::roots::RootRegistry;
 use crate::utils::func_ext::OptionExt;
 use crate::utils::io::{bit_set, count_bits_set, count_bits_set_range};
 use crate::utils::iter_ext::IterExt;
-use crate::vm_types::type_info::{ProductType, TypeInfo};
+use crate::vm_types::type_info::TypeInfo;
+use crate::vm_types::type_registry::TypeRegistry;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum SweepMode {
+    // les objets morts sont rendus aux listes libres de `HeapAllocator`
+    FreeList,
+    // les objets morts sont remplacés par des objets de remplissage, le tas reste parcourable
+    Filler
+}
 
 pub struct GarbageCollector {
     pub heap: ObjectAllocator,
     // chaque bit répresente un début d'objet possible. i.e., un "word"
-    bitmap: Vec<Vec<u8>>,
-    size_of_living: HashMap<BitmapIndex, usize>
+    // les bytes sont atomiques afin que les threads du marquage parallèle puissent les mettre en même temps
+    bitmap: Vec<Vec<AtomicU8>>,
+    size_of_living: HashMap<BitmapIndex, usize>,
+    // le cycle de marquage incrémental en cours, s'il y en a un
+    marking: Option<IncrementalMarking>,
+    // le nombre de threads qui marquent le tas, le marquage est séquentiel s'il n'y en a qu'un
+    pub mark_threads: usize,
+    // les racines enregistrées, elles s'ajoutent aux racines passées à chaque collection
+    pub roots: Rc<RefCell<RootRegistry>>,
+    // les objets trouvés par le parcours conservatif depuis la dernière collection, ils sont des racines
+    // épinglées de la prochaine collection seulement
+    conservative_roots: Vec<*mut ObjectHeader>,
+    // les objets épinglés par `pin`, ils sont des racines et ne sont jamais déplacés par le compactage
+    pinned: HashSet<*mut ObjectHeader>,
+    // les finaliseurs déclarés, par nom de type
+    finalizers: HashMap<String, Finalizer>,
+    // les objets inaccessibles dont le finaliseur n'a pas encore été lancé, ils sont des racines
+    finalization_queue: VecDeque<*mut ObjectHeader>,
+    // les objets dont le finaliseur a été lancé, ils ne seront plus jamais finalisés
+    finalized: HashSet<*mut ObjectHeader>,
+    // les tables d'éphémérons créées par `new_ephemeron_table`, une table détruite est oubliée
+    ephemerons: Vec<Weak<RefCell<EphemeronTable>>>
+}
+
+// Un finaliseur reçoit l'objet mort, toujours lisible, il ne doit pas le garder au-delà de la prochaine
+// collection sans le rendre accessible
+pub type Finalizer = Rc<dyn Fn(*mut ObjectHeader)>;
+
+// Un cycle de marquage incrémental. On utilise l'abstraction tricolore: les objets blancs ne sont pas marqués,
+// les objets gris sont marqués mais leurs références n'ont pas encore été parcourues, les objets noirs sont
+// marqués et parcourus. Les objets gris sont exactement ceux de `grey`.
+struct IncrementalMarking {
+    grey: Vec<*mut ObjectHeader>,
+    // les objets signalés par le mutateur entre deux étapes: les anciennes valeurs des champs écrasés et les
+    // objets alloués pendant le cycle, ils sont grisés au début de l'étape suivante s'ils sont encore blancs
+    shaded: Rc<RefCell<Vec<*mut ObjectHeader>>>,
+    // la barrière et le callback d'allocation installés avant le cycle, ils sont remis en place à la fin
+    previous_barrier: Rc<RefCell<Box<dyn WriteBarrier>>>,
+    previous_callback: Rc<RefCell<AllocationCallback>>
+}
+
+// La barrière "snapshot-at-the-beginning": l'ancienne valeur d'un champ écrasé est grisée, de sorte que tous
+// les objets accessibles au début du cycle soient marqués, même si le mutateur les détache entre deux étapes.
+struct SnapshotBarrier {
+    previous: Rc<RefCell<Box<dyn WriteBarrier>>>,
+    shaded: Rc<RefCell<Vec<*mut ObjectHeader>>>
+}
+
+impl WriteBarrier for SnapshotBarrier {
+    unsafe fn on_reference_store(&mut self, obj: *mut ObjectHeader, offset: usize, old: *mut ObjectHeader, new: *mut ObjectHeader) {
+        if !old.is_null() {
+            self.shaded.borrow_mut().push(old);
+        }
+        self.previous.borrow_mut().on_reference_store(obj, offset, old, new);
+    }
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Hash)]
@@ -44,11 +114,11 @@ impl BitmapIndex {
     }
 
     fn bitmap_nth(&self) -> usize {
-        (self.data >> 24 & 0xFF) as usize
+        ((self.data >> 24) & 0xFF) as usize
     }
 
     fn offset(&self) -> usize {
-        (self.data >> 8 & 0xFFFF) as usize
+        ((self.data >> 8) & 0xFFFF) as usize
     }
 
     fn bit(&self) -> usize {
@@ -60,6 +130,95 @@ impl BitmapIndex {
     }
 }
 
+// un pointeur qu'on peut envoyer à un autre thread, les objets ne sont ni déplacés ni modifiés pendant le marquage
+#[derive(Copy, Clone)]
+struct SendPtr(*mut ObjectHeader);
+
+unsafe impl Send for SendPtr {}
+
+// Une vue du tas partagée par les threads du marquage parallèle. Les blocs sont triés par adresse, chacun avec
+// l'indice de son bitmap, i.e. sa position dans `committed_regions`.
+struct SharedHeap<'a> {
+    blocks: Vec<(usize, usize, usize)>,
+    bitmap: &'a [Vec<AtomicU8>],
+    types: &'a TypeRegistry
+}
+
+impl SharedHeap<'_> {
+    fn bitmap_index_of(&self, address: usize) -> Option<BitmapIndex> {
+        // le dernier bloc qui commence avant l'adresse est le seul qui puisse la contenir
+        let after = self.blocks.partition_point(|(start, _, _)| *start <= address);
+        let (start, size, nth) = *self.blocks.get(after.checked_sub(1)?)?;
+        (address < start + size).then(|| {
+            let word = (address - start) / align_of::<usize>();
+            BitmapIndex::new(nth, word / 8, word % 8)
+        })
+    }
+
+    // marquer atomiquement un objet, retourne `true` seulement pour le thread qui l'a marqué le premier, c'est
+    // donc lui seul qui parcourt l'objet
+    fn try_mark(&self, index: BitmapIndex) -> bool {
+        let (bitmap_nth, offset, bit) = index.unpack();
+        self.bitmap[bitmap_nth][offset].fetch_or(1 << bit, Ordering::Relaxed) & (1 << bit) == 0
+    }
+}
+
+// La place d'un objet vivant une fois compacté: sa taille arrondie au mot, de sorte que l'objet placé juste
+// après lui reste aligné même si sa taille n'est pas un multiple du mot (e.g. une chaîne).
+unsafe fn living_size(obj: *mut ObjectHeader) -> usize {
+    obj.size().next_multiple_of(align_of::<usize>())
+}
+
+// le nombre d'objets qu'un thread du marquage parallèle garde pour lui avant de partager le surplus
+const LOCAL_WORK: usize = 64;
+
+// Le travail d'un thread du marquage parallèle: il parcourt les objets de sa pile privée, qui n'est jamais
+// verrouillée, et ne partage dans sa deque que la moitié la plus ancienne de la pile lorsqu'elle dépasse
+// `LOCAL_WORK`. Quand la pile est vide, il reprend la moitié de sa propre deque, ou à défaut de celle d'un autre
+// thread, en une seule prise de verrou. `pending` compte les objets marqués mais pas encore parcourus, le marquage
+// est terminé lorsqu'il tombe à zéro. Retourne les tailles des objets que ce thread a marqués, elles sont fusionnées
+// dans `size_of_living` après le marquage.
+unsafe fn mark_worker(me: usize, heap: &SharedHeap, deques: &[Mutex<VecDeque<SendPtr>>], pending: &AtomicUsize) -> HashMap<BitmapIndex, usize> {
+    let mut sizes = HashMap::new();
+    let mut local = Vec::new();
+    loop {
+        if local.is_empty() {
+            // on ne tient jamais deux verrous à la fois
+            local = (0..deques.len()).find_map(|i| {
+                let mut deque = deques[(me + i) % deques.len()].lock().unwrap();
+                let half = deque.len().div_ceil(2);
+                (half > 0).then(|| deque.drain(..half).collect())
+            }).unwrap_or_default();
+        }
+        match local.pop() {
+            Some(SendPtr(obj)) => {
+                for (pointer, _) in reachability::pointers(heap.types, obj).unwrap() {
+                    // les pointeurs nuls et ceux vers un autre tas n'ont pas d'indice
+                    if let Some(index) = heap.bitmap_index_of(pointer as usize) && heap.try_mark(index) {
+                        sizes.insert(index, living_size(pointer));
+                        pending.fetch_add(1, Ordering::SeqCst);
+                        local.push(SendPtr(pointer));
+                    }
+                }
+                if local.len() > LOCAL_WORK {
+                    let shared = local.drain(..local.len() / 2);
+                    deques[me].lock().unwrap().extend(shared);
+                }
+                pending.fetch_sub(1, Ordering::SeqCst);
+            }
+            None if pending.load(Ordering::SeqCst) == 0 => return sizes,
+            None => thread::yield_now()
+        }
+    }
+}
+
+// Le résultat de `compute_locations` pour un bloc du tas: la nouvelle adresse du premier objet de chaque bloc de
+// compactage, et les blocs de compactage qui restent en place, calculés une seule fois par collection.
+pub struct CompactionPlan {
+    offsets: HashMap<usize, *mut u8>,
+    pinned_blocks: HashSet<usize>
+}
+
 const BYTES_PER_BLOCK: usize = 256;
 const BITS_IN_BLOCK: usize = BYTES_PER_BLOCK;
 
@@ -70,21 +229,37 @@ impl GarbageCollector {
         let gc = Rc::new(RefCell::new(GarbageCollector {
             heap: ObjectAllocator::new(),
             bitmap: vec![],
-            size_of_living: hashmap!{}
+            size_of_living: hashmap!{},
+            marking: None,
+            mark_threads: 1,
+            roots: RootRegistry::new(),
+            conservative_roots: vec![],
+            pinned: HashSet::new(),
+            finalizers: HashMap::new(),
+            finalization_queue: VecDeque::new(),
+            finalized: HashSet::new(),
+            ephemerons: vec![]
         }));
 
-        let cloned = gc.clone();
-        gc.clone().borrow_mut().heap.allocator.expand_callback = Box::new(move |tracker| unsafe {
+        // une référence faible, sinon le ramasse-miettes se garderait lui-même en vie et son tas ne serait jamais libéré
+        let cloned = Rc::downgrade(&gc);
+        gc.borrow_mut().heap.allocator.expand_callback = Box::new(move |tracker| unsafe {
             // bien...
-            // chaque bitmap contient les bytes, dans lesquels chaque bit répresente un début d'objet possible
-            // puisque les objets sont `align_of::<usize>()`-alignés, alors on divise la taille de la mémoire par
-            // `align_of::<usize>()`, de plus, puisque chaque bit répresente un début d'objet, alors on divise
-            // de plus par `8`, les bits dans un byte.
-            (*cloned.as_ref().as_ptr()).bitmap.push(vec![0; tracker.size / align_of::<usize>() / 8]);
+            if let Some(gc) = cloned.upgrade() {
+                (*gc.as_ref().as_ptr()).on_expand(tracker);
+            }
         });
         gc
     }
 
+    pub fn on_expand(&mut self, block: HeapBlock) {
+        // chaque bitmap contient les bytes, dans lesquels chaque bit répresente un début d'objet possible
+        // puisque les objets sont `align_of::<usize>()`-alignés, alors on divise la taille de la mémoire par
+        // `align_of::<usize>()`, de plus, puisque chaque bit répresente un début d'objet, alors on divise
+        // de plus par `8`, les bits dans un byte.
+        self.bitmap.push((0..block.size / align_of::<usize>() / 8).map(|_| AtomicU8::new(0)).collect());
+    }
+
     pub unsafe fn next_object(block: &HeapBlock, this_object_option: Option<*mut ObjectHeader>) -> Option<*mut ObjectHeader> {
         if this_object_option.is_none() {
             // on cherche le premier objet dans le bloc
@@ -92,9 +267,8 @@ impl GarbageCollector {
         }
 
         let this_object = this_object_option.unwrap();
-        // désadressage d'un pointeur indirect
-        let ty = Box::from_raw((*this_object).ptr_to_type_info.cast::<ProductType>());
-        let size = ty.size();
+        // la taille dans l'en-tête comprend l'en-tête lui-même, elle est valable pour les remplisseurs aussi
+        let size = this_object.size();
         let obj_end = this_object.cast::<u8>().add(size);
         let padding = (!(obj_end as usize) + 1) & (align_of::<usize>() - 1);
         let next_object = obj_end.add(padding);
@@ -109,7 +283,7 @@ impl GarbageCollector {
             // se trouverent avoir des valeurs valides de type signature. On ne peut pas résoudre
             // ce problème, mais puisque la présence de ramasse-miettes, on l'instruit de compactifier
             // l'espace de la mémoire, afin que notre hypothèse optimiste soit toujours vraie.
-            ObjectHeader::type_sig_within_valid_range((*next_object_header).type_sig).then_some(next_object_header)
+            ObjectHeader::type_sig_within_valid_range(next_object_header.type_sig()).then_some(next_object_header)
         } else {
             None
         }
@@ -136,7 +310,17 @@ impl GarbageCollector {
     // à la taille de la mémoire. De plus, il réquiert moins de mémoire, puisque dans tout moment, la liste d'attent, ce n'est
     // pas grande.
     pub(crate) unsafe fn mark_living(&mut self, gc_roots: &mut [*mut ObjectHeader]) {
+        // une collection complète rend le cycle incrémental en cours inutile
+        self.stop_marking();
         self.reset_all_marks();
+        if gc_roots.is_empty() {
+            // rien n'est accessible, tous les objets sont morts
+            return;
+        }
+        if self.mark_threads > 1 {
+            self.mark_living_parallel(gc_roots);
+            return;
+        }
         gc_roots.iter().for_each(|root| self.set_marked(*root, true));
         // on trouve le premier objet dans le premier bloc, d'abord on trouve logicalment le premier bloc,
         // puis on trouve le premier objet phisique dans le bloc.
@@ -147,6 +331,41 @@ impl GarbageCollector {
         self.mark_single(first)
     }
 
+    // Le marquage parallèle: les racines sont réparties entre `mark_threads` threads, chacun a sa propre deque
+    // et vole le travail des autres lorsqu'elle est vide. Le bitmap est mis atomiquement, chaque thread garde les
+    // tailles des objets qu'il a marqués dans sa propre table, ce qui évite un verrou sur `size_of_living`.
+    unsafe fn mark_living_parallel(&mut self, gc_roots: &[*mut ObjectHeader]) {
+        let threads = self.mark_threads;
+        let mut blocks = self.heap.allocator.committed_regions.values().enumerate()
+            .map(|(nth, block)| (block.start as usize, block.size, nth))
+            .collect::<Vec<_>>();
+        blocks.sort_unstable();
+        let heap = SharedHeap {
+            blocks,
+            bitmap: &self.bitmap,
+            types: &self.heap.types
+        };
+        let deques = (0..threads).map(|_| Mutex::new(VecDeque::new())).collect::<Vec<_>>();
+        let pending = AtomicUsize::new(0);
+        let mut sizes = HashMap::new();
+        for (i, root) in gc_roots.iter().enumerate() {
+            if let Some(index) = heap.bitmap_index_of(*root as usize) && heap.try_mark(index) {
+                sizes.insert(index, living_size(*root));
+                pending.fetch_add(1, Ordering::SeqCst);
+                deques[i % threads].lock().unwrap().push_back(SendPtr(*root));
+            }
+        }
+        let per_thread = thread::scope(|scope| {
+            let workers = (0..threads).map(|me| {
+                let (heap, deques, pending) = (&heap, &deques, &pending);
+                scope.spawn(move || unsafe { mark_worker(me, heap, deques, pending) })
+            }).collect::<Vec<_>>();
+            workers.into_iter().map(|worker| worker.join().unwrap()).collect::<Vec<_>>()
+        });
+        per_thread.into_iter().for_each(|thread_sizes| sizes.extend(thread_sizes));
+        self.size_of_living = sizes;
+    }
+
     unsafe fn mark_single(&mut self, first_root_in_block: *mut ObjectHeader) {
         let mut cur = Some(first_root_in_block);
         let mut work_list = Vec::new();
@@ -176,18 +395,96 @@ impl GarbageCollector {
         }
     }
 
+    // Commencer un cycle de marquage incrémental depuis `roots`. Entre deux appels à `mark_step`, le mutateur
+    // peut allouer et écrire des références par `ObjectAllocator::set_reference`, mais aucun objet n'est déplacé
+    // avant `finish_collection`. Les objets alloués pendant le cycle sont considérés comme vivants.
+    pub unsafe fn start_marking(&mut self, roots: &[*mut ObjectHeader]) {
+        self.stop_marking();
+        self.reset_all_marks();
+        let shaded = Rc::new(RefCell::new(self.with_registered_roots(roots)));
+        let previous_barrier = Rc::new(RefCell::new(replace(&mut self.heap.barrier, Box::new(NoBarrier))));
+        self.heap.barrier = Box::new(SnapshotBarrier { previous: previous_barrier.clone(), shaded: shaded.clone() });
+        let previous_callback = Rc::new(RefCell::new(replace(&mut self.heap.allocation_callback, Box::new(|_| ()))));
+        let (cloned_shaded, cloned_callback) = (shaded.clone(), previous_callback.clone());
+        self.heap.allocation_callback = Box::new(move |obj| {
+            cloned_shaded.borrow_mut().push(obj);
+            (cloned_callback.borrow_mut())(obj)
+        });
+        self.marking = Some(IncrementalMarking { grey: vec![], shaded, previous_barrier, previous_callback });
+    }
+
+    pub fn is_marking(&self) -> bool {
+        self.marking.is_some()
+    }
+
+    // Une étape du marquage incrémental: au plus `budget` objets gris sont parcourus. Retourne `true` s'il ne
+    // reste plus d'objet gris, le cycle peut alors être terminé sans autre travail de marquage.
+    pub unsafe fn mark_step(&mut self, budget: usize) -> bool {
+        let Some(mut marking) = self.marking.take() else {
+            return true;
+        };
+        let shaded = marking.shaded.take();
+        shaded.into_iter().for_each(|obj| self.shade(obj, &mut marking.grey));
+        for _ in 0..budget {
+            match marking.grey.pop() {
+                Some(obj) => for (pointer, _) in self.heap.pointers(obj).unwrap() {
+                    self.shade(pointer, &mut marking.grey);
+                },
+                None => break
+            }
+        }
+        let done = marking.grey.is_empty() && marking.shaded.borrow().is_empty();
+        self.marking = Some(marking);
+        done
+    }
+
+    // Marquer les objets accessibles depuis `objects` en plus des objets déjà marqués, sans rien effacer.
+    unsafe fn mark_from(&mut self, objects: &[*mut ObjectHeader]) {
+        let mut grey = vec![];
+        objects.iter().for_each(|obj| self.shade(*obj, &mut grey));
+        while let Some(obj) = grey.pop() {
+            for (pointer, _) in self.heap.pointers(obj).unwrap() {
+                self.shade(pointer, &mut grey);
+            }
+        }
+    }
+
+    // griser un objet blanc, les pointeurs vers un autre tas (e.g. la pépinière) sont ignorés
+    unsafe fn shade(&mut self, obj: *mut ObjectHeader, grey: &mut Vec<*mut ObjectHeader>) {
+        if !obj.is_null() && self.heap.allocator.get_block(obj.cast()).is_some() && !self.is_marked(obj) {
+            self.set_marked(obj, true);
+            grey.push(obj);
+        }
+    }
+
+    // Terminer le marquage du cycle en cours et retirer la barrière, le bitmap est alors prêt pour `compact` ou
+    // `sweep`.
+    pub unsafe fn finish_marking(&mut self) {
+        while !self.mark_step(usize::MAX) {}
+        self.stop_marking();
+    }
+
+    fn stop_marking(&mut self) {
+        if let Some(marking) = self.marking.take() {
+            // d'autres copies des `Rc` peuvent encore vivre, e.g. une barrière du cycle retenue ailleurs, on sort
+            // donc la barrière et le callback de leurs cellules au lieu de supposer qu'on en est le seul détenteur
+            self.heap.barrier = replace(&mut *marking.previous_barrier.borrow_mut(), Box::new(NoBarrier));
+            self.heap.allocation_callback = replace(&mut *marking.previous_callback.borrow_mut(), Box::new(|_| ()));
+        }
+    }
+
     fn reset_all_marks(&mut self) {
-        self.bitmap.iter_mut().for_each(|vec| vec.fill(0));
+        self.bitmap.iter().flatten().for_each(|byte| byte.store(0, Ordering::Relaxed));
         self.size_of_living.clear();
     }
 
     pub fn all_marked_bits(&self) -> Vec<*mut ObjectHeader> {
         let all_indexes = self.bitmap.iter()
             .enumerate()
-            .flat_map(|(bitmap_nth, vec)| vec.iter().enumerate().filter(|(_, bit)| **bit != 0).map(|t| (bitmap_nth, t.0)).collect::<Vec<(usize, usize)>>())
+            .flat_map(|(bitmap_nth, vec)| vec.iter().enumerate().filter(|(_, bit)| bit.load(Ordering::Relaxed) != 0).map(|t| (bitmap_nth, t.0)).collect::<Vec<(usize, usize)>>())
             .collect::<Vec<_>>();
         all_indexes.iter().flat_map(|(bitmap_nth, offset)| unsafe {
-            let bits = count_bits_set(self.bitmap[*bitmap_nth][*offset]);
+            let bits = count_bits_set(self.bitmap_byte(*bitmap_nth, *offset));
             bits.iter().map(move |bit| self.bitmap_index_to_address(BitmapIndex::new(*bitmap_nth, *offset, *bit))).collect::<Vec<_>>()
         }).collect::<Vec<_>>()
     }
@@ -196,7 +493,7 @@ impl GarbageCollector {
         let (bitmap_nth, offset, bit) = self.address_to_bitmap_index(this_object).unpack();
         // on examine d'abord s'il y a des bits mis dans le même byte
         // on a su qu'il y a au moins un bit mis, ce qui est le bit qui répresente `this_object`
-        let this_chunk = count_bits_set(self.bitmap[bitmap_nth][offset]).into_iter().find(|x| *x > bit);
+        let this_chunk = count_bits_set(self.bitmap_byte(bitmap_nth, offset)).into_iter().find(|x| *x > bit);
         let res = match this_chunk {
             Some(x) =>
                 Some(self.bitmap_index_to_address(BitmapIndex::new(bitmap_nth, offset, x))),
@@ -204,8 +501,8 @@ impl GarbageCollector {
                 // **x != 0 checks whether there are bits set in the byte
                 let bitmap_slice = &self.bitmap[bitmap_nth][offset + 1..];
                 let new_index_start_relative = offset + 1;
-                let new_index = bitmap_slice.iter().enumerate().find(|(_, x)| **x != 0).map(|o| o.0);
-                let bit = new_index.map(|x| *count_bits_set(bitmap_slice[x]).first().unwrap());
+                let new_index = bitmap_slice.iter().enumerate().find(|(_, x)| x.load(Ordering::Relaxed) != 0).map(|o| o.0);
+                let bit = new_index.map(|x| *count_bits_set(bitmap_slice[x].load(Ordering::Relaxed)).first().unwrap());
                 new_index.combine(bit).map(|(new_idx, bit_unwrapped)| self.bitmap_index_to_address(BitmapIndex::new(bitmap_nth, new_index_start_relative + new_idx, bit_unwrapped)))
             }
         };
@@ -220,23 +517,28 @@ impl GarbageCollector {
 
     unsafe fn first_in_bitmap(&self, bitmap_nth: usize) -> Option<*mut ObjectHeader> {
         let bitmap = &self.bitmap[bitmap_nth];
-        bitmap.iter().enumerate().find(|(_, x)| **x != 0).map(|(i, x)| self.bitmap_index_to_address(BitmapIndex::new(bitmap_nth, i, *count_bits_set(*x).first().unwrap())))
+        bitmap.iter().enumerate().map(|(i, x)| (i, x.load(Ordering::Relaxed))).find(|(_, x)| *x != 0)
+            .map(|(i, x)| self.bitmap_index_to_address(BitmapIndex::new(bitmap_nth, i, *count_bits_set(x).first().unwrap())))
     }
 
     unsafe fn is_marked(&self, address: *mut ObjectHeader) -> bool {
         let (bitmap_nth, offset, bit) = self.address_to_bitmap_index(address).unpack();
-        self.bitmap[bitmap_nth][offset] & (1 << bit) != 0
+        self.bitmap_byte(bitmap_nth, offset) & (1 << bit) != 0
+    }
+
+    fn bitmap_byte(&self, bitmap_nth: usize, offset: usize) -> u8 {
+        self.bitmap[bitmap_nth][offset].load(Ordering::Relaxed)
     }
 
     pub unsafe fn set_marked(&mut self, address: *mut ObjectHeader, value: bool) {
         let bi = self.address_to_bitmap_index(address);
         let (bitmap_nth, offset, bit) = bi.unpack();
         if value {
-            self.size_of_living.insert(bi, (*address).size);
-            self.bitmap[bitmap_nth][offset] |= 1 << bit;
+            self.size_of_living.insert(bi, living_size(address));
+            self.bitmap[bitmap_nth][offset].fetch_or(1 << bit, Ordering::Relaxed);
         } else {
             self.size_of_living.remove(&bi);
-            self.bitmap[bitmap_nth][offset] &= !(1 << bit);
+            self.bitmap[bitmap_nth][offset].fetch_and(!(1 << bit), Ordering::Relaxed);
         }
     }
 
@@ -268,9 +570,15 @@ impl GarbageCollector {
 
     // "The Compressor"
     // Je voudrais référer à l'algorithme 3.4 et la figure 3.3 dans le livre.
-    pub unsafe fn compute_locations(&self, heap_block: &HeapBlock) -> HashMap<usize, *mut u8> {
+    // Les objets épinglés ne bougent pas: tout bloc de compactage qui contient le début d'un objet épinglé reste en
+    // place, avec tous ses objets. La position courante ne dépasse jamais l'adresse de l'objet courant, les objets
+    // qui précèdent un tel bloc glissent donc vers le bas sans l'atteindre, et ceux qui le suivent sont placés
+    // après son dernier objet.
+    pub unsafe fn compute_locations(&self, heap_block: &HeapBlock) -> CompactionPlan {
+        let pinned_blocks = self.pinned_compaction_blocks(heap_block);
         let mut location = heap_block.start;
         let mut block = self.compaction_block_index_of(heap_block.start, heap_block);
+        let mut in_pinned_block = false;
         let mut offset = hashmap!{};
         for (idx, bit_block) in self.bitmap[self.index_of_heap_block(heap_block)].iter().enumerate() {
             for i in 0..8 { // 8: bits of byte
@@ -282,22 +590,32 @@ impl GarbageCollector {
                 if bit_index % BITS_IN_BLOCK == 0 {
                     // le premier objet dans 'block' sera mis à 'location'
                     offset.insert(block, location);
+                    in_pinned_block = pinned_blocks.contains(&block);
                     block += 1
                 }
 
-                if bit_set(*bit_block, i) {
-                    location = location.byte_add(*self.size_of_living.get(&BitmapIndex::new(self.index_of_heap_block(heap_block), idx, i)).unwrap());
+                if bit_set(bit_block.load(Ordering::Relaxed), i) {
+                    let size = *self.size_of_living.get(&BitmapIndex::new(self.index_of_heap_block(heap_block), idx, i)).unwrap();
+                    location = if in_pinned_block {
+                        // l'objet reste à sa place, 'bit_index' est son décalage dans le bloc du tas
+                        heap_block.start.byte_add(bit_index + size)
+                    } else {
+                        location.byte_add(size)
+                    };
                 }
             }
         }
 
-        offset
+        CompactionPlan { offsets: offset, pinned_blocks }
     }
 
-    pub unsafe fn new_address_after_compaction(&self, old_address: *mut u8, offset_table: &HashMap<usize, *mut u8>, heap_block: &HeapBlock) -> *mut u8 {
+    pub unsafe fn new_address_after_compaction(&self, old_address: *mut u8, plan: &CompactionPlan, heap_block: &HeapBlock) -> *mut u8 {
         let block = self.compaction_block_index_of(old_address, heap_block);
+        if plan.pinned_blocks.contains(&block) {
+            return old_address;
+        }
         let precede = self.preceding_offset_in_compaction_block_2(old_address, heap_block);
-        offset_table.get(&block).unwrap().byte_add(precede)
+        plan.offsets.get(&block).unwrap().byte_add(precede)
     }
 
     unsafe fn preceding_offset_in_compaction_block_2(&self, address: *mut u8, heap_block: &HeapBlock) -> usize {
@@ -309,21 +627,21 @@ impl GarbageCollector {
             return 0;
         }
         if start_bit_chunk.offset() == end_bit_chunk.offset() {
-            let in_between = count_bits_set_range(self.bitmap[start_bit_chunk.bitmap_nth()][start_bit_chunk.offset()], start_bit_chunk.bit(), end_bit_chunk.bit());
+            let in_between = count_bits_set_range(self.bitmap_byte(start_bit_chunk.bitmap_nth(), start_bit_chunk.offset()), start_bit_chunk.bit(), end_bit_chunk.bit());
             let addresses = in_between.iter()
                 .map(|bit| self.size_of_living.get(&BitmapIndex::new(start_bit_chunk.bitmap_nth(), start_bit_chunk.offset(), *bit)).unwrap_or(&0));
             return addresses.sum::<usize>();
         }
-        let start_higher_size = count_bits_set_range(self.bitmap[start_bit_chunk.bitmap_nth()][start_bit_chunk.offset()], start_bit_chunk.bit(), 8)
+        let start_higher_size = count_bits_set_range(self.bitmap_byte(start_bit_chunk.bitmap_nth(), start_bit_chunk.offset()), start_bit_chunk.bit(), 8)
             .iter()
             .map(|bit | self.size_of_living.get(&BitmapIndex::new(start_bit_chunk.bitmap_nth(), start_bit_chunk.offset(), *bit)).unwrap_or(&0))
             .sum::<usize>();
-        let end_lower_size = count_bits_set_range(self.bitmap[end_bit_chunk.bitmap_nth()][end_bit_chunk.offset()], 0, end_bit_chunk.bit())
+        let end_lower_size = count_bits_set_range(self.bitmap_byte(end_bit_chunk.bitmap_nth(), end_bit_chunk.offset()), 0, end_bit_chunk.bit())
             .iter()
             .map(|bit | self.size_of_living.get(&BitmapIndex::new(end_bit_chunk.bitmap_nth(), end_bit_chunk.offset(), *bit)).unwrap_or(&0))
             .sum::<usize>();
         let in_between_chunks = (start_bit_chunk.offset() + 1..end_bit_chunk.offset())
-            .map(|offset| (offset, self.bitmap[start_bit_chunk.bitmap_nth()][offset]));
+            .map(|offset| (offset, self.bitmap_byte(start_bit_chunk.bitmap_nth(), offset)));
         let in_between_size: usize = in_between_chunks
             .flat_map(|(offset, chunk)| count_bits_set(chunk).iter()
                 .map(|bit| self.size_of_living.get(&BitmapIndex::new(start_bit_chunk.bitmap_nth(), offset, *bit)).unwrap_or(&0))
@@ -335,32 +653,62 @@ impl GarbageCollector {
         (start as usize - heap_block.start as usize) / BYTES_PER_BLOCK
     }
 
+    // les blocs de compactage de `heap_block` qui restent en place
+    unsafe fn pinned_compaction_blocks(&self, heap_block: &HeapBlock) -> HashSet<usize> {
+        self.pinned.iter().chain(self.conservative_roots.iter())
+            .filter(|obj| heap_block.contains(obj.cast()))
+            .map(|obj| self.compaction_block_index_of(obj.cast(), heap_block))
+            .collect()
+    }
+
+    // Remplir les trous laissés devant et entre les objets des blocs de compactage restés en place, afin que le tas
+    // reste parcourable. Un remplisseur peut n'occuper qu'un mot, tous les trous sont donc remplis.
+    unsafe fn fill_gaps(&mut self, objects: &mut [*mut ObjectHeader]) {
+        objects.sort();
+        for block in self.heap.allocator.committed_regions.values() {
+            let mut cursor = block.start;
+            for obj in objects.iter().filter(|obj| block.contains(obj.cast())) {
+                let gap = *obj as usize - cursor as usize;
+                if gap >= size_of::<usize>() {
+                    ObjectHeader::write_filler(cursor.cast(), gap);
+                }
+                let obj_end = obj.cast::<u8>().byte_add(obj.size());
+                cursor = obj_end.byte_add((!(obj_end as usize) + 1) & (align_of::<usize>() - 1));
+            }
+        }
+    }
+
     unsafe fn compact(&mut self, roots: &mut [*mut ObjectHeader]) -> HashMap<*mut ObjectHeader, *mut ObjectHeader> {
         let mut last_moved = hashset![];
         let mut uninitialized_starts = vec![(0usize, 0usize); self.heap.allocator.committed_regions.len()];
-        let offset_table_cache = self.heap.allocator.committed_regions.iter().map(|x| (x.1, self.compute_locations(x.1)))
+        let plans = self.heap.allocator.committed_regions.iter().map(|x| (x.1, self.compute_locations(x.1)))
             .collect::<HashMap<_, _>>();
         let mut new_root = hashmap![];
+        let mut moved_objects = vec![];
         for root in roots {
             if !(*root).is_null() {
                 let heap_block = self.block_of(*root);
-                let offset_table = offset_table_cache.get(heap_block).unwrap();
-                new_root.insert(*root, self.new_address_after_compaction(*root as *mut u8, offset_table, heap_block) as *mut ObjectHeader);
+                let plan = plans.get(heap_block).unwrap();
+                new_root.insert(*root, self.new_address_after_compaction(*root as *mut u8, plan, heap_block) as *mut ObjectHeader);
             }
         }
 
         for heap_block in self.heap.allocator.committed_regions.values() {
             let block_index = self.index_of_heap_block(heap_block);
             let mut scan = self.first_in_bitmap(block_index);
-            while let Some(s) = scan && (s as usize) < (heap_block.start.byte_add(heap_block.size) as usize) {
-                for (reference, offset) in self.heap.pointers(s).unwrap_or(hashset! {}) {
+            // `next_in_bitmap` passe au bloc suivant, qui peut se trouver physiquement avant celui-ci
+            while let Some(s) = scan && heap_block.contains(s.cast()) {
+                // les références faibles dont la cible est morte sont déjà nulles, les autres suivent leur cible
+                let mut references = self.heap.pointers(s).unwrap_or(hashset! {});
+                references.extend(self.heap.weak_pointers(s).unwrap_or(hashset! {}));
+                for (reference, offset) in references {
                     if !reference.is_null() {
                         let block_of_reference = self.block_of(reference);
-                        ptr::write(s.to_data_start::<u8>().add(offset) as *mut *mut ObjectHeader, self.new_address_after_compaction(reference as *mut u8, offset_table_cache.get(block_of_reference).unwrap(), block_of_reference) as *mut ObjectHeader);
+                        ptr::write(s.to_data_start::<u8>().add(offset) as *mut *mut ObjectHeader, self.new_address_after_compaction(reference as *mut u8, plans.get(block_of_reference).unwrap(), block_of_reference) as *mut ObjectHeader);
                     }
                 }
                 let block_of_reference = self.block_of(s);
-                let new_addr = self.new_address_after_compaction(s as *mut u8, offset_table_cache.get(block_of_reference).unwrap(), block_of_reference);
+                let new_addr = self.new_address_after_compaction(s as *mut u8, plans.get(block_of_reference).unwrap(), block_of_reference);
                 let old_addr = s as *mut u8;
 
                 if last_moved.contains(&(old_addr, new_addr)) {
@@ -368,9 +716,10 @@ impl GarbageCollector {
                     continue
                 }
 
-                self.copy_unsafe(old_addr, new_addr, (*s).size);
+                self.copy_unsafe(old_addr, new_addr, s.size());
+                moved_objects.push(new_addr.cast::<ObjectHeader>());
                 if new_addr as usize > uninitialized_starts[block_index].0 {
-                    uninitialized_starts[block_index] = (new_addr as usize, (*s).size);
+                    uninitialized_starts[block_index] = (new_addr as usize, s.size());
                 }
                 last_moved.insert((old_addr, new_addr));
                 scan = self.next_in_bitmap(s);
@@ -380,13 +729,54 @@ impl GarbageCollector {
             let block_idx = self.index_of_heap_block(block);
             let mut new_block = *block;
             let (addr, size) = uninitialized_starts[block_idx];
-            new_block.unallocated_start = (addr + size) as *mut u8;
+            // s'il n'y a aucun objet vivant dans ce bloc, le bloc entier devient libre
+            new_block.unallocated_start = if addr == 0 { block.start } else { (addr + size) as *mut u8 };
             (*layout, new_block)
         });
         self.heap.allocator.committed_regions = LinkedHashMap::from_iter(new_regions_map);
+        // les trous ont été comblés par le compactage
+        self.heap.allocator.free_lists.clear();
+        self.heap.allocator.use_free_lists = false;
+        if !self.pinned.is_empty() || !self.conservative_roots.is_empty() {
+            self.fill_gaps(&mut moved_objects);
+        }
+        self.heap.allocated_objects = moved_objects;
         new_root
     }
 
+    // Balayer le tas sans déplacer les objets: chaque espace entre deux objets marqués est soit rendu aux listes
+    // libres, soit rempli par un objet de remplissage, selon `mode`. L'espace après le dernier objet marqué d'un
+    // bloc est rendu au pointeur de bump. On ne lit que le bitmap et `size_of_living`, les objets morts ne sont
+    // jamais désadressés. Il faut que `mark_living` soit appelé avant. Retourne le nombre de bytes récupérés.
+    pub unsafe fn sweep(&mut self, mode: SweepMode) -> usize {
+        let allocated_before = self.heap.allocator.allocated();
+        self.heap.allocator.free_lists.clear();
+        self.heap.allocator.use_free_lists = mode == SweepMode::FreeList;
+        let blocks = self.heap.allocator.committed_regions.iter().map(|(layout, block)| (*layout, *block)).collect::<Vec<_>>();
+        for (block_index, (layout, block)) in blocks.iter().enumerate() {
+            let mut cursor = block.start;
+            let mut scan = self.first_in_bitmap(block_index);
+            while let Some(s) = scan && block.contains(s.cast()) {
+                let gap = s as usize - cursor as usize;
+                // un trou rendu aux listes libres est rempli lui aussi, le tas reste parcourable jusqu'à ce qu'il
+                // soit réutilisé
+                if gap >= size_of::<usize>() {
+                    ObjectHeader::write_filler(cursor.cast(), gap);
+                }
+                if mode == SweepMode::FreeList {
+                    self.heap.allocator.free_lists.push(cursor, gap);
+                }
+                let obj_end = s.cast::<u8>().byte_add(*self.size_of_living.get(&self.address_to_bitmap_index(s)).unwrap());
+                cursor = obj_end.byte_add((!(obj_end as usize) + 1) & (align_of::<usize>() - 1));
+                scan = self.next_in_bitmap(s);
+            }
+            self.heap.allocator.committed_regions.get_mut(layout).unwrap().unallocated_start = cursor;
+        }
+        self.heap.allocated_objects = self.all_marked_bits();
+        // les trous des listes libres précédentes comptent de nouveau comme alloués une fois remplis
+        allocated_before.saturating_sub(self.heap.allocator.allocated())
+    }
+
     unsafe fn copy_unsafe(&self, src: *mut u8, dst: *mut u8, count: usize) {
         let mut temp_arr = vec![];
 
@@ -399,8 +789,237 @@ impl GarbageCollector {
         }
     }
 
+    // Les racines enregistrées dans `roots` sont collectées avec `roots` et mises à jour sur place, la table
+    // retournée contient les nouvelles adresses de toutes les racines.
     pub unsafe fn collect(&mut self, roots: &mut [*mut ObjectHeader]) -> HashMap<*mut ObjectHeader, *mut ObjectHeader> {
-        self.mark_living(&mut roots.to_vec());
-        self.compact(roots)
+        let mut all_roots = self.with_registered_roots(roots);
+        self.mark_living(&mut all_roots);
+        self.process_unreachable();
+        self.compact_roots(&mut all_roots)
+    }
+
+    unsafe fn compact_roots(&mut self, all_roots: &mut [*mut ObjectHeader]) -> HashMap<*mut ObjectHeader, *mut ObjectHeader> {
+        // la file de finalisation a peut-être grandi depuis que les racines ont été rassemblées, et les objets déjà
+        // finalisés encore vivants ne sont pas des racines: leurs adresses doivent toutes être mises à jour
+        let mut moved = all_roots.to_vec();
+        moved.extend(self.finalization_queue.iter().chain(self.finalized.iter()).copied());
+        let tables = self.ephemeron_tables();
+        // après `clear_dead_ephemerons` toutes les clés sont vivantes, les valeurs nulles ou d'un autre tas restent en place
+        tables.iter().for_each(|table| moved.extend(table.borrow().entries()
+            .flat_map(|(key, value)| [key, value])
+            .filter(|obj| !obj.is_null() && self.heap.allocator.get_block(obj.cast()).is_some())));
+        let forwarding = self.compact(&mut moved);
+        self.roots.borrow_mut().forward(&forwarding);
+        // les clés ont peut-être bougé, la table est rehachée
+        tables.iter().for_each(|table| table.borrow_mut().forward(&forwarding));
+        // comme pour les racines, un objet absent de la table n'a pas bougé
+        self.finalization_queue.iter_mut().for_each(|obj| *obj = forwarding.get(obj).copied().unwrap_or(*obj));
+        self.finalized = self.finalized.iter().map(|obj| forwarding.get(obj).copied().unwrap_or(*obj)).collect();
+        self.conservative_roots.clear();
+        forwarding
+    }
+
+    fn with_registered_roots(&self, roots: &[*mut ObjectHeader]) -> Vec<*mut ObjectHeader> {
+        let mut all_roots = roots.to_vec();
+        all_roots.extend(self.roots.borrow().roots());
+        all_roots.extend(self.conservative_roots.iter().copied());
+        all_roots.extend(self.pinned.iter().copied());
+        all_roots.extend(self.finalization_queue.iter().copied());
+        all_roots
+    }
+
+    // Épingler un objet: il reste à la même adresse, et accessible, jusqu'à `unpin`. Les épinglages ne
+    // s'emboîtent pas, un seul `unpin` suffit.
+    pub fn pin(&mut self, obj: *mut ObjectHeader) {
+        self.pinned.insert(obj);
+    }
+
+    // Retourne faux si l'objet n'était pas épinglé
+    pub fn unpin(&mut self, obj: *mut ObjectHeader) -> bool {
+        self.pinned.remove(&obj)
+    }
+
+    pub fn is_pinned(&self, obj: *mut ObjectHeader) -> bool {
+        self.pinned.contains(&obj) || self.conservative_roots.contains(&obj)
+    }
+
+    // Parcourir de manière conservative les mots entre `start` et `end`, typiquement une pile native. Les objets
+    // trouvés sont des racines de la prochaine collection et sont épinglés pendant celle-ci, il faut donc
+    // parcourir la pile à nouveau avant chaque collection. Retourne les objets trouvés.
+    pub unsafe fn scan_stack(&mut self, start: *const usize, end: *const usize) -> Vec<*mut ObjectHeader> {
+        let found = conservative::scan_range(&self.heap, start, end);
+        self.add_conservative_roots(&found);
+        found
+    }
+
+    // Comme `scan_stack`, pour une copie des registres
+    pub unsafe fn scan_registers(&mut self, registers: &[usize]) -> Vec<*mut ObjectHeader> {
+        let found = conservative::scan_words(&self.heap, registers);
+        self.add_conservative_roots(&found);
+        found
+    }
+
+    fn add_conservative_roots(&mut self, found: &[*mut ObjectHeader]) {
+        // pendant un cycle incrémental, les racines trouvées sont grisées comme les objets signalés par le mutateur
+        if let Some(marking) = &self.marking {
+            marking.shaded.borrow_mut().extend(found.iter().copied());
+        }
+        for obj in found {
+            if !self.conservative_roots.contains(obj) {
+                self.conservative_roots.push(*obj);
+            }
+        }
+    }
+
+    // Terminer le cycle incrémental en cours puis compacter le tas, comme `collect`.
+    pub unsafe fn finish_collection(&mut self, roots: &mut [*mut ObjectHeader]) -> HashMap<*mut ObjectHeader, *mut ObjectHeader> {
+        self.finish_marking();
+        self.process_unreachable();
+        let mut all_roots = self.with_registered_roots(roots);
+        self.compact_roots(&mut all_roots)
+    }
+
+    // Terminer le cycle incrémental en cours puis balayer le tas, comme `collect_non_moving`.
+    pub unsafe fn finish_collection_non_moving(&mut self, mode: SweepMode) -> usize {
+        self.finish_marking();
+        self.process_unreachable();
+        self.conservative_roots.clear();
+        self.sweep(mode)
+    }
+
+    // Une collection qui ne déplace aucun objet, les racines et toutes les adresses tenues par le mutateur
+    // restent valables. Retourne le nombre de bytes récupérés.
+    pub unsafe fn collect_non_moving(&mut self, roots: &mut [*mut ObjectHeader], mode: SweepMode) -> usize {
+        self.mark_living(&mut self.with_registered_roots(roots));
+        self.process_unreachable();
+        self.conservative_roots.clear();
+        self.sweep(mode)
+    }
+
+    // Ce qui suit le marquage dans toutes les collections. Les objets ressuscités pour leur finaliseur sont
+    // marqués avant que les références faibles ne soient examinées, les références faibles vers eux restent donc
+    // valables jusqu'à la collection qui les récupère.
+    unsafe fn process_unreachable(&mut self) {
+        self.mark_ephemerons();
+        self.queue_finalizable();
+        // les objets ressuscités peuvent être les clés d'autres éphémérons
+        self.mark_ephemerons();
+        self.clear_dead_ephemerons();
+        self.clear_dead_weak_references();
+    }
+
+    pub fn new_ephemeron_table(&mut self) -> Rc<RefCell<EphemeronTable>> {
+        let table = EphemeronTable::new();
+        self.ephemerons.push(Rc::downgrade(&table));
+        table
+    }
+
+    fn ephemeron_tables(&mut self) -> Vec<Rc<RefCell<EphemeronTable>>> {
+        self.ephemerons.retain(|table| table.strong_count() > 0);
+        self.ephemerons.iter().filter_map(Weak::upgrade).collect()
+    }
+
+    unsafe fn is_marked_here(&self, obj: *mut ObjectHeader) -> bool {
+        !obj.is_null() && self.heap.allocator.get_block(obj.cast()).is_some() && self.is_marked(obj)
+    }
+
+    // Le point fixe des éphémérons: la valeur d'une entrée est marquée dès que sa clé est marquée, ce qui peut
+    // marquer la clé d'une autre entrée, on recommence jusqu'à ce qu'aucune nouvelle valeur ne soit marquée. Les
+    // entrées ne sont examinées qu'une fois le marquage terminé, même pendant un cycle incrémental.
+    unsafe fn mark_ephemerons(&mut self) {
+        let tables = self.ephemeron_tables();
+        loop {
+            let values = tables.iter()
+                .flat_map(|table| table.borrow().entries().collect::<Vec<_>>())
+                .filter(|(key, value)| self.is_marked_here(*key) && !value.is_null()
+                    && self.heap.allocator.get_block(value.cast()).is_some() && !self.is_marked(*value))
+                .map(|(_, value)| value)
+                .collect::<Vec<_>>();
+            if values.is_empty() {
+                return;
+            }
+            self.mark_from(&values);
+        }
+    }
+
+    // les entrées dont la clé est morte disparaissent, leurs valeurs sont récupérées avec elles
+    unsafe fn clear_dead_ephemerons(&mut self) {
+        for table in self.ephemeron_tables() {
+            table.borrow_mut().retain(|key, _| self.is_marked_here(key));
+        }
+    }
+
+    // Déclarer le finaliseur des objets de type `type_info`, les types sont comparés par leur nom.
+    pub fn declare_finalizer(&mut self, type_info: &dyn TypeInfo, finalizer: impl Fn(*mut ObjectHeader) + 'static) {
+        self.finalizers.insert(type_info.name(), Rc::new(finalizer));
+    }
+
+    unsafe fn finalizer_of(&self, obj: *mut ObjectHeader) -> Option<&Finalizer> {
+        self.finalizers.get(&self.heap.type_of(obj).ok()?.name())
+    }
+
+    // Après le marquage: les objets inaccessibles dont le type déclare un finaliseur, et qui n'ont pas encore
+    // été finalisés, sont ressuscités avec tous les objets qu'ils référencent et mis dans la file de
+    // finalisation. Ils y restent des racines jusqu'à ce que le mutateur lance leur finaliseur, la collection
+    // suivante les récupère.
+    unsafe fn queue_finalizable(&mut self) {
+        let unreachable = if self.finalizers.is_empty() { vec![] } else {
+            self.heap.allocated_objects.iter().copied()
+                .filter(|obj| !self.is_marked(*obj) && !self.finalized.contains(obj) && self.finalizer_of(*obj).is_some())
+                .collect::<Vec<_>>()
+        };
+        // les objets finalisés qui sont morts sont récupérés par cette collection
+        let finalized = self.finalized.iter().copied().filter(|obj| self.is_marked(*obj)).collect();
+        self.finalized = finalized;
+        self.mark_from(&unreachable);
+        self.finalization_queue.extend(unreachable);
+    }
+
+    pub fn pending_finalizers(&self) -> usize {
+        self.finalization_queue.len()
+    }
+
+    // Retirer le prochain objet de la file de finalisation avec son finaliseur, le mutateur le lance hors de la
+    // pause, et sans emprunter le ramasse-miettes puisque le finaliseur peut allouer.
+    pub unsafe fn next_finalizable(&mut self) -> Option<(*mut ObjectHeader, Finalizer)> {
+        let obj = self.finalization_queue.pop_front()?;
+        self.finalized.insert(obj);
+        Some((obj, self.finalizer_of(obj).unwrap().clone()))
+    }
+
+    // Lancer tous les finaliseurs en attente, retourne leur nombre
+    pub unsafe fn run_finalizers(gc: &Rc<RefCell<GarbageCollector>>) -> usize {
+        let mut count = 0;
+        loop {
+            let next = gc.borrow_mut().next_finalizable();
+            match next {
+                Some((obj, finalizer)) => finalizer(obj),
+                None => return count
+            }
+            count += 1;
+        }
+    }
+
+    // Les références faibles ne sont pas suivies par le marquage. Une fois le marquage terminé, un champ faible
+    // d'un objet vivant dont la cible n'est pas marquée est mis à nul, les autres seront mis à jour par le
+    // compactage comme les références ordinaires. Les cibles qui se trouvent dans un autre tas sont ignorées.
+    unsafe fn clear_dead_weak_references(&mut self) {
+        for obj in self.all_marked_bits() {
+            for (referent, offset) in self.heap.weak_pointers(obj).unwrap_or(hashset! {}) {
+                if !referent.is_null() && self.heap.allocator.get_block(referent.cast()).is_some() && !self.is_marked(referent) {
+                    ptr::write(obj.to_data_start::<u8>().add(offset) as *mut *mut ObjectHeader, ptr::null_mut());
+                }
+            }
+        }
+    }
+
+    // À appeler chaque fois que le mutateur lit la cible d'une référence faible. Pendant un cycle incrémental, la
+    // cible n'est peut-être accessible que par des références faibles, et la barrière ne voit que les anciennes
+    // valeurs des champs écrasés: si le mutateur la range dans un objet déjà parcouru, elle ne serait jamais
+    // marquée. On la grise donc comme un objet alloué pendant le cycle.
+    pub fn on_weak_load(&mut self, referent: *mut ObjectHeader) {
+        if let Some(marking) = &self.marking && !referent.is_null() {
+            marking.shaded.borrow_mut().push(referent);
+        }
     }
 }
@@ -0,0 +1,283 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+use std::mem::{align_of, swap};
+use std::ptr;
+use std::rc::Rc;
+use crate::allocator::barrier::WriteBarrier;
+use crate::allocator::heap_allocator::{HeapAllocator, HeapBlock, HeapSpan};
+use crate::allocator::object_allocator::{ObjectAllocator, ObjectHeader, ObjectHeaderHelper, MAX_COMPACT_AGE};
+use crate::gc::collector::{Collector, CollectorStats};
+use crate::gc::gc::GarbageCollector;
+use crate::gc::reachability::ObjectAllocatorExt;
+use crate::utils::errors::AllocatorError;
+use crate::vm_types::layout::Layout;
+use crate::vm_types::type_sig::TypeSig;
+
+// Un ramasse-miettes générationnel: les nouveaux objets sont alloués dans la pépinière, une collection mineure
+// copie les survivants de la pépinière vers l'espace des survivants, ou vers le tas mature s'ils ont survécu à
+// `promotion_threshold` collections mineures. Le tas mature est le ramasse-miettes "mark-compact" habituel.
+pub struct GenerationalCollector {
+    // la pépinière, tous les nouveaux objets y sont alloués
+    pub nursery: ObjectAllocator,
+    // l'espace vers lequel les survivants d'une collection mineure sont copiés, il échange son rôle avec la
+    // pépinière après chaque collection mineure
+    survivor_space: HeapAllocator,
+    pub mature: Rc<RefCell<GarbageCollector>>,
+    // les objets matures qui contiennent peut-être des références vers la pépinière, ils sont traités comme
+    // des racines par la collection mineure
+    remembered_set: Rc<RefCell<HashSet<*mut ObjectHeader>>>,
+    // les blocs de la pépinière courante, partagés avec les barrières d'écriture
+    nursery_blocks: Rc<RefCell<Vec<HeapBlock>>>,
+    // le nombre de collections mineures auxquelles chaque objet de la pépinière a survécu
+    ages: HashMap<*mut ObjectHeader, usize>,
+    pub promotion_threshold: usize,
+    // quand le tas mature dépasse cette taille, la prochaine collection par `Collector::collect` est majeure
+    major_threshold: usize,
+    stats: CollectorStats
+}
+
+// l'état d'une seule collection mineure
+struct Evacuation {
+    promote_all: bool,
+    forwarding: HashMap<*mut ObjectHeader, *mut ObjectHeader>,
+    ages: HashMap<*mut ObjectHeader, usize>,
+    promoted: Vec<*mut ObjectHeader>,
+    scan: Vec<*mut ObjectHeader>
+}
+
+// la barrière d'écriture des deux générations: un objet hors de la pépinière qui reçoit une référence est retenu,
+// la cible sera examinée par la prochaine collection mineure. Elle est aussi posée sur la pépinière, car une
+// écriture faite par la pépinière peut viser un objet mature.
+struct RememberingBarrier {
+    remembered_set: Rc<RefCell<HashSet<*mut ObjectHeader>>>,
+    nursery_blocks: Rc<RefCell<Vec<HeapBlock>>>
+}
+
+impl WriteBarrier for RememberingBarrier {
+    unsafe fn on_reference_store(&mut self, obj: *mut ObjectHeader, _offset: usize, _old: *mut ObjectHeader, new: *mut ObjectHeader) {
+        if !new.is_null() && !self.nursery_blocks.borrow().iter().any(|block| block.contains(obj.cast())) {
+            self.remembered_set.borrow_mut().insert(obj);
+        }
+    }
+}
+
+// un objet d'au moins `nursery_size / LARGE_OBJECT_FRACTION` bytes est alloué dans le tas mature
+const LARGE_OBJECT_FRACTION: usize = 4;
+
+fn may_contain_references(type_sig: usize) -> bool {
+    matches!(type_sig, TypeSig::REFERENCE | TypeSig::WEAK_REFERENCE | TypeSig::PRODUCT | TypeSig::RECORD | TypeSig::SUM | TypeSig::ARRAY)
+}
+
+impl GenerationalCollector {
+    pub unsafe fn new(nursery_size: usize, promotion_threshold: usize) -> Result<Self, AllocatorError> {
+        let mature = GarbageCollector::new();
+        let remembered_set = Rc::new(RefCell::new(HashSet::new()));
+        // chaque espace est un seul bloc qui ne grandit jamais, la liste est remise au bloc de la pépinière après
+        // chaque échange des espaces
+        let mut nursery = ObjectAllocator::new_with_allocator(HeapAllocator::new_fixed(nursery_size)?);
+        let nursery_blocks = Rc::new(RefCell::new(nursery.allocator.committed_regions.values().copied().collect()));
+        let barrier = || Box::new(RememberingBarrier { remembered_set: remembered_set.clone(), nursery_blocks: nursery_blocks.clone() });
+        mature.borrow_mut().heap.barrier = barrier();
+        let cloned = remembered_set.clone();
+        mature.borrow_mut().heap.allocation_callback = Box::new(move |obj| unsafe {
+            // on ne sait pas encore si les références de l'objet pointent vers la pépinière, on retient tous
+            // les objets qui peuvent en contenir, la prochaine collection mineure les filtrera
+            if may_contain_references(obj.type_sig()) {
+                cloned.borrow_mut().insert(obj);
+            }
+        });
+        nursery.barrier = barrier();
+        // les deux tas partagent le registre des types, un objet promu garde donc l'indice de son type
+        nursery.types = mature.borrow().heap.types.clone();
+        // les grands objets sont alloués directement dans le tas mature, ils ne tiendraient peut-être pas dans la
+        // pépinière même juste après une collection mineure. Comme l'objet n'est pas encore écrit, il est retenu
+        // quel que soit son type.
+        let (cloned_mature, cloned_set) = (mature.clone(), remembered_set.clone());
+        nursery.large_objects = Some((nursery_size / LARGE_OBJECT_FRACTION, Box::new(move |size| unsafe {
+            let mut mature = cloned_mature.borrow_mut();
+            let obj = mature.heap.allocator.alloc(size, align_of::<usize>())?;
+            mature.heap.allocated_objects.push(obj.cast());
+            cloned_set.borrow_mut().insert(obj.cast());
+            Ok(obj)
+        })));
+        Ok(GenerationalCollector {
+            nursery,
+            survivor_space: HeapAllocator::new_fixed(nursery_size)?,
+            mature,
+            remembered_set,
+            nursery_blocks,
+            ages: HashMap::new(),
+            promotion_threshold,
+            major_threshold: nursery_size * 4,
+            stats: CollectorStats::default()
+        })
+    }
+
+    pub fn in_nursery(&self, obj: *mut ObjectHeader) -> bool {
+        self.nursery.allocator.get_block(obj.cast()).is_some()
+    }
+
+    pub fn remembered_set_size(&self) -> usize {
+        self.remembered_set.borrow().len()
+    }
+
+    // Copier un objet de la pépinière vers l'espace des survivants ou vers le tas mature, l'objet copié sera
+    // parcouru plus tard afin de copier les objets auxquels il fait référence.
+    unsafe fn evacuate(&mut self, obj: *mut ObjectHeader, evacuation: &mut Evacuation) -> Result<*mut ObjectHeader, AllocatorError> {
+        if let Some(new) = evacuation.forwarding.get(&obj) {
+            return Ok(*new);
+        }
+        let size = obj.size();
+        // un en-tête compact porte l'âge de son objet, l'âge sature et un objet qui n'en a plus est promu
+        let age = obj.age().unwrap_or_else(|| self.ages.get(&obj).copied().unwrap_or(0)) + 1;
+        let survivor = if evacuation.promote_all || age >= self.promotion_threshold || (obj.is_compact() && age > MAX_COMPACT_AGE) {
+            None
+        } else {
+            // l'espace des survivants a la même taille que la pépinière, mais la fragmentation de ses blocs
+            // peut l'empêcher d'accueillir tous les survivants, ceux qui ne tiennent pas sont promus
+            self.survivor_space.alloc(size, align_of::<usize>()).ok()
+        };
+        let new = match survivor {
+            Some(new) => {
+                evacuation.ages.insert(new.cast(), age);
+                new.cast::<ObjectHeader>()
+            }
+            None => {
+                let new = self.mature.borrow_mut().heap.allocator.alloc(size, align_of::<usize>())?.cast::<ObjectHeader>();
+                evacuation.promoted.push(new);
+                new
+            }
+        };
+        ptr::copy_nonoverlapping(obj.cast::<u8>(), new.cast::<u8>(), size);
+        new.set_age(age);
+        evacuation.forwarding.insert(obj, new);
+        evacuation.scan.push(new);
+        Ok(new)
+    }
+
+    unsafe fn evacuate_nursery(&mut self, roots: &[*mut ObjectHeader], promote_all: bool) -> Result<HashMap<*mut ObjectHeader, *mut ObjectHeader>, AllocatorError> {
+        let mut evacuation = Evacuation {
+            promote_all,
+            forwarding: HashMap::new(),
+            ages: HashMap::new(),
+            promoted: vec![],
+            scan: vec![]
+        };
+        let mut new_roots = HashMap::new();
+        for root in roots {
+            let new = if self.in_nursery(*root) { self.evacuate(*root, &mut evacuation)? } else { *root };
+            new_roots.insert(*root, new);
+        }
+        // les objets retenus sont parcourus comme les objets copiés, leurs références vers la pépinière sont
+        // mises à jour sur place
+        let remembered = self.remembered_set.borrow().iter().copied().collect::<Vec<_>>();
+        evacuation.scan.extend(remembered.iter());
+        while let Some(obj) = evacuation.scan.pop() {
+            for (pointer, offset) in self.nursery.pointers(obj).unwrap() {
+                if !pointer.is_null() && self.in_nursery(pointer) {
+                    let new = self.evacuate(pointer, &mut evacuation)?;
+                    ptr::write(obj.to_data_start::<u8>().add(offset) as *mut *mut ObjectHeader, new);
+                }
+            }
+        }
+        // les références faibles vers la pépinière suivent leur cible si elle a été copiée, sinon elles sont mises
+        // à nul, la pépinière sera réutilisée
+        let weak_holders = evacuation.forwarding.values().copied().chain(remembered.iter().copied()).collect::<Vec<_>>();
+        for obj in weak_holders {
+            for (referent, offset) in self.nursery.weak_pointers(obj).unwrap() {
+                if !referent.is_null() && self.in_nursery(referent) {
+                    let new = evacuation.forwarding.get(&referent).copied().unwrap_or(ptr::null_mut());
+                    ptr::write(obj.to_data_start::<u8>().add(offset) as *mut *mut ObjectHeader, new);
+                }
+            }
+        }
+
+        // tous les objets vivants ont quitté la pépinière, l'espace des survivants devient la nouvelle pépinière
+        swap(&mut self.nursery.allocator, &mut self.survivor_space);
+        self.survivor_space.reset();
+        *self.nursery_blocks.borrow_mut() = self.nursery.allocator.committed_regions.values().copied().collect();
+        self.nursery.allocated_objects = evacuation.ages.keys().copied().collect();
+        self.ages = evacuation.ages;
+        self.mature.borrow_mut().heap.allocated_objects.extend(evacuation.promoted.iter());
+        // on ne retient que les objets matures qui pointent encore vers la nouvelle pépinière
+        let still_remembered = remembered.into_iter().chain(evacuation.promoted)
+            .filter(|obj| self.nursery.pointers(*obj).unwrap().iter().chain(self.nursery.weak_pointers(*obj).unwrap().iter())
+                .any(|(pointer, _)| !pointer.is_null() && self.in_nursery(*pointer)))
+            .collect();
+        *self.remembered_set.borrow_mut() = still_remembered;
+        Ok(new_roots)
+    }
+
+    // Une collection mineure: seule la pépinière est collectée, les objets matures ne sont ni marqués ni
+    // déplacés. Retourne les nouvelles adresses des racines.
+    pub unsafe fn minor_collect(&mut self, roots: &mut [*mut ObjectHeader]) -> Result<HashMap<*mut ObjectHeader, *mut ObjectHeader>, AllocatorError> {
+        self.evacuate_nursery(roots, false)
+    }
+
+    // Une collection majeure: la pépinière est d'abord entièrement promue, de sorte qu'aucun objet mature ne
+    // pointe vers la pépinière pendant le marquage et le compactage du tas mature.
+    pub unsafe fn major_collect(&mut self, roots: &mut [*mut ObjectHeader]) -> Result<HashMap<*mut ObjectHeader, *mut ObjectHeader>, AllocatorError> {
+        let evacuated = self.evacuate_nursery(roots, true)?;
+        let mut mature_roots = roots.iter().map(|root| evacuated[root]).collect::<Vec<_>>();
+        let compacted = self.mature.borrow_mut().collect(&mut mature_roots);
+        self.remembered_set.borrow_mut().clear();
+        Ok(roots.iter().map(|root| (*root, compacted[&evacuated[root]])).collect())
+    }
+}
+
+impl Collector for GenerationalCollector {
+    fn heap(&mut self) -> &mut ObjectAllocator {
+        &mut self.nursery
+    }
+
+    unsafe fn allocated(&self) -> usize {
+        self.nursery.allocator.allocated() + self.mature.borrow().heap.allocator.allocated()
+    }
+
+    unsafe fn mark(&mut self, roots: &[*mut ObjectHeader]) -> HashSet<*mut ObjectHeader> {
+        // `pointers` ne dépend pas de l'allocateur, le parcours traverse les deux générations
+        let mature = self.mature.borrow();
+        self.nursery.reachable_within(roots, &[&self.nursery.allocator, &mature.heap.allocator]).unwrap()
+    }
+
+    // Une collection mineure, ou majeure si le tas mature a trop grossi depuis la dernière collection majeure.
+    unsafe fn collect(&mut self, roots: &mut [*mut ObjectHeader]) -> Result<HashMap<*mut ObjectHeader, *mut ObjectHeader>, AllocatorError> {
+        let (before, started) = (self.allocated(), Instant::now());
+        let mature_size = self.mature.borrow().heap.allocator.allocated();
+        let forwarding = if mature_size > self.major_threshold {
+            let forwarding = self.major_collect(roots)?;
+            self.major_threshold = self.major_threshold.max(self.mature.borrow().heap.allocator.allocated() * 2);
+            forwarding
+        } else {
+            self.minor_collect(roots)?
+        };
+        self.stats.record(before, self.allocated(), started);
+        Ok(forwarding)
+    }
+
+    fn stats(&self) -> CollectorStats {
+        self.stats
+    }
+
+    // le tas mature reçoit un nouveau registre, la pépinière doit continuer à le partager
+    fn set_layout(&mut self, layout: Layout) -> Result<(), AllocatorError> {
+        if !self.nursery.allocated_objects.is_empty() {
+            return Err(AllocatorError::HeapNotEmpty);
+        }
+        let mut mature = self.mature.borrow_mut();
+        mature.heap.set_layout(layout)?;
+        self.nursery.types = mature.heap.types.clone();
+        Ok(())
+    }
+}
+
+impl Drop for GenerationalCollector {
+    fn drop(&mut self) {
+        unsafe {
+            self.nursery.allocator.free();
+            self.survivor_space.free();
+            self.mature.borrow_mut().heap.allocator.free();
+        }
+    }
+}
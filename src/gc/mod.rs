@@ -1,2 +1,9 @@
 pub mod reachability;
-pub mod gc;
\ No newline at end of file
+#[allow(clippy::module_inception)]
+pub mod gc;
+pub mod collector;
+pub mod generational;
+pub mod roots;
+pub mod semispace;
+pub mod conservative;
+pub mod ephemeron;
\ No newline at end of file
@@ -1,93 +1,152 @@
 use std::collections::{HashSet};
+use std::sync::Arc;
 use maplit::hashset;
+use crate::allocator::heap_allocator::HeapAllocator;
 use crate::allocator::object_allocator::{ObjectAllocator, ObjectHeader, ObjectHeaderHelper};
 use crate::utils::errors::GCError;
 use crate::vm_types::type_info::*;
 use crate::vm_types::type_kind::TypeKind;
+use crate::vm_types::type_registry::TypeRegistry;
 use crate::vm_types::type_sig::TypeSig;
 use crate::utils::func_ext::FuncExt;
 
 pub trait ObjectAllocatorExt {
     unsafe fn pointers(&self, obj_start: *mut ObjectHeader) -> Result<HashSet<(*mut ObjectHeader, usize)>, GCError>;
 
+    unsafe fn weak_pointers(&self, obj_start: *mut ObjectHeader) -> Result<HashSet<(*mut ObjectHeader, usize)>, GCError>;
+
     unsafe fn pointers_all(&self, obj_starts: &[*mut ObjectHeader]) -> Result<HashSet<(*mut ObjectHeader, usize)>, GCError>;
 
     unsafe fn reachable(&self, root_objects: &[*mut ObjectHeader]) -> Result<HashSet<*mut ObjectHeader>, GCError>;
+
+    // Comme `reachable`, mais le parcours suit les références vers tous les tas de `heaps`.
+    unsafe fn reachable_within(&self, root_objects: &[*mut ObjectHeader], heaps: &[&HeapAllocator]) -> Result<HashSet<*mut ObjectHeader>, GCError>;
 }
 
-unsafe fn reachable(allocator: &ObjectAllocator, root_object: *mut ObjectHeader) -> Result<HashSet<*mut ObjectHeader>, GCError> {
+unsafe fn reachable(allocator: &ObjectAllocator, root_object: *mut ObjectHeader, heaps: &[&HeapAllocator]) -> Result<HashSet<*mut ObjectHeader>, GCError> {
     // Calculer le clôture transitif de la relation d'accéssibilité
     // entre les objets alloués.
-    let mut reachable = vec![root_object];
-    let mut visited = HashSet::new();
+    // un objet n'est parcouru que la première fois qu'il est atteint, le graphe peut contenir des cycles. Les
+    // références nulles et celles qui sortent de `heaps` ne mènent à aucun objet.
+    let in_heaps = |ptr: *mut ObjectHeader| !ptr.is_null() && heaps.iter().any(|heap| heap.get_block(ptr.cast()).is_some());
     let mut result = HashSet::new();
+    if !in_heaps(root_object) {
+        return Ok(result);
+    }
+    let mut reachable = vec![root_object];
     result.insert(root_object);
     while let Some(ptr) = reachable.pop() {
-        let pointers = allocator.pointers(ptr)?.iter().map(|tuple| (*tuple).0)
-            .collect::<Vec<_>>();
-        pointers.into_iter().for_each(|x| {
-            let copied = x.clone();
-            result.insert(copied).ignore();
-            if !visited.contains(&x) {
+        for (x, _) in allocator.pointers(ptr)? {
+            if in_heaps(x) && result.insert(x) {
                 reachable.push(x);
-            } else {
-                visited.insert(x);
             }
-        });
+        }
     }
     Ok(result)
 }
 
+// Récupérer les pointeurs d'un seul objet. Seul le registre des types y est nécessaire, de sorte que les threads du
+// marquage parallèle puissent l'appeler sans partager l'allocateur.
+pub(crate) unsafe fn pointers(types: &TypeRegistry, obj_start: *mut ObjectHeader) -> Result<HashSet<(*mut ObjectHeader, usize)>, GCError> {
+    pointers_of_kind(types, obj_start, TypeKind::Reference)
+}
+
+// Les références faibles ne sont jamais suivies par le marquage, le ramasse-miettes les met à jour ou à nul
+// une fois le marquage terminé.
+pub(crate) unsafe fn weak_pointers(types: &TypeRegistry, obj_start: *mut ObjectHeader) -> Result<HashSet<(*mut ObjectHeader, usize)>, GCError> {
+    pointers_of_kind(types, obj_start, TypeKind::WeakReference)
+}
+
+// La table des décalages d'un agrégat, retenue par le registre: d'après son indice pour le type de l'objet lui-même,
+// d'après son nom pour un type placé dans un champ.
+fn field_table(types: &TypeRegistry, type_info: &dyn TypeInfo, id: Option<usize>, tag: usize) -> Arc<[usize]> {
+    id.and_then(|id| types.field_offsets(id, tag)).or_else(|| types.nested_offsets(type_info, tag)).unwrap()
+}
+
+// Les décalages, à partir de `base`, des champs de genre `kind` d'une valeur de type `type_info`, d'indice `id` si
+// c'est le type de l'objet lui-même. Les produits, records et sommes placés directement dans un champ sont parcourus
+// récursivement. Le cas d'une somme est lu dans l'objet lui-même, d'où `data`, le début des données de l'objet.
+unsafe fn slot_offsets(types: &TypeRegistry, type_info: &dyn TypeInfo, id: Option<usize>, kind: TypeKind, data: *mut u8, base: usize, offsets: &mut Vec<usize>) {
+    match type_info.kind() {
+        field_kind if field_kind == kind => offsets.push(base),
+        TypeKind::Product => {
+            let product_type = type_info.as_any().downcast_ref::<ProductType>().unwrap();
+            product_slot_offsets(types, product_type, &field_table(types, type_info, id, 0), kind, data, base, offsets);
+        },
+        TypeKind::Record => {
+            let record_type = type_info.as_any().downcast_ref::<RecordType>().unwrap();
+            // la table suit l'ordre de déclaration des champs
+            for (field, offset) in record_type.0.values().zip(field_table(types, type_info, id, 0).iter()) {
+                slot_offsets(types, field.as_ref(), None, kind, data, base + offset, offsets);
+            }
+        },
+        TypeKind::Sum => {
+            let sum_type = type_info.as_any().downcast_ref::<SumType>().unwrap();
+            let tag = data.add(base).cast::<usize>().read_unaligned();
+            if let Some((_, case)) = sum_type.case(tag) {
+                product_slot_offsets(types, case, &field_table(types, type_info, id, tag), kind, data, base, offsets);
+            }
+        },
+        _ => {}
+    }
+}
+
+unsafe fn product_slot_offsets(types: &TypeRegistry, type_info: &ProductType, alignment_table: &[usize], kind: TypeKind, data: *mut u8, base: usize, offsets: &mut Vec<usize>) {
+    for (field, offset) in type_info.0.iter().zip(alignment_table) {
+        slot_offsets(types, field.as_ref(), None, kind, data, base + offset, offsets);
+    }
+}
+
+unsafe fn pointers_of_kind(types: &TypeRegistry, obj_start: *mut ObjectHeader, kind: TypeKind) -> Result<HashSet<(*mut ObjectHeader, usize)>, GCError> {
+    // NOTE: first cast obj_start to u8 and add to alignment then cast to *usize, now we have
+    // a pointer that points to the address of the referee, and after that we dereference
+    // it to get the referee's address.
+    let read_slots = |offsets: Vec<usize>| -> HashSet<(*mut ObjectHeader, usize)> {
+        offsets.into_iter()
+            .map(|offset| (*obj_start.to_data_start::<u8>().add(offset).cast::<usize>() as *mut ObjectHeader, offset))
+            .collect()
+    };
+    let read_pointers = |type_info: &dyn TypeInfo| -> HashSet<(*mut ObjectHeader, usize)> {
+        let mut offsets = vec![];
+        slot_offsets(types, type_info, Some(obj_start.type_index()), kind, obj_start.to_data_start(), 0, &mut offsets);
+        read_slots(offsets)
+    };
+    let type_info = || types.get(obj_start.type_index()).ok_or(GCError::FailedToReadObjectAt(obj_start as *const usize));
+    match obj_start.type_sig() {
+        TypeSig::NAT | TypeSig::INT | TypeSig::DOUBLE | TypeSig::CHAR | TypeSig::BOOL | TypeSig::STRING | TypeSig::FILLER => Ok(hashset!{}),
+        // NOTE: first cast obj_start to u8 and add to alignment then cast to usize, now we have
+        // a pointer that points to the address of the referee, and after that we dereference
+        // it to get the referee's address.
+        TypeSig::REFERENCE | TypeSig::WEAK_REFERENCE if TypeSig::to_type_kind(obj_start.type_sig()) == kind =>
+            Ok(hashset!{(*obj_start.to_data_start::<usize>() as *mut ObjectHeader, 0)}),
+        TypeSig::REFERENCE | TypeSig::WEAK_REFERENCE => Ok(hashset!{}),
+        TypeSig::PRODUCT | TypeSig::RECORD | TypeSig::SUM =>
+            Ok(read_pointers(type_info()?.as_ref())),
+        TypeSig::ARRAY => {
+            let type_info = type_info()?;
+            let type_info = type_info.as_any().downcast_ref::<ArrayType>().unwrap();
+            let length = *obj_start.to_data_start::<usize>();
+            // chaque élément est parcouru comme un champ placé à son décalage, les éléments agrégats aussi
+            let mut offsets = vec![];
+            for index in 0..length {
+                slot_offsets(types, type_info.0.as_ref(), None, kind, obj_start.to_data_start(), type_info.element_offset(index, types.layout()), &mut offsets);
+            }
+            Ok(read_slots(offsets))
+        },
+        _ => Err(GCError::FailedToReadObjectAt(obj_start as *const usize))
+    }
+}
+
 impl ObjectAllocatorExt for ObjectAllocator {
     // Récupérer tous les pointeurs dont les objets alloués contient.
     // noinspection all
     // Return value: the pointers and their offsets relative to the data start.
     unsafe fn pointers(&self, obj_start: *mut ObjectHeader) -> Result<HashSet<(*mut ObjectHeader, usize)>, GCError> {
-        let read_product_pointers = |type_info: &ProductType| -> HashSet<(*mut ObjectHeader, usize)> {
-            type_info.0.iter().enumerate()
-                .filter(|(_, x)| x.kind() == TypeKind::Reference)
-                .map(|x| x.0)
-                .collect::<Vec<usize>>()
-                .iter().map(|x| {
-                let alignment_at = *(*type_info).alignment_table().get(*x).unwrap();
-                // NOTE: first cast obj_start to u8 and add to alignment then cast to *usize, now we have
-                // a pointer that points to the address of the referee, and after that we dereference
-                // it to get the referee's address.
-                (*obj_start.to_data_start::<u8>().add(alignment_at).cast::<usize>() as *mut ObjectHeader, alignment_at)
-            }).collect()
-        };
-        let header = &*obj_start;
-        match header.type_sig {
-            TypeSig::NAT | TypeSig::INT | TypeSig::DOUBLE | TypeSig::CHAR | TypeSig::BOOL => Ok(hashset!{}),
-            // NOTE: first cast obj_start to u8 and add to alignment then cast to usize, now we have
-            // a pointer that points to the address of the referee, and after that we dereference
-            // it to get the referee's address.
-            TypeSig::REFERENCE =>
-                Ok(hashset!{(*obj_start.to_data_start::<usize>() as *mut ObjectHeader, 0)}),
-            TypeSig::PRODUCT =>
-                Ok(read_product_pointers(&*header.ptr_to_type_info.cast::<ProductType>())),
-            TypeSig::RECORD => {
-                let type_info = &*header.ptr_to_type_info.cast::<RecordType>();
-                let res = type_info.0.iter()
-                    .filter(|(_, x)| x.kind() == TypeKind::Reference)
-                    .map(|x| x.0.clone())
-                    .collect::<Vec<String>>()
-                    .iter().map(|x| {
-                    let alignment_at = *(*type_info).alignment_table().get(x).unwrap();
-                    // NOTE: first cast obj_start to u8 and add to alignment then cast to usize, now we have
-                    // a pointer that points to the address of the referee, and after that we dereference
-                    // it to get the referee's address.
-                    (*obj_start.to_data_start::<u8>().add(alignment_at).cast::<usize>() as *mut ObjectHeader, alignment_at)
-                }).collect();
-                Ok(res)
-            },
-            TypeSig::SUM => {
-                let type_info = header.ptr_to_type_info as *const SumType;
-                let variant = (*type_info).0.get(&(*type_info).1).unwrap().clone();
-                Ok(read_product_pointers(variant.as_ref()))
-            },
-            _ => Err(GCError::FailedToReadObjectAt(obj_start as *const usize))
-        }
+        pointers(&self.types, obj_start)
+    }
+
+    unsafe fn weak_pointers(&self, obj_start: *mut ObjectHeader) -> Result<HashSet<(*mut ObjectHeader, usize)>, GCError> {
+        weak_pointers(&self.types, obj_start)
     }
 
     unsafe fn pointers_all(&self, obj_starts: &[*mut ObjectHeader]) -> Result<HashSet<(*mut ObjectHeader, usize)>, GCError> {
@@ -98,12 +157,16 @@ impl ObjectAllocatorExt for ObjectAllocator {
     }
 
     unsafe fn reachable(&self, root_objects: &[*mut ObjectHeader]) -> Result<HashSet<*mut ObjectHeader>, GCError> {
+        self.reachable_within(root_objects, &[&self.allocator])
+    }
+
+    unsafe fn reachable_within(&self, root_objects: &[*mut ObjectHeader], heaps: &[&HeapAllocator]) -> Result<HashSet<*mut ObjectHeader>, GCError> {
         if root_objects.is_empty() {
             return Ok(HashSet::new());
         }
-        root_objects.iter().fold(reachable(self, root_objects[0]), |acc, e|
+        root_objects.iter().fold(reachable(self, root_objects[0], heaps), |acc, e|
             acc.and_then(|mut acc_set|
-                reachable(self, *e).map(|new|
+                reachable(self, *e, heaps).map(|new|
                     acc_set.apply(|a| a.extend(new)).clone())))
     }
 }
\ No newline at end of file
@@ -0,0 +1,126 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::allocator::object_allocator::ObjectHeader;
+
+// Les racines enregistrées auprès du ramasse-miettes. Chaque racine est une case qui contient l'adresse d'un
+// objet, `GarbageCollector::collect` les ajoute à ses propres racines et met les cases à jour après le
+// compactage, le mutateur n'a donc jamais à manipuler la table des nouvelles adresses.
+#[derive(Default)]
+pub struct RootRegistry {
+    // les racines des portées, empilées: chaque portée retient la hauteur de la pile à son ouverture
+    scoped: Vec<*mut ObjectHeader>,
+    // le nombre de portées ouvertes, seule la plus récente peut créer des racines
+    depth: usize,
+    // les racines globales, une case vide est réutilisée par la prochaine racine globale
+    globals: Vec<Option<*mut ObjectHeader>>
+}
+
+impl RootRegistry {
+    pub fn new() -> Rc<RefCell<RootRegistry>> {
+        Rc::new(RefCell::new(RootRegistry::default()))
+    }
+
+    // toutes les racines enregistrées, les cases nulles sont ignorées
+    pub fn roots(&self) -> Vec<*mut ObjectHeader> {
+        self.scoped.iter().copied()
+            .chain(self.globals.iter().flatten().copied())
+            .filter(|root| !root.is_null())
+            .collect()
+    }
+
+    pub fn forward(&mut self, forwarding: &HashMap<*mut ObjectHeader, *mut ObjectHeader>) {
+        self.scoped.iter_mut()
+            .chain(self.globals.iter_mut().flatten())
+            .for_each(|root| if let Some(new) = forwarding.get(root) {
+                *root = *new;
+            });
+    }
+}
+
+// Une portée de racines locales, toutes les racines créées par `root` sont retirées quand la portée est
+// détruite. Les portées s'emboîtent comme les blocs du programme, seule la plus récente peut créer des racines.
+pub struct HandleScope {
+    registry: Rc<RefCell<RootRegistry>>,
+    height: usize,
+    depth: usize
+}
+
+impl HandleScope {
+    pub fn new(registry: &Rc<RefCell<RootRegistry>>) -> HandleScope {
+        let mut borrowed = registry.borrow_mut();
+        borrowed.depth += 1;
+        HandleScope { registry: registry.clone(), height: borrowed.scoped.len(), depth: borrowed.depth }
+    }
+
+    pub fn root(&self, obj: *mut ObjectHeader) -> Local<'_> {
+        let mut registry = self.registry.borrow_mut();
+        if registry.depth != self.depth {
+            panic!("Only the innermost handle scope can create roots");
+        }
+        registry.scoped.push(obj);
+        Local { scope: self, index: registry.scoped.len() - 1 }
+    }
+}
+
+impl Drop for HandleScope {
+    fn drop(&mut self) {
+        let mut registry = self.registry.borrow_mut();
+        registry.scoped.truncate(self.height);
+        registry.depth -= 1;
+    }
+}
+
+// Une racine locale, elle ne peut pas survivre à sa portée
+#[derive(Copy, Clone)]
+pub struct Local<'s> {
+    scope: &'s HandleScope,
+    index: usize
+}
+
+impl Local<'_> {
+    pub fn get(&self) -> *mut ObjectHeader {
+        self.scope.registry.borrow().scoped[self.index]
+    }
+
+    pub fn set(&self, obj: *mut ObjectHeader) {
+        self.scope.registry.borrow_mut().scoped[self.index] = obj;
+    }
+}
+
+// Une racine globale, elle reste enregistrée jusqu'à sa destruction
+pub struct Global {
+    registry: Rc<RefCell<RootRegistry>>,
+    index: usize
+}
+
+impl Global {
+    pub fn new(registry: &Rc<RefCell<RootRegistry>>, obj: *mut ObjectHeader) -> Global {
+        let mut borrowed = registry.borrow_mut();
+        let index = match borrowed.globals.iter().position(Option::is_none) {
+            Some(index) => {
+                borrowed.globals[index] = Some(obj);
+                index
+            }
+            None => {
+                borrowed.globals.push(Some(obj));
+                borrowed.globals.len() - 1
+            }
+        };
+        Global { registry: registry.clone(), index }
+    }
+
+    pub fn get(&self) -> *mut ObjectHeader {
+        self.registry.borrow().globals[self.index].unwrap()
+    }
+
+    pub fn set(&self, obj: *mut ObjectHeader) {
+        self.registry.borrow_mut().globals[self.index] = Some(obj);
+    }
+}
+
+impl Drop for Global {
+    fn drop(&mut self) {
+        self.registry.borrow_mut().globals[self.index] = None;
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+use std::mem::{align_of, swap};
+use std::ptr;
+use crate::allocator::heap_allocator::{HeapAllocator, HeapBlock, HeapSpan};
+use crate::allocator::object_allocator::{ObjectAllocator, ObjectHeader, ObjectHeaderHelper};
+use crate::gc::collector::{Collector, CollectorStats};
+use crate::gc::reachability::ObjectAllocatorExt;
+use crate::utils::errors::AllocatorError;
+use crate::vm_types::type_sig::TypeSig;
+
+// Un ramasse-miettes à copie "semispace", d'après l'algorithme de Cheney: le tas est divisé en deux blocs de même
+// taille, on alloue dans l'espace d'origine, et une collection copie les objets accessibles vers l'espace de
+// destination, puis les deux espaces échangent leurs rôles. La liste d'attente est l'espace de destination
+// lui-même: les objets entre `scan` et le pointeur d'allocation ont été copiés mais pas encore parcourus.
+// Il n'y a ni bitmap ni table de décalages, le coût d'une collection ne dépend que des objets vivants.
+pub struct SemispaceCollector {
+    // l'espace d'origine, tous les nouveaux objets y sont alloués
+    pub heap: ObjectAllocator,
+    to_space: HeapAllocator,
+    pub semispace_size: usize,
+    stats: CollectorStats
+}
+
+impl SemispaceCollector {
+    pub unsafe fn new(semispace_size: usize) -> Result<Self, AllocatorError> {
+        Ok(SemispaceCollector {
+            heap: ObjectAllocator::new_with_allocator(HeapAllocator::new_fixed(semispace_size)?),
+            to_space: HeapAllocator::new_fixed(semispace_size)?,
+            semispace_size,
+            stats: CollectorStats::default()
+        })
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    fn from_space(&self) -> &HeapBlock {
+        self.heap.allocator.committed_regions.values().next().unwrap()
+    }
+
+    fn to_space(&self) -> &HeapBlock {
+        self.to_space.committed_regions.values().next().unwrap()
+    }
+
+    // Copier un objet de l'espace d'origine vers l'espace de destination, sauf s'il y a déjà été copié: dans ce
+    // cas son en-tête a été remplacé par un en-tête `FORWARDED` qui contient la nouvelle adresse.
+    unsafe fn forward(&mut self, obj: *mut ObjectHeader) -> *mut ObjectHeader {
+        if obj.type_sig() == TypeSig::FORWARDED {
+            return obj.forwarding_address();
+        }
+        let size = obj.size();
+        // l'espace de destination a la même taille que l'espace d'origine, les objets vivants y tiennent toujours
+        let new = self.to_space.alloc(size, align_of::<usize>()).unwrap().cast::<ObjectHeader>();
+        ptr::copy_nonoverlapping(obj.cast::<u8>(), new.cast::<u8>(), size);
+        obj.forward_to(new);
+        new
+    }
+
+    // Collecte l'espace d'origine, retourne les nouvelles adresses des racines. Toutes les autres adresses de
+    // l'espace d'origine deviennent invalides.
+    pub unsafe fn collect(&mut self, roots: &mut [*mut ObjectHeader]) -> HashMap<*mut ObjectHeader, *mut ObjectHeader> {
+        let mut new_roots = HashMap::new();
+        for root in roots.iter() {
+            let new = if self.from_space().contains(root.cast()) { self.forward(*root) } else { *root };
+            new_roots.insert(*root, new);
+        }
+
+        let mut scan = self.to_space().start.cast::<ObjectHeader>();
+        let mut copied = vec![];
+        while scan.cast::<u8>() < self.to_space().unallocated_start {
+            for (pointer, offset) in self.heap.pointers(scan).unwrap() {
+                if !pointer.is_null() && self.from_space().contains(pointer.cast()) {
+                    let new = self.forward(pointer);
+                    ptr::write(scan.to_data_start::<u8>().add(offset) as *mut *mut ObjectHeader, new);
+                }
+            }
+            copied.push(scan);
+            let obj_end = scan.cast::<u8>().add(scan.size());
+            scan = obj_end.add((!(obj_end as usize) + 1) & (align_of::<usize>() - 1)).cast();
+        }
+
+        // les références faibles n'ont pas été suivies: celles dont la cible a été copiée suivent leur cible, les
+        // autres sont mises à nul avant que l'espace d'origine ne soit libéré
+        for obj in copied.iter() {
+            for (referent, offset) in self.heap.weak_pointers(*obj).unwrap() {
+                if !referent.is_null() && self.from_space().contains(referent.cast()) {
+                    let new = if referent.type_sig() == TypeSig::FORWARDED { referent.forwarding_address() } else { ptr::null_mut() };
+                    ptr::write(obj.to_data_start::<u8>().add(offset) as *mut *mut ObjectHeader, new);
+                }
+            }
+        }
+
+        // tous les objets vivants ont été copiés, l'espace d'origine entier est libre
+        swap(&mut self.heap.allocator, &mut self.to_space);
+        self.to_space.reset();
+        self.heap.allocated_objects = copied;
+        new_roots
+    }
+}
+
+impl Collector for SemispaceCollector {
+    fn heap(&mut self) -> &mut ObjectAllocator {
+        &mut self.heap
+    }
+
+    unsafe fn allocated(&self) -> usize {
+        self.heap.allocator.allocated()
+    }
+
+    unsafe fn mark(&mut self, roots: &[*mut ObjectHeader]) -> HashSet<*mut ObjectHeader> {
+        self.heap.reachable(roots).unwrap()
+    }
+
+    unsafe fn collect(&mut self, roots: &mut [*mut ObjectHeader]) -> Result<HashMap<*mut ObjectHeader, *mut ObjectHeader>, AllocatorError> {
+        let (before, started) = (self.allocated(), Instant::now());
+        let forwarding = SemispaceCollector::collect(self, roots);
+        self.stats.record(before, self.allocated(), started);
+        Ok(forwarding)
+    }
+
+    fn stats(&self) -> CollectorStats {
+        self.stats
+    }
+}
+
+impl Drop for SemispaceCollector {
+    fn drop(&mut self) {
+        unsafe {
+            self.heap.allocator.free();
+            self.to_space.free();
+        }
+    }
+}
@@ -0,0 +1,444 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use linked_hash_map::LinkedHashMap;
+use crate::allocator::object_allocator::{FieldRef, ObjectAllocator, ObjectHeader, ObjectHeaderHelper, Variant};
+use crate::allocator::value::Value;
+use crate::gc::collector::{Collector, CollectorKind, CollectorStats};
+use crate::gc::gc::{GarbageCollector, SweepMode};
+use crate::utils::errors::{AllocatorError, HeapError};
+use crate::utils::func_ext::OptionExt;
+use crate::vm_types::layout::Layout;
+use crate::vm_types::type_info::*;
+use crate::vm_types::type_kind::TypeKind;
+use crate::vm_types::type_sig::TypeSig;
+
+static NEXT_HEAP_ID: AtomicUsize = AtomicUsize::new(0);
+
+// Les marqueurs des handles dont les objets n'ont pas de représentation Rust
+pub enum Object {}
+pub enum Product {}
+pub enum Record {}
+pub enum Sum {}
+pub enum Reference {}
+pub enum WeakReference {}
+pub enum Array {}
+pub enum Str {}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RawHandle {
+    ptr: *mut ObjectHeader,
+    heap: usize,
+    // l'époque du tas au moment où le handle a été émis, chaque collection incrémente l'époque du tas,
+    // seuls les handles passés comme racines à la collection sont mis à jour.
+    epoch: usize
+}
+
+// A typed reference to an object living in a `Heap`. Handles are only valid until the next
+// collection unless they are passed to it as roots, using an outdated handle is reported as
+// `HeapError::StaleHandle` instead of dereferencing a dangling pointer.
+pub struct Handle<T> {
+    raw: RawHandle,
+    _marker: PhantomData<T>
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> Handle<T> {
+    pub fn erase(self) -> Handle<Object> {
+        Handle { raw: self.raw, _marker: PhantomData }
+    }
+}
+
+pub trait Root {
+    fn raw_mut(&mut self) -> &mut RawHandle;
+}
+
+impl<T> Root for Handle<T> {
+    fn raw_mut(&mut self) -> &mut RawHandle {
+        &mut self.raw
+    }
+}
+
+pub trait Scalar: Copy + 'static {
+    const TYPE_SIG: usize;
+
+    unsafe fn write(heap: &mut ObjectAllocator, value: Self) -> Result<*mut ObjectHeader, AllocatorError>;
+}
+
+impl Scalar for i64 {
+    const TYPE_SIG: usize = TypeSig::INT;
+
+    unsafe fn write(heap: &mut ObjectAllocator, value: Self) -> Result<*mut ObjectHeader, AllocatorError> {
+        heap.write_int(value)
+    }
+}
+
+impl Scalar for u64 {
+    const TYPE_SIG: usize = TypeSig::NAT;
+
+    unsafe fn write(heap: &mut ObjectAllocator, value: Self) -> Result<*mut ObjectHeader, AllocatorError> {
+        heap.write_nat(value)
+    }
+}
+
+impl Scalar for f64 {
+    const TYPE_SIG: usize = TypeSig::DOUBLE;
+
+    unsafe fn write(heap: &mut ObjectAllocator, value: Self) -> Result<*mut ObjectHeader, AllocatorError> {
+        heap.write_double(value)
+    }
+}
+
+impl Scalar for char {
+    const TYPE_SIG: usize = TypeSig::CHAR;
+
+    unsafe fn write(heap: &mut ObjectAllocator, value: Self) -> Result<*mut ObjectHeader, AllocatorError> {
+        heap.write_char(value)
+    }
+}
+
+impl Scalar for bool {
+    const TYPE_SIG: usize = TypeSig::BOOL;
+
+    unsafe fn write(heap: &mut ObjectAllocator, value: Self) -> Result<*mut ObjectHeader, AllocatorError> {
+        heap.write_bool(value)
+    }
+}
+
+// Une façade sûre au-dessus de `GarbageCollector`: toutes les opérations vérifient la validité
+// des handles avant de toucher la mémoire, de sorte qu'aucun appel ne requiert `unsafe`.
+pub struct Heap {
+    id: usize,
+    epoch: usize,
+    collector: RefCell<Box<dyn Collector>>
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Heap {
+    pub fn new() -> Self {
+        Self::with_collector(CollectorKind::MarkCompact).unwrap()
+    }
+
+    pub fn with_collector(kind: CollectorKind) -> Result<Self, HeapError> {
+        Ok(Heap {
+            id: NEXT_HEAP_ID.fetch_add(1, Ordering::Relaxed),
+            epoch: 0,
+            collector: RefCell::new(unsafe { kind.build()? })
+        })
+    }
+
+    // Les objets alloués ensuite ont un en-tête compact d'un seul mot s'ils y tiennent, les objets déjà alloués
+    // gardent leur en-tête, les deux formats cohabitent dans le même tas.
+    pub fn set_compact_headers(&mut self, enabled: bool) {
+        self.collector.get_mut().heap().compact_headers = enabled;
+    }
+
+    // La disposition des données des objets, elle ne peut être choisie que tant que le tas est vide.
+    pub fn set_layout(&mut self, layout: Layout) -> Result<(), HeapError> {
+        Ok(self.collector.get_mut().set_layout(layout)?)
+    }
+
+    // le ramasse-miettes à bitmap, seuls les tas construits sur lui offrent le marquage incrémental, parallèle
+    // et le balayage
+    fn garbage_collector(&self) -> Result<Rc<RefCell<GarbageCollector>>, HeapError> {
+        self.collector.borrow().garbage_collector().cloned().ok_or(HeapError::UnsupportedByCollector)
+    }
+
+    fn handle<T>(&self, ptr: *mut ObjectHeader) -> Handle<T> {
+        Handle {
+            raw: RawHandle { ptr, heap: self.id, epoch: self.epoch },
+            _marker: PhantomData
+        }
+    }
+
+    fn check(&self, raw: &RawHandle) -> Result<*mut ObjectHeader, HeapError> {
+        if raw.heap != self.id {
+            Err(HeapError::ForeignHandle)
+        } else if raw.epoch != self.epoch {
+            Err(HeapError::StaleHandle)
+        } else {
+            Ok(raw.ptr)
+        }
+    }
+
+    pub fn alloc<T: Scalar>(&mut self, value: T) -> Result<Handle<T>, HeapError> {
+        let p = unsafe { T::write(self.collector.get_mut().heap(), value)? };
+        Ok(self.handle(p))
+    }
+
+    pub fn get<T: Scalar>(&self, handle: Handle<T>) -> Result<T, HeapError> {
+        let p = self.check(&handle.raw)?;
+        Ok(unsafe { *p.to_data_start::<T>() })
+    }
+
+    pub fn alloc_str(&mut self, value: &str) -> Result<Handle<Str>, HeapError> {
+        let p = unsafe { self.collector.get_mut().heap().write_string(value)? };
+        Ok(self.handle(p))
+    }
+
+    // la chaîne emprunte le tas, aucune collection ne peut donc la déplacer tant qu'elle est lue
+    pub fn get_str(&self, handle: Handle<Str>) -> Result<&str, HeapError> {
+        let p = self.check(&handle.raw)?;
+        Ok(unsafe { self.collector.borrow_mut().heap().read_string(p)? })
+    }
+
+    pub fn alloc_reference<T>(&mut self, target: Handle<T>) -> Result<Handle<Reference>, HeapError> {
+        let p = self.check(&target.raw)?;
+        let type_info = ReferenceType(unsafe { p.type_sig() });
+        let reference = unsafe { self.collector.get_mut().heap().write_reference(p as usize, &type_info)? };
+        Ok(self.handle(reference))
+    }
+
+    pub fn deref(&self, reference: Handle<Reference>) -> Result<Handle<Object>, HeapError> {
+        let p = self.check(&reference.raw)?;
+        Ok(self.handle(unsafe { *p.to_data_start::<usize>() } as *mut ObjectHeader))
+    }
+
+    // Une référence faible ne garde pas sa cible en vie, elle est mise à nul quand la cible est récupérée.
+    pub fn alloc_weak_reference<T>(&mut self, target: Handle<T>) -> Result<Handle<WeakReference>, HeapError> {
+        let p = self.check(&target.raw)?;
+        let type_info = WeakReferenceType(unsafe { p.type_sig() });
+        let reference = unsafe { self.collector.get_mut().heap().write_weak_reference(p as usize, &type_info)? };
+        Ok(self.handle(reference))
+    }
+
+    // `None` si la cible a été récupérée
+    pub fn deref_weak(&self, reference: Handle<WeakReference>) -> Result<Option<Handle<Object>>, HeapError> {
+        let p = self.check(&reference.raw)?;
+        let target = unsafe { *p.to_data_start::<usize>() } as *mut ObjectHeader;
+        if let Ok(gc) = self.garbage_collector() {
+            gc.borrow_mut().on_weak_load(target);
+        }
+        Ok((!target.is_null()).then(|| self.handle(target)))
+    }
+
+    // Reference fields must be given as `Handle<Object>`, they are checked and lowered to addresses here.
+    pub fn alloc_product(&mut self, type_info: &ProductType, fields: &[Arc<dyn Any>]) -> Result<Handle<Product>, HeapError> {
+        let lowered = self.lower_fields(&type_info.0, fields)?;
+        let p = unsafe { self.collector.get_mut().heap().write_product(&lowered, type_info)? };
+        Ok(self.handle(p))
+    }
+
+    pub fn alloc_record(&mut self, type_info: &RecordType, fields: &LinkedHashMap<String, Arc<dyn Any>>) -> Result<Handle<Record>, HeapError> {
+        let lowered = self.lower_record_fields(type_info, fields)?;
+        let p = unsafe { self.collector.get_mut().heap().write_record(&lowered, type_info)? };
+        Ok(self.handle(p))
+    }
+
+    pub fn alloc_sum(&mut self, type_info: &SumType, case: &str, fields: &[Arc<dyn Any>]) -> Result<Handle<Sum>, HeapError> {
+        let lowered = self.lower_variant(type_info, case, fields)?;
+        let p = unsafe { self.collector.get_mut().heap().write_sum(case, &lowered.fields, type_info)? };
+        Ok(self.handle(p))
+    }
+
+    // le nom du cas choisi, lu dans l'objet
+    pub fn sum_case(&self, sum: Handle<Sum>) -> Result<String, HeapError> {
+        let p = self.check(&sum.raw)?;
+        let (tag, type_info) = unsafe {
+            let mut collector = self.collector.borrow_mut();
+            (collector.heap().read_tag(p)?, collector.heap().type_of(p)?)
+        };
+        let sum_type = type_info.as_any().downcast_ref::<SumType>().to_result(|| AllocatorError::TypeMismatch("Expected a sum".to_string()))?;
+        Ok(sum_type.case(tag).to_result(|| AllocatorError::ReadObjectFailed(format!("Invalid tag {}", tag)))?.0.clone())
+    }
+
+    // Change le cas d'une somme vivante sans la déplacer, les références de l'ancien et du nouveau cas passent
+    // par la barrière d'écriture du tas.
+    pub fn set_variant(&mut self, sum: Handle<Sum>, case: &str, fields: &[Arc<dyn Any>]) -> Result<(), HeapError> {
+        let p = self.check(&sum.raw)?;
+        let type_info = unsafe { self.collector.get_mut().heap().type_of(p)? };
+        let sum_type = type_info.as_any().downcast_ref::<SumType>().to_result(|| AllocatorError::TypeMismatch("Expected a sum".to_string()))?;
+        let lowered = self.lower_variant(sum_type, case, fields)?;
+        unsafe { self.collector.get_mut().heap().set_variant(p, case, &lowered.fields)? };
+        Ok(())
+    }
+
+    // Reference elements must be given as `Handle<Object>`, like the fields of `alloc_product`.
+    pub fn alloc_array(&mut self, type_info: &ArrayType, elements: &[Arc<dyn Any>]) -> Result<Handle<Array>, HeapError> {
+        let lowered = elements.iter().map(|element| self.lower_field(&type_info.0, element)).collect::<Result<Vec<_>, _>>()?;
+        let p = unsafe { self.collector.get_mut().heap().write_array(&lowered, type_info)? };
+        Ok(self.handle(p))
+    }
+
+    pub fn array_length(&self, array: Handle<Array>) -> Result<usize, HeapError> {
+        let p = self.check(&array.raw)?;
+        Ok(unsafe { self.collector.borrow_mut().heap().array_length(p)? })
+    }
+
+    fn lower_fields(&self, types: &[Arc<dyn TypeInfo>], fields: &[Arc<dyn Any>]) -> Result<Vec<Arc<dyn Any>>, HeapError> {
+        if types.len() != fields.len() {
+            return Err(AllocatorError::ProductSizeMismatch.into());
+        }
+        types.iter().zip(fields.iter()).map(|(ty, field)| self.lower_field(ty, field)).collect()
+    }
+
+    fn lower_variant(&self, type_info: &SumType, case: &str, fields: &[Arc<dyn Any>]) -> Result<Variant, HeapError> {
+        let selected = type_info.0.get(case)
+            .to_result(|| AllocatorError::ObjectAllocationFailed(format!("Unknown case {}", case)))?;
+        Ok(Variant::new(case, self.lower_fields(&selected.0, fields)?))
+    }
+
+    fn lower_record_fields(&self, type_info: &RecordType, fields: &LinkedHashMap<String, Arc<dyn Any>>) -> Result<LinkedHashMap<String, Arc<dyn Any>>, HeapError> {
+        let mut lowered = LinkedHashMap::<String, Arc<dyn Any>>::new();
+        for (name, ty) in type_info.0.iter() {
+            let field = fields.get(name)
+                .to_result(|| AllocatorError::FailedToReadData(format!("Missing data for field {}", name)))?;
+            lowered.insert(name.clone(), self.lower_field(ty, field)?);
+        }
+        Ok(lowered)
+    }
+
+    fn lower_field(&self, type_info: &Arc<dyn TypeInfo>, field: &Arc<dyn Any>) -> Result<Arc<dyn Any>, HeapError> {
+        let mismatch = || AllocatorError::FailedToReadData(format!("Failed to read data for a field of type {}", type_info.name()));
+        // les agrégats placés dans un champ sont abaissés champ par champ
+        match type_info.kind() {
+            TypeKind::Product => {
+                let product_type = type_info.as_any().downcast_ref::<ProductType>().to_result(mismatch)?;
+                let fields = field.downcast_ref::<Vec<Arc<dyn Any>>>().to_result(mismatch)?;
+                return Ok(Arc::new(self.lower_fields(&product_type.0, fields)?));
+            },
+            TypeKind::Sum => {
+                let sum_type = type_info.as_any().downcast_ref::<SumType>().to_result(mismatch)?;
+                let variant = field.downcast_ref::<Variant>().to_result(mismatch)?;
+                return Ok(Arc::new(self.lower_variant(sum_type, &variant.case, &variant.fields)?));
+            },
+            TypeKind::Record => {
+                let record_type = type_info.as_any().downcast_ref::<RecordType>().to_result(mismatch)?;
+                let fields = field.downcast_ref::<LinkedHashMap<String, Arc<dyn Any>>>().to_result(mismatch)?;
+                return Ok(Arc::new(self.lower_record_fields(record_type, fields)?));
+            },
+            kind if !kind.is_reference() => return Ok(field.clone()),
+            _ => {}
+        }
+        let handle = field.downcast_ref::<Handle<Object>>()
+            .to_result(|| AllocatorError::FailedToReadData("Reference fields must be given as Handle<Object>".to_string()))?;
+        Ok(Arc::new(self.check(&handle.raw)? as usize))
+    }
+
+    // Met à jour un champ d'un objet vivant, les références passent par la barrière d'écriture du tas et
+    // doivent être données comme `Handle<Object>`, de la même manière que pour `alloc_product`.
+    pub fn set_field<'a, T>(&mut self, handle: Handle<T>, field: impl Into<FieldRef<'a>>, value: Arc<dyn Any>) -> Result<(), HeapError> {
+        let p = self.check(&handle.raw)?;
+        let field = field.into();
+        let (type_info, _) = unsafe { self.collector.get_mut().heap().field_slot(p, field)? };
+        let lowered = self.lower_field(&type_info, &value)?;
+        unsafe { self.collector.get_mut().heap().set_field(p, field, &lowered)? };
+        Ok(())
+    }
+
+    // Lit un seul champ d'un objet vivant, les références sont rendues comme des adresses, comme pour `read`.
+    pub fn get_field<'a, T>(&self, handle: Handle<T>, field: impl Into<FieldRef<'a>>) -> Result<Value, HeapError> {
+        let p = self.check(&handle.raw)?;
+        Ok(unsafe { self.collector.borrow_mut().heap().get_field(p, field)? })
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn read<T>(&self, handle: Handle<T>) -> Result<(Arc<dyn TypeInfo>, Arc<dyn Any>), HeapError> {
+        let p = self.check(&handle.raw)?;
+        Ok(unsafe { self.collector.borrow_mut().heap().read_obj(p)? })
+    }
+
+    pub fn kind<T>(&self, handle: Handle<T>) -> Result<TypeKind, HeapError> {
+        let p = self.check(&handle.raw)?;
+        Ok(TypeSig::to_type_kind(unsafe { p.type_sig() }))
+    }
+
+    // Collecte le tas, tous les objets inaccessibles depuis `roots` sont récupérés. Les racines sont
+    // mises à jour en place, tous les autres handles deviennent périmés.
+    pub fn collect(&mut self, roots: &mut [&mut dyn Root]) -> Result<(), HeapError> {
+        let mut raw_roots = self.raw_roots(roots)?;
+        let forwarding = unsafe { self.collector.get_mut().collect(&mut raw_roots)? };
+        self.forward_roots(roots, &forwarding);
+        Ok(())
+    }
+
+    // Commence un marquage incrémental depuis `roots`. Aucun objet n'est déplacé avant `finish_collection`,
+    // tous les handles restent donc valables entre les étapes, le mutateur peut allouer et appeler `set_field`.
+    pub fn start_marking(&mut self, roots: &mut [&mut dyn Root]) -> Result<(), HeapError> {
+        let raw_roots = self.raw_roots(roots)?;
+        unsafe { self.garbage_collector()?.borrow_mut().start_marking(&raw_roots) };
+        Ok(())
+    }
+
+    // Parcourt au plus `budget` objets, retourne `true` lorsque le marquage est terminé.
+    pub fn mark_step(&mut self, budget: usize) -> Result<bool, HeapError> {
+        Ok(unsafe { self.garbage_collector()?.borrow_mut().mark_step(budget) })
+    }
+
+    // Termine le marquage en cours puis compacte le tas, les racines sont mises à jour comme pour `collect`.
+    pub fn finish_collection(&mut self, roots: &mut [&mut dyn Root]) -> Result<(), HeapError> {
+        let mut raw_roots = self.raw_roots(roots)?;
+        let forwarding = unsafe { self.garbage_collector()?.borrow_mut().finish_collection(&mut raw_roots) };
+        self.forward_roots(roots, &forwarding);
+        Ok(())
+    }
+
+    fn forward_roots(&mut self, roots: &mut [&mut dyn Root], forwarding: &HashMap<*mut ObjectHeader, *mut ObjectHeader>) {
+        self.epoch += 1;
+        for root in roots.iter_mut() {
+            let raw = root.raw_mut();
+            // les racines nulles ou hors du tas ne sont pas dans la table, elles restent en place
+            raw.ptr = forwarding.get(&raw.ptr).copied().unwrap_or(raw.ptr);
+            raw.epoch = self.epoch;
+        }
+    }
+
+    // Comme `collect`, mais aucun objet n'est déplacé: les racines gardent leurs adresses, seule leur époque
+    // est mise à jour. Retourne le nombre de bytes récupérés.
+    pub fn collect_non_moving(&mut self, roots: &mut [&mut dyn Root], mode: SweepMode) -> Result<usize, HeapError> {
+        let mut raw_roots = self.raw_roots(roots)?;
+        let reclaimed = unsafe { self.garbage_collector()?.borrow_mut().collect_non_moving(&mut raw_roots, mode) };
+        self.epoch += 1;
+        roots.iter_mut().for_each(|root| root.raw_mut().epoch = self.epoch);
+        Ok(reclaimed)
+    }
+
+    // Le nombre de threads qui marquent le tas pendant les collections suivantes.
+    pub fn set_mark_threads(&mut self, threads: usize) -> Result<(), HeapError> {
+        self.garbage_collector()?.borrow_mut().mark_threads = threads.max(1);
+        Ok(())
+    }
+
+    // Le finaliseur des objets de type `type_info`, il est lancé par `run_finalizers` après la collection qui
+    // trouve l'objet inaccessible, l'objet est récupéré par la collection suivante.
+    pub fn declare_finalizer(&mut self, type_info: &dyn TypeInfo, finalizer: impl Fn(*mut ObjectHeader) + 'static) -> Result<(), HeapError> {
+        self.garbage_collector()?.borrow_mut().declare_finalizer(type_info, finalizer);
+        Ok(())
+    }
+
+    // Lancer les finaliseurs en attente, retourne leur nombre
+    pub fn run_finalizers(&mut self) -> Result<usize, HeapError> {
+        let gc = self.garbage_collector()?;
+        Ok(unsafe { GarbageCollector::run_finalizers(&gc) })
+    }
+
+    fn raw_roots(&self, roots: &mut [&mut dyn Root]) -> Result<Vec<*mut ObjectHeader>, HeapError> {
+        roots.iter_mut()
+            .map(|root| self.check(root.raw_mut()))
+            .collect()
+    }
+
+    pub fn allocated(&self) -> usize {
+        unsafe { self.collector.borrow().allocated() }
+    }
+
+    pub fn stats(&self) -> CollectorStats {
+        self.collector.borrow().stats()
+    }
+}
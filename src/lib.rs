@@ -1,21 +1,14 @@
 #![allow(incomplete_features)]
+#![allow(clippy::missing_safety_doc)]
+// les données des objets circulent en `Arc<dyn Any>` sans jamais quitter le thread du tas
+#![allow(clippy::arc_with_non_send_sync)]
 #![feature(ptr_sub_ptr)]
 #![feature(downcast_unchecked)]
-#![feature(unsized_locals, unsized_fn_params)]
 #![feature(box_into_inner)]
 #![feature(allocator_api)]
 #![feature(iter_collect_into)]
 #![feature(let_chains)]
 
-use crate::test::gc::test_reachability;
-use crate::test::mocking::ObjectMocker;
-
-pub(crate) mod allocator;
-mod gc;
-mod test;
-pub(crate) mod utils;
-pub(crate) mod vm_types;
-
 // On a en fait une raison plutôt raisonnable pour ne pas implementer le soi-disant "buddy algorithm"
 // D'abord, le buddy algorithm a pour effet de reduire la fragmentation de la mémoire, mais dans un heap
 // tous les objets qui on va allouer a pour taille de puissance de 2, donc il n'y a pas de fragmentation
@@ -23,9 +16,17 @@ pub(crate) mod vm_types;
 // de fusionner les blocs "buddies" après la libération d'un bloc, mais dans un heap, on a le ramasse-miettes,
 // spécifiquement on a un ramasse-miettes à "mark-compact", la reduction de fragementation sera
 // effectuée dans la phase de fragementation du ramasse-miettes.
-fn main() {
-    unsafe {
-        let mut mocker = ObjectMocker::new();
-        test_reachability(&mut mocker);
-    }
-}
+
+// le code généré par `#[derive(HostType)]` nomme ce crate `::mem_allocator`, y compris dans ses propres tests
+extern crate self as mem_allocator;
+
+pub mod allocator;
+pub mod gc;
+pub mod heap;
+#[cfg(test)]
+mod test;
+pub mod utils;
+pub mod vm_types;
+
+pub use heap::{Handle, Heap};
+pub use utils::errors::{AllocatorError, GCError, HeapError};
@@ -0,0 +1,154 @@
+use std::any::Any;
+use std::collections::HashSet;
+use std::sync::Arc;
+use linked_hash_map::LinkedHashMap;
+use crate::allocator::heap_allocator::HeapSpan;
+use crate::allocator::object_allocator::{ObjectHeader, ObjectHeaderHelper};
+use crate::gc::collector::CollectorKind;
+use crate::gc::gc::{GarbageCollector, SweepMode};
+use crate::gc::reachability::ObjectAllocatorExt;
+use crate::heap::Heap;
+use crate::utils::errors::AllocatorError;
+use crate::vm_types::layout::Layout;
+use crate::vm_types::type_info::{ArrayType, RecordType, ReferenceType, TypeInfo};
+use crate::vm_types::type_sig::TypeSig;
+use crate::vm_types::type_tokens;
+
+unsafe fn ints(values: &[Arc<dyn Any>]) -> Vec<i64> {
+    values.iter().map(|value| *value.downcast_ref::<i64>().unwrap()).collect()
+}
+
+unsafe fn walk(gc: &GarbageCollector) -> HashSet<*mut ObjectHeader> {
+    let mut walked = HashSet::new();
+    for block in gc.heap.allocator.committed_regions.values() {
+        let mut cur = GarbageCollector::next_object(block, None);
+        while let Some(c) = cur && block.contains(c.cast()) {
+            if c.type_sig() != TypeSig::FILLER {
+                walked.insert(c);
+            }
+            cur = GarbageCollector::next_object(block, Some(c));
+        }
+    }
+    walked
+}
+
+// Des tableaux d'entiers de longueurs différentes, et des tableaux de références vers des entiers. Seule une partie
+// des tableaux est accessible, les autres objets sont des déchets intercalés afin que le compactage déplace tout.
+#[test]
+fn test_arrays() {
+    unsafe {
+        let int_array = ArrayType(Arc::new(type_tokens::INT));
+        let ref_array = ArrayType(Arc::new(ReferenceType(TypeSig::INT)));
+        let gc = GarbageCollector::new();
+        let (mut arrays, mut ref_arrays) = {
+            let mut borrowed = gc.borrow_mut();
+            let mut arrays = vec![];
+            let mut ref_arrays = vec![];
+            for length in 0..12 {
+                borrowed.heap.write_array(&(0..length).map(|i| Arc::new(-i) as Arc<dyn Any>).collect::<Vec<_>>(), &int_array).unwrap();
+                let elements = (0..length).map(|i| Arc::new(length * 100 + i) as Arc<dyn Any>).collect::<Vec<_>>();
+                arrays.push(borrowed.heap.write_array(&elements, &int_array).unwrap());
+                let targets = (0..length).map(|i| Arc::new(borrowed.heap.write_int(length * 10 + i).unwrap() as usize) as Arc<dyn Any>).collect::<Vec<_>>();
+                ref_arrays.push(borrowed.heap.write_array(&targets, &ref_array).unwrap());
+            }
+            (arrays, ref_arrays)
+        };
+        {
+            let mut borrowed = gc.borrow_mut();
+            assert!(arrays.iter().enumerate().all(|(i, array)| borrowed.heap.array_length(*array).unwrap() == i), "Les tableaux retiennent leur longueur");
+            assert!(arrays.iter().enumerate().all(|(i, array)| ints(&borrowed.heap.read_array(*array).unwrap()) == (0..i as i64).map(|j| i as i64 * 100 + j).collect::<Vec<_>>()), "Les tableaux sont lus dans l'ordre");
+            assert!(ref_arrays.iter().enumerate().all(|(i, array)| borrowed.heap.pointers(*array).unwrap().len() == i), "Chaque élément référence est un pointeur");
+            assert_eq!(walk(&borrowed), borrowed.heap.allocated_objects.iter().copied().collect::<HashSet<_>>(), "Le tas des tableaux est parcourable");
+            let last = ref_arrays[10];
+            let target = borrowed.heap.write_int(7).unwrap();
+            borrowed.heap.set_reference(last, 3, target).unwrap();
+            let out_of_range = borrowed.heap.set_reference(last, 10, target);
+            assert!(matches!(out_of_range, Err(AllocatorError::UnknownField(_))), "Les éléments hors du tableau sont refusés");
+        }
+
+        let mut roots = arrays.iter().chain(ref_arrays.iter()).step_by(2).copied().collect::<Vec<_>>();
+        let forwarding = gc.borrow_mut().collect(&mut roots);
+        arrays = arrays.iter().step_by(2).map(|array| forwarding[array]).collect();
+        ref_arrays = ref_arrays.iter().step_by(2).map(|array| forwarding[array]).collect();
+        let borrowed = gc.borrow_mut();
+        assert!(arrays.iter().enumerate().all(|(i, array)| {
+            let length = 2 * i as i64;
+            ints(&borrowed.heap.read_array(*array).unwrap()) == (0..length).map(|j| length * 100 + j).collect::<Vec<_>>()
+        }), "Les tableaux déplacés sont intacts");
+        assert!(ref_arrays.iter().enumerate().all(|(i, array)| {
+            let length = 2 * i;
+            (0..length).all(|j| {
+                let target = *array.to_data_start::<u8>().add(ref_array.element_offset(j, Layout::Padded)).cast::<*mut ObjectHeader>();
+                let expected = if length == 10 && j == 3 { 7 } else { (length * 10 + j) as i64 };
+                *target.to_data_start::<i64>() == expected
+            })
+        }), "Les éléments des tableaux suivent leurs cibles");
+        let live = arrays.iter().chain(ref_arrays.iter()).copied().collect::<Vec<_>>();
+        assert_eq!(borrowed.heap.allocated_objects.len(), borrowed.heap.reachable(&live).unwrap().len(), "Seuls les tableaux accessibles ont survécu");
+        assert_eq!(walk(&borrowed), borrowed.heap.allocated_objects.iter().copied().collect::<HashSet<_>>(), "Le tas compacté est parcourable");
+        drop(borrowed);
+
+        // les éléments sont tracés par tous les ramasse-miettes
+        let kinds = [
+            CollectorKind::MarkSweep(SweepMode::FreeList),
+            CollectorKind::Semispace { semispace_size: 64 * 1024 },
+            CollectorKind::Generational { nursery_size: 16 * 1024, promotion_threshold: 2 }
+        ];
+        for kind in kinds {
+            let mut collector = kind.build().unwrap();
+            let targets = (0..20).map(|i| Arc::new(collector.heap().write_int(i).unwrap() as usize) as Arc<dyn Any>).collect::<Vec<_>>();
+            let mut array = collector.heap().write_array(&targets, &ref_array).unwrap();
+            for _ in 0..3 {
+                (0..50).for_each(|i| {
+                    collector.heap().write_int(-i).unwrap();
+                });
+                array = collector.collect(&mut [array]).unwrap()[&array];
+            }
+            let values = (0..20).map(|i| *(*array.to_data_start::<u8>().add(ref_array.element_offset(i, Layout::Padded)).cast::<*mut ObjectHeader>()).to_data_start::<i64>()).collect::<Vec<_>>();
+            assert_eq!(values, (0..20).collect::<Vec<_>>(), "Les cibles des éléments ont survécu ({:?})", kind);
+        }
+
+        let mut heap = Heap::new();
+        let targets = (0..5).map(|i| Arc::new(heap.alloc(i as i64).unwrap().erase()) as Arc<dyn Any>).collect::<Vec<_>>();
+        let mut array = heap.alloc_array(&ref_array, &targets).unwrap();
+        heap.collect(&mut [&mut array]).unwrap();
+        let (type_info, elements) = heap.read(array).unwrap();
+        assert!(type_info.name() == "[&Int]" && heap.array_length(array).unwrap() == 5
+            && elements.downcast_ref::<Vec<Arc<dyn Any>>>().unwrap().iter().all(|element| !(*element.downcast_ref::<usize>().unwrap() as *mut ObjectHeader).is_null()), "Le tas alloue des tableaux");
+    }
+}
+
+// Un tableau de records qui contiennent des références: les références des éléments sont tracées, et le compactage
+// les met à jour lorsqu'il déplace leurs cibles.
+#[test]
+fn test_record_arrays() {
+    unsafe {
+        let mut fields = LinkedHashMap::<String, Arc<dyn TypeInfo>>::new();
+        fields.insert("id".to_string(), Arc::new(type_tokens::INT));
+        fields.insert("target".to_string(), Arc::new(ReferenceType(TypeSig::INT)));
+        let entry_array = ArrayType(Arc::new(RecordType(Arc::new(fields))));
+        let gc = GarbageCollector::new();
+        let mut array = {
+            let mut borrowed = gc.borrow_mut();
+            let elements = (0..10i64).map(|i| {
+                // des déchets devant chaque cible, afin que le compactage la déplace
+                borrowed.heap.write_int(-i).unwrap();
+                let mut entry = LinkedHashMap::<String, Arc<dyn Any>>::new();
+                entry.insert("id".to_string(), Arc::new(i));
+                entry.insert("target".to_string(), Arc::new(borrowed.heap.write_int(i * 10).unwrap() as usize));
+                Arc::new(entry) as Arc<dyn Any>
+            }).collect::<Vec<_>>();
+            let array = borrowed.heap.write_array(&elements, &entry_array).unwrap();
+            assert_eq!(borrowed.heap.pointers(array).unwrap().len(), 10, "Les références des éléments sont des pointeurs");
+            array
+        };
+        array = gc.borrow_mut().collect(&mut [array])[&array];
+        let borrowed = gc.borrow();
+        assert_eq!(borrowed.heap.allocated_objects.len(), 11, "Le tableau et les cibles de ses éléments ont survécu");
+        let targets = borrowed.heap.read_array(array).unwrap().iter().map(|element| {
+            let entry = element.downcast_ref::<LinkedHashMap<String, Arc<dyn Any>>>().unwrap();
+            *(*entry["target"].downcast_ref::<usize>().unwrap() as *mut ObjectHeader).to_data_start::<i64>()
+        }).collect::<Vec<_>>();
+        assert_eq!(targets, (0..10).map(|i| i * 10).collect::<Vec<_>>(), "Les références des éléments suivent leurs cibles");
+    }
+}
@@ -0,0 +1,100 @@
+use std::any::Any;
+use std::collections::HashSet;
+use std::ptr;
+use std::sync::Arc;
+use rand::Rng;
+use rand::seq::SliceRandom;
+use crate::allocator::object_allocator::{ObjectHeader, ObjectHeaderHelper};
+use crate::gc::collector::{Collector, CollectorKind};
+use crate::gc::gc::SweepMode;
+use crate::gc::reachability::ObjectAllocatorExt;
+use crate::test::mocking::ObjectMocker;
+use crate::utils::errors::AllocatorError;
+use crate::utils::io::format_read_object;
+use crate::vm_types::type_info::{ProductType, ReferenceType};
+use crate::vm_types::type_sig::TypeSig;
+
+// La même charge pour tous les ramasse-miettes: on alloue des objets au hasard, une partie devient racine, et on
+// collecte quand le tas est plein ou toutes les 1000 allocations pour les tas qui ne sont jamais pleins.
+pub unsafe fn run_workload(collector: &mut dyn Collector, obj_mocker: &mut ObjectMocker, allocations: usize) -> bool {
+    let mut roots: Vec<*mut ObjectHeader> = vec![];
+    let mut all_preserved = true;
+    obj_mocker.mocked_objects_ptrs.clear();
+    for i in 1..=allocations {
+        let mut obj = obj_mocker.mock_object(0, false).unwrap();
+        // la collection périodique a lieu avant l'allocation, sinon le nouvel objet, qui n'est pas encore une
+        // racine, serait récupéré
+        let periodic = i % 1000 == 0;
+        let result = (!periodic).then(|| collector.heap().allocate_general(&obj));
+        if periodic || matches!(result, Some(Err(AllocatorError::OutOfMemory))) {
+            roots.shuffle(&mut rand::thread_rng());
+            roots.truncate(20);
+            let live = collector.mark(&roots).len();
+            // les objets scalaires ne changent pas en étant déplacés
+            let scalars = roots.iter().filter(|root| (TypeSig::NAT..=TypeSig::BOOL).contains(&root.type_sig()))
+                .map(|root| (*root, format_read_object(&collector.heap().read_obj(*root).unwrap()))).collect::<Vec<_>>();
+            let forwarding = collector.collect(&mut roots).unwrap();
+            roots = roots.iter().map(|root| forwarding[root]).collect();
+            all_preserved &= collector.mark(&roots).len() == live;
+            all_preserved &= scalars.into_iter().all(|(old, formatted)| format_read_object(&collector.heap().read_obj(forwarding[&old]).unwrap()) == formatted);
+            obj_mocker.mocked_objects_ptrs = roots.iter().map(|root| (TypeSig::to_type_kind(root.type_sig()), *root)).collect();
+        }
+        let allocated = match result {
+            Some(Err(AllocatorError::OutOfMemory)) | None => {
+                // les références de l'objet mocké pointent peut-être vers des objets déplacés
+                obj = obj_mocker.mock_object(0, false).unwrap();
+                collector.heap().allocate_general(&obj).unwrap()
+            }
+            Some(res) => res.unwrap()
+        };
+        obj_mocker.mocked_objects_ptrs.push((obj.0.kind(), allocated));
+        if rand::thread_rng().gen_bool(0.1) {
+            roots.push(allocated);
+        }
+    }
+    all_preserved
+}
+
+#[test]
+fn test_all_collectors() {
+    unsafe {
+        let obj_mocker = &mut ObjectMocker::new();
+        let kinds = [
+            CollectorKind::MarkCompact,
+            CollectorKind::MarkSweep(SweepMode::FreeList),
+            CollectorKind::MarkSweep(SweepMode::Filler),
+            CollectorKind::Semispace { semispace_size: 64 * 1024 },
+            CollectorKind::Generational { nursery_size: 16 * 1024, promotion_threshold: 3 }
+        ];
+        for kind in kinds {
+            let mut collector = kind.build().unwrap();
+            let preserved = run_workload(collector.as_mut(), obj_mocker, 5000);
+            let stats = collector.stats();
+            assert!(stats.collections > 0 && stats.bytes_reclaimed > 0, "Le collecteur a récupéré de la mémoire ({:?})", kind);
+            assert!(preserved, "Tous les objets accessibles ont survécu ({:?})", kind);
+        }
+    }
+}
+
+// Une référence nulle ne mène à aucun objet, le marquage des ramasse-miettes qui passent par `reachable` l'ignore.
+#[test]
+fn test_null_references() {
+    unsafe {
+        let kinds = [
+            CollectorKind::Semispace { semispace_size: 64 * 1024 },
+            CollectorKind::Generational { nursery_size: 16 * 1024, promotion_threshold: 3 }
+        ];
+        for kind in kinds {
+            let mut collector = kind.build().unwrap();
+            let target = collector.heap().write_int(7).unwrap();
+            let holder_type = ProductType(vec![Arc::new(ReferenceType(TypeSig::INT)), Arc::new(ReferenceType(TypeSig::INT))]);
+            let fields = [Arc::new(0usize) as Arc<dyn Any>, Arc::new(target as usize)];
+            let holder = collector.heap().write_product(&fields, &holder_type).unwrap();
+            let null = collector.heap().write_reference(0, &ReferenceType(TypeSig::INT)).unwrap();
+            assert_eq!(collector.mark(&[holder, null, ptr::null_mut()]), HashSet::from([holder, target, null]), "Les références nulles sont ignorées ({:?})", kind);
+            let forwarding = collector.collect(&mut [holder]).unwrap();
+            let (moved, _) = collector.heap().pointers(forwarding[&holder]).unwrap().into_iter().find(|(pointer, _)| !pointer.is_null()).unwrap();
+            assert_eq!(*moved.to_data_start::<i64>(), 7, "La cible non nulle a survécu ({:?})", kind);
+        }
+    }
+}
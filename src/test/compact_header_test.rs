@@ -0,0 +1,102 @@
+use std::mem::size_of;
+use std::sync::Arc;
+use crate::allocator::object_allocator::{ObjectAllocator, ObjectHeader, ObjectHeaderHelper, MAX_COMPACT_AGE};
+use crate::gc::collector::CollectorKind;
+use crate::gc::gc::{GarbageCollector, SweepMode};
+use crate::gc::generational::GenerationalCollector;
+use crate::gc::semispace::SemispaceCollector;
+use crate::heap::Heap;
+use crate::test::collector_test::run_workload;
+use crate::test::mocking::ObjectMocker;
+use crate::utils::io::format_read_object;
+use crate::vm_types::type_info::{ProductType, ReferenceType};
+use crate::vm_types::type_registry::TypeRegistry;
+use crate::vm_types::type_sig::TypeSig;
+use crate::vm_types::type_tokens;
+
+// Les en-têtes compacts sont décodés par tous les ramasse-miettes, et cohabitent avec les en-têtes complets.
+#[test]
+fn test_compact_headers() {
+    unsafe {
+        let obj_mocker = &mut ObjectMocker::new();
+        let mut full = ObjectAllocator::new();
+        let mut compact = ObjectAllocator::new();
+        compact.compact_headers = true;
+        let int = compact.write_int(-42).unwrap();
+        assert!(int.is_compact() && int.header_size() == size_of::<usize>() && int.size() == 16
+            && int.type_sig() == TypeSig::INT && int.type_index() == TypeRegistry::INT && *int.to_data_start::<i64>() == -42, "L'en-tête compact tient dans un mot");
+        let string = compact.write_string("abc").unwrap();
+        assert!(string.size() == 24 && compact.read_string(string).unwrap() == "abc", "La taille d'un objet compact est arrondie au mot");
+        assert!(ObjectHeader::compact(TypeSig::ARRAY, size_of::<usize>() << 22, 20).is_none()
+            && ObjectHeader::compact(TypeSig::STRING, 12, TypeRegistry::STRING).is_none(), "Les objets trop grands gardent un en-tête complet");
+        let before = compact.allocator.allocated();
+        for _ in 0..1000 {
+            full.write_int(1).unwrap();
+            compact.write_int(1).unwrap();
+        }
+        assert!((compact.allocator.allocated() - before) * 2 <= full.allocator.allocated(), "Les petits objets prennent deux fois moins de place");
+
+        // les pointeurs d'un produit compact suivent leurs cibles déplacées par le compactage
+        let gc = GarbageCollector::new();
+        gc.borrow_mut().heap.compact_headers = true;
+        let point = ProductType(vec![Arc::new(type_tokens::INT), Arc::new(ReferenceType(TypeSig::INT))]);
+        let mut objects = {
+            let mut borrowed = gc.borrow_mut();
+            (0..10).for_each(|i| {
+                borrowed.heap.write_int(i).unwrap();
+            });
+            let target = borrowed.heap.write_int(7).unwrap();
+            let empty = borrowed.heap.write_product(&[], &ProductType(vec![])).unwrap();
+            vec![borrowed.heap.write_product(&[Arc::new(1i64), Arc::new(target as usize)], &point).unwrap(), empty]
+        };
+        let forwarding = gc.borrow_mut().collect(&mut objects);
+        objects = objects.iter().map(|obj| forwarding[obj]).collect();
+        let target = *objects[0].to_data_start::<u8>().add(8).cast::<*mut ObjectHeader>();
+        assert!(*target.to_data_start::<i64>() == 7 && target.is_compact()
+            && objects[1].size() == size_of::<usize>() && gc.borrow().heap.allocated_objects.len() == 3, "Le compactage déplace les objets compacts");
+
+        // un objet sans données ne fait qu'un mot, l'adresse de sa copie tient tout de même dans son en-tête
+        let mut semispace = SemispaceCollector::new(16 * 1024).unwrap();
+        semispace.heap.compact_headers = true;
+        let mut roots = vec![semispace.heap.write_product(&[], &ProductType(vec![])).unwrap(), semispace.heap.write_nat(9).unwrap()];
+        let forwarding = semispace.collect(&mut roots);
+        roots = roots.iter().map(|root| forwarding[root]).collect();
+        assert!(roots[0].is_compact() && roots[0].size() == size_of::<usize>()
+            && format_read_object(&semispace.heap.read_obj(roots[1]).unwrap()) == "Type: Nat, données: 9", "Les objets compacts d'un seul mot sont copiés");
+
+        // l'âge est gardé dans l'en-tête compact
+        let mut generational = GenerationalCollector::new(16 * 1024, 3).unwrap();
+        generational.nursery.compact_headers = true;
+        let mut roots = vec![generational.nursery.write_int(5).unwrap()];
+        let mut ages = vec![];
+        for _ in 0..3 {
+            let forwarding = generational.minor_collect(&mut roots).unwrap();
+            roots = roots.iter().map(|root| forwarding[root]).collect();
+            ages.push((roots[0].age(), generational.in_nursery(roots[0])));
+        }
+        assert_eq!(ages, vec![(Some(1), true), (Some(2), true), (Some(3), false)], "L'âge est gardé dans l'en-tête");
+        roots[0].set_age(100);
+        assert_eq!(roots[0].age(), Some(MAX_COMPACT_AGE), "L'âge sature");
+
+        let kinds = [
+            CollectorKind::MarkCompact,
+            CollectorKind::MarkSweep(SweepMode::FreeList),
+            CollectorKind::MarkSweep(SweepMode::Filler),
+            CollectorKind::Semispace { semispace_size: 64 * 1024 },
+            CollectorKind::Generational { nursery_size: 16 * 1024, promotion_threshold: 3 }
+        ];
+        for kind in kinds {
+            let mut collector = kind.build().unwrap();
+            collector.heap().compact_headers = true;
+            assert!(run_workload(collector.as_mut(), obj_mocker, 5000), "Tous les objets accessibles ont survécu avec des en-têtes compacts ({:?})", kind);
+        }
+
+        let mut heap = Heap::new();
+        let full = heap.alloc(1i64).unwrap();
+        heap.set_compact_headers(true);
+        let mut objects = [full, heap.alloc(2i64).unwrap()];
+        let [first, second] = &mut objects;
+        heap.collect(&mut [first, second]).unwrap();
+        assert!(heap.get(objects[0]).unwrap() == 1 && heap.get(objects[1]).unwrap() == 2, "Les deux formats cohabitent dans le tas");
+    }
+}
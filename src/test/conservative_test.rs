@@ -0,0 +1,54 @@
+use std::collections::HashSet;
+use rand::Rng;
+use crate::allocator::object_allocator::{ObjectHeader, ObjectHeaderHelper};
+use crate::gc::reachability::ObjectAllocatorExt;
+use crate::test::mocking::ObjectMocker;
+use crate::utils::io::format_read_object;
+use crate::vm_types::type_sig::TypeSig;
+
+#[test]
+fn test_conservative_roots() {
+    unsafe {
+        let obj_mocker = &mut ObjectMocker::new();
+        let mut allocated_ptrs = vec![];
+        (0..1000).for_each(|_| {
+            let res = obj_mocker.mock_and_allocate_object().unwrap();
+            allocated_ptrs.push(res.1);
+        });
+        let pinned = (0..10).map(|_| allocated_ptrs[rand::thread_rng().gen_range(0..1000)]).collect::<HashSet<*mut ObjectHeader>>();
+        let in_register = allocated_ptrs[rand::thread_rng().gen_range(0..1000)];
+        // les objets scalaires ne changent pas quand les objets qu'ils référencent sont déplacés, contrairement aux références
+        let before = pinned.iter().chain([&in_register]).filter(|obj| (TypeSig::NAT..=TypeSig::BOOL).contains(&obj.type_sig())).map(|obj| (*obj, format_read_object(&obj_mocker.allocator.borrow_mut().heap.read_obj(*obj).unwrap()))).collect::<Vec<_>>();
+
+        // une fausse pile: les adresses des objets, des pointeurs intérieurs, des entiers et une adresse de la pile
+        let local = 42usize;
+        let mut stack = [0usize; 64];
+        for (i, obj) in pinned.iter().enumerate() {
+            stack[i * 4] = *obj as usize;
+            stack[i * 4 + 1] = *obj as usize + 8;
+            stack[i * 4 + 2] = *obj as usize + 1;
+            stack[i * 4 + 3] = rand::thread_rng().gen_range(0..usize::MAX);
+        }
+        stack[63] = &local as *const usize as usize;
+        // la pile croît vers le bas, les bornes sont données du haut vers le bas
+        let found = obj_mocker.allocator.borrow_mut().scan_stack(stack.as_ptr().add(stack.len()), stack.as_ptr());
+        assert_eq!(found.iter().copied().collect::<HashSet<_>>(), pinned, "Seuls les débuts d'objets sont retenus");
+        let registers = [in_register as usize + 16, in_register as usize, 0, usize::MAX];
+        assert_eq!(obj_mocker.allocator.borrow_mut().scan_registers(&registers), vec![in_register], "La copie des registres est parcourue");
+
+        let mut roots = pinned.iter().copied().chain([in_register]).collect::<Vec<_>>();
+        let reachables = obj_mocker.allocator.borrow().heap.reachable(&roots).unwrap();
+        // aucune racine précise, les racines conservatives suffisent
+        let forwarding = obj_mocker.allocator.borrow_mut().collect(&mut []);
+        assert!(forwarding.iter().all(|(old, new)| old == new), "Les racines conservatives n'ont pas été déplacées");
+        let after = before.iter().map(|(obj, _)| (*obj, format_read_object(&obj_mocker.allocator.borrow_mut().heap.read_obj(*obj).unwrap()))).collect::<Vec<_>>();
+        assert_eq!(before, after, "Les objets épinglés sont intacts");
+        assert_eq!(obj_mocker.allocator.borrow().heap.allocated_objects.len(), reachables.len(), "Seuls les objets accessibles ont survécu");
+
+        // les racines conservatives ne valent que pour une collection
+        let forwarding = obj_mocker.allocator.borrow_mut().collect(&mut roots);
+        assert_eq!(obj_mocker.allocator.borrow().heap.reachable(&roots.iter().map(|root| forwarding[root]).collect::<Vec<_>>()).unwrap().len(), reachables.len(), "La collection suivante compacte le tas");
+        obj_mocker.allocator.borrow_mut().collect(&mut []);
+        assert_eq!(obj_mocker.allocator.borrow().heap.allocator.allocated(), 0, "Le tas est vide");
+    }
+}
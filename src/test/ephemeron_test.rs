@@ -0,0 +1,76 @@
+use std::sync::Arc;
+use crate::allocator::object_allocator::{ObjectHeader, ObjectHeaderHelper};
+use crate::gc::gc::{GarbageCollector, SweepMode};
+use crate::gc::reachability::ObjectAllocatorExt;
+use crate::vm_types::type_info::{ProductType, ReferenceType};
+use crate::vm_types::type_sig::TypeSig;
+use crate::vm_types::type_tokens;
+
+// Des entiers servent de clés, chaque valeur est un produit qui référence sa propre clé et ne doit pas la garder
+// en vie. Seules les clés paires sont des racines. Une chaîne d'entrées dont chaque valeur est la clé de l'entrée
+// suivante n'est accessible que par sa première clé.
+#[test]
+fn test_ephemerons() {
+    unsafe {
+        let memo_type = ProductType(vec![Arc::new(type_tokens::INT), Arc::new(ReferenceType(TypeSig::INT))]);
+        for moving in [true, false] {
+            let gc = GarbageCollector::new();
+            let table = gc.borrow_mut().new_ephemeron_table();
+            let (mut keys, mut chain, offset) = {
+                let mut borrowed = gc.borrow_mut();
+                // des déchets devant les clés, afin que le compactage les déplace
+                (0..50).for_each(|i| {
+                    borrowed.heap.write_int(-i).unwrap();
+                });
+                let keys = (0..10).map(|i| borrowed.heap.write_int(i).unwrap()).collect::<Vec<_>>();
+                for (i, key) in keys.iter().enumerate() {
+                    let memo = borrowed.heap.write_product(&[Arc::new(i as i64 * 10), Arc::new(*key as usize)], &memo_type).unwrap();
+                    table.borrow_mut().insert(*key, memo);
+                }
+                let chain = (0..3).map(|i| borrowed.heap.write_int(100 + i).unwrap()).collect::<Vec<_>>();
+                table.borrow_mut().insert(chain[0], chain[1]);
+                table.borrow_mut().insert(chain[1], chain[2]);
+                let offset = borrowed.heap.field_slot(table.borrow().get(keys[0]).unwrap(), 1.into()).unwrap().1;
+                (keys, chain, offset)
+            };
+
+            let mut roots = keys.iter().step_by(2).copied().chain([chain[0]]).collect::<Vec<_>>();
+            if moving {
+                let forwarding = gc.borrow_mut().collect(&mut roots);
+                keys = keys.iter().step_by(2).map(|key| forwarding[key]).collect();
+                chain = vec![forwarding[&chain[0]]];
+            } else {
+                gc.borrow_mut().collect_non_moving(&mut roots, SweepMode::FreeList);
+                keys = keys.iter().step_by(2).copied().collect();
+                chain.truncate(1);
+            }
+
+            let table = table.borrow();
+            assert_eq!(table.len(), keys.len() + 2, "Les entrées des clés mortes ont été retirées (déplacement: {})", moving);
+            assert!(keys.iter().enumerate().all(|(i, key)| {
+                let memo = table.get(*key).unwrap();
+                *memo.to_data_start::<i64>() == 20 * i as i64
+                    && *memo.to_data_start::<u8>().add(offset).cast::<*mut ObjectHeader>() == *key
+                    && *key.to_data_start::<i64>() == 2 * i as i64
+            }), "Les valeurs suivent leurs clés (déplacement: {})", moving);
+            let second = table.get(chain[0]);
+            let third = second.and_then(|second| table.get(second));
+            assert!(third.is_some_and(|third| *second.unwrap().to_data_start::<i64>() == 101 && *third.to_data_start::<i64>() == 102), "La chaîne d'éphémérons est gardée en vie (déplacement: {})", moving);
+            if moving {
+                let borrowed = gc.borrow();
+                let live = borrowed.heap.reachable(&keys.iter().copied().chain(chain.iter().copied()).chain(table.entries().map(|(_, value)| value)).collect::<Vec<_>>()).unwrap();
+                assert_eq!(borrowed.heap.allocated_objects.len(), live.len(), "Les valeurs des clés mortes ont été récupérées");
+            }
+        }
+
+        // une table détruite n'est plus examinée par les collections suivantes
+        let gc = GarbageCollector::new();
+        let table = gc.borrow_mut().new_ephemeron_table();
+        let key = gc.borrow_mut().heap.write_int(1).unwrap();
+        let value = gc.borrow_mut().heap.write_int(2).unwrap();
+        table.borrow_mut().insert(key, value);
+        drop(table);
+        gc.borrow_mut().collect(&mut [key]);
+        assert_eq!(gc.borrow().heap.allocated_objects.len(), 1, "Une table détruite ne garde plus ses valeurs en vie");
+    }
+}
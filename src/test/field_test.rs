@@ -0,0 +1,97 @@
+use std::any::Any;
+use std::sync::Arc;
+use linked_hash_map::LinkedHashMap;
+use crate::allocator::object_allocator::ObjectAllocator;
+use crate::allocator::value::Value;
+use crate::gc::gc::GarbageCollector;
+use crate::heap::Heap;
+use crate::utils::errors::{AllocatorError, HeapError};
+use crate::vm_types::layout::Layout;
+use crate::vm_types::type_info::{ArrayType, ProductType, RecordType, ReferenceType, SumType, TypeInfo};
+use crate::vm_types::type_sig::TypeSig;
+use crate::vm_types::type_tokens;
+
+fn account() -> RecordType {
+    let mut fields = LinkedHashMap::<String, Arc<dyn TypeInfo>>::new();
+    fields.insert("open".to_string(), Arc::new(type_tokens::BOOL));
+    fields.insert("balance".to_string(), Arc::new(type_tokens::INT));
+    fields.insert("owner".to_string(), Arc::new(ReferenceType(TypeSig::STRING)));
+    fields.insert("range".to_string(), Arc::new(ProductType(vec![Arc::new(type_tokens::CHAR), Arc::new(type_tokens::CHAR)])));
+    RecordType(Arc::new(fields))
+}
+
+unsafe fn write_account(heap: &mut ObjectAllocator, balance: i64) -> *mut crate::allocator::object_allocator::ObjectHeader {
+    let owner = heap.write_string("Ada").unwrap();
+    let mut data = LinkedHashMap::<String, Arc<dyn Any>>::new();
+    data.insert("open".to_string(), Arc::new(true));
+    data.insert("balance".to_string(), Arc::new(balance));
+    data.insert("owner".to_string(), Arc::new(owner as usize));
+    data.insert("range".to_string(), Arc::new(vec![Arc::new('a') as Arc<dyn Any>, Arc::new('z')]));
+    heap.write_record(&data, &account()).unwrap()
+}
+
+// Les champs d'un objet vivant sont lus et modifiés sur place, leurs décalages et les positions des noms des champs
+// ne sont calculés qu'une fois par type.
+#[test]
+fn test_fields() {
+    unsafe {
+        let gc = GarbageCollector::new();
+        let mut borrowed = gc.borrow_mut();
+        let before = borrowed.heap.types.cached_offsets();
+        let accounts = (0..100).map(|i| write_account(&mut borrowed.heap, i)).collect::<Vec<_>>();
+        let balances = accounts.iter().map(|account| borrowed.heap.get_field(*account, "balance").unwrap()).collect::<Vec<_>>();
+        assert_eq!(balances, (0..100).map(Value::Int).collect::<Vec<_>>(), "Un champ est lu par son nom");
+        assert!(borrowed.heap.get_field(accounts[3], 1).unwrap() == Value::Int(3)
+            && borrowed.heap.get_field(accounts[3], 0).unwrap() == Value::Bool(true), "Un champ est lu par sa position");
+        assert_eq!(borrowed.heap.get_field(accounts[0], "range").unwrap(), Value::Product(vec![Value::Char('a'), Value::Char('z')]), "Un champ composé est lu en entier");
+        let Value::Ref(owner) = borrowed.heap.get_field(accounts[0], "owner").unwrap() else { panic!() };
+        assert_eq!(borrowed.heap.read_string(owner).unwrap(), "Ada", "Une référence est lue comme une adresse");
+        assert_eq!(borrowed.heap.types.cached_offsets(), before + 2, "Les tables des décalages du record et du produit imbriqué sont calculées une seule fois");
+        // chaque lecture par nom d'un champ composé trouve la position du nom, les décalages du record et ceux du
+        // produit imbriqué dans le registre
+        let hits = borrowed.heap.types.cache_hits();
+        (0..10).for_each(|_| {
+            borrowed.heap.get_field(accounts[0], "range").unwrap();
+        });
+        assert!(borrowed.heap.types.cache_hits() == hits + 30 && borrowed.heap.types.cached_offsets() == before + 2, "Les lectures répétées sont servies par le registre");
+
+        borrowed.heap.set_field(accounts[7], "balance", &(Arc::new(-70i64) as Arc<dyn Any>)).unwrap();
+        borrowed.heap.set_field(accounts[7], 0, &(Arc::new(false) as Arc<dyn Any>)).unwrap();
+        assert!(borrowed.heap.get_field(accounts[7], "balance").unwrap() == Value::Int(-70)
+            && borrowed.heap.get_field(accounts[7], "open").unwrap() == Value::Bool(false) && borrowed.heap.get_field(accounts[8], "balance").unwrap() == Value::Int(8), "Un champ est modifié sur place");
+        assert!(matches!(borrowed.heap.set_field(accounts[7], "balance", &(Arc::new(1.0f64) as Arc<dyn Any>)), Err(AllocatorError::TypeMismatch(_))), "Le type du champ est vérifié");
+        assert!(matches!(borrowed.heap.get_field(accounts[7], "debt"), Err(AllocatorError::UnknownField(_)))
+            && matches!(borrowed.heap.get_field(accounts[7], 4), Err(AllocatorError::UnknownField(_))), "Les champs inconnus sont refusés");
+
+        // le cas courant d'une somme choisit sa table de décalages
+        let mut cases = LinkedHashMap::new();
+        cases.insert("Small".to_string(), Arc::new(ProductType(vec![Arc::new(type_tokens::BOOL)])));
+        cases.insert("Large".to_string(), Arc::new(ProductType(vec![Arc::new(type_tokens::BOOL), Arc::new(type_tokens::DOUBLE)])));
+        let size = SumType(cases);
+        let sum = borrowed.heap.write_sum("Small", &[Arc::new(true)], &size).unwrap();
+        let small = borrowed.heap.get_field(sum, 0).unwrap();
+        borrowed.heap.set_variant(sum, "Large", &[Arc::new(false), Arc::new(2.5f64)]).unwrap();
+        assert!(small == Value::Bool(true) && borrowed.heap.get_field(sum, 1).unwrap() == Value::Double(2.5)
+            && matches!(borrowed.heap.set_variant(sum, "Small", &[Arc::new(true)]).and_then(|_| borrowed.heap.get_field(sum, 1)), Err(AllocatorError::UnknownField(_)))
+            && borrowed.heap.types.cached_offsets() == before + 4, "Les champs d'une somme suivent son cas");
+        let array = borrowed.heap.write_array(&[Arc::new(4u64), Arc::new(5u64)], &ArrayType(Arc::new(type_tokens::NAT))).unwrap();
+        assert_eq!(borrowed.heap.get_field(array, 1).unwrap(), Value::Nat(5), "Les éléments d'un tableau sont des champs");
+        drop(borrowed);
+
+        let mut compact = ObjectAllocator::new();
+        compact.set_layout(Layout::Compact).unwrap();
+        let account = write_account(&mut compact, 12);
+        assert!(compact.get_field(account, "balance").unwrap() == Value::Int(12)
+            && compact.get_field(account, "open").unwrap() == Value::Bool(true)
+            && compact.get_field(account, "range").unwrap() == Value::Product(vec![Value::Char('a'), Value::Char('z')]), "Les décalages suivent la disposition du tas");
+
+        let mut heap = Heap::new();
+        let point = ProductType(vec![Arc::new(type_tokens::INT), Arc::new(type_tokens::DOUBLE)]);
+        let mut product = heap.alloc_product(&point, &[Arc::new(1i64), Arc::new(0.5f64)]).unwrap();
+        heap.set_field(product, 0, Arc::new(2i64)).unwrap();
+        let stale = product;
+        heap.collect(&mut [&mut product]).unwrap();
+        assert!(heap.get_field(product, 0).unwrap() == Value::Int(2) && heap.get_field(product, 1).unwrap() == Value::Double(0.5)
+            && matches!(heap.get_field(stale, 0), Err(HeapError::StaleHandle)), "Le tas lit un champ");
+    }
+}
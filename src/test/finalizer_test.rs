@@ -0,0 +1,69 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+use crate::allocator::object_allocator::{ObjectHeader, ObjectHeaderHelper};
+use crate::gc::gc::GarbageCollector;
+use crate::gc::reachability::ObjectAllocatorExt;
+use crate::heap::Heap;
+use crate::vm_types::type_info::{ProductType, ReferenceType};
+use crate::vm_types::type_sig::TypeSig;
+use crate::vm_types::type_tokens;
+
+// Des "fichiers": un descripteur et une référence vers un tampon natif. Seuls les fichiers pairs restent
+// accessibles, les impairs doivent être finalisés une seule fois, puis récupérés à la collection suivante.
+#[test]
+fn test_finalizers() {
+    unsafe {
+        let file_type = ProductType(vec![Arc::new(type_tokens::NAT), Arc::new(ReferenceType(TypeSig::INT))]);
+        let gc = GarbageCollector::new();
+        let closed = Rc::new(RefCell::new(vec![]));
+        let mut files = {
+            let mut borrowed = gc.borrow_mut();
+            // des déchets devant les fichiers, afin que le compactage les déplace
+            (0..50).for_each(|i| {
+                borrowed.heap.write_int(-i).unwrap();
+            });
+            (0..10u64).map(|fd| {
+                let buffer = borrowed.heap.write_int(fd as i64 * 100).unwrap();
+                borrowed.heap.write_product(&[Arc::new(fd), Arc::new(buffer as usize)], &file_type).unwrap()
+            }).collect::<Vec<_>>()
+        };
+        let buffer_offset = gc.borrow().heap.field_slot(files[0], 1.into()).unwrap().1;
+        let recorded = closed.clone();
+        gc.borrow_mut().declare_finalizer(&file_type, move |file: *mut ObjectHeader| {
+            let buffer = *file.to_data_start::<u8>().add(buffer_offset).cast::<*mut ObjectHeader>();
+            recorded.borrow_mut().push((*file.to_data_start::<u64>(), *buffer.to_data_start::<i64>()));
+        });
+
+        let mut roots = files.iter().step_by(2).copied().collect::<Vec<_>>();
+        let forwarding = gc.borrow_mut().collect(&mut roots);
+        files = files.iter().step_by(2).map(|file| forwarding[file]).collect();
+        assert!(closed.borrow().is_empty(), "Aucun finaliseur n'est lancé pendant la collection");
+        assert_eq!(gc.borrow().pending_finalizers(), 5, "Les fichiers impairs attendent leur finaliseur");
+        assert!(files.iter().enumerate().all(|(i, file)| {
+            let buffer = *file.to_data_start::<u8>().add(buffer_offset).cast::<*mut ObjectHeader>();
+            *file.to_data_start::<u64>() == 2 * i as u64 && *buffer.to_data_start::<i64>() == 200 * i as i64
+        }), "Les fichiers pairs sont intacts");
+
+        let ran = GarbageCollector::run_finalizers(&gc);
+        let mut fds = closed.borrow().clone();
+        fds.sort();
+        assert!(ran == 5 && fds == (0..5).map(|i| (2 * i + 1, 100 * (2 * i + 1) as i64)).collect::<Vec<_>>(), "Chaque fichier impair a été finalisé une fois");
+
+        let forwarding = gc.borrow_mut().collect(&mut files);
+        files = files.iter().map(|file| forwarding[file]).collect();
+        let borrowed = gc.borrow();
+        assert_eq!(borrowed.heap.allocated_objects.len(), borrowed.heap.reachable(&files).unwrap().len(), "Les fichiers finalisés ont été récupérés");
+        assert!(borrowed.pending_finalizers() == 0 && closed.borrow().len() == 5, "Aucun finaliseur n'est relancé");
+        drop(borrowed);
+
+        let mut heap = Heap::new();
+        let count = Rc::new(RefCell::new(0));
+        let counted = count.clone();
+        heap.declare_finalizer(&type_tokens::INT, move |_| *counted.borrow_mut() += 1).unwrap();
+        heap.alloc(1i64).unwrap();
+        let mut kept = heap.alloc(2i64).unwrap();
+        heap.collect(&mut [&mut kept]).unwrap();
+        assert!(*count.borrow() == 0 && heap.run_finalizers().unwrap() == 1 && *count.borrow() == 1, "Le tas lance les finaliseurs à la demande");
+    }
+}
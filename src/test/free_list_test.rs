@@ -0,0 +1,73 @@
+use std::collections::HashSet;
+use rand::Rng;
+use crate::allocator::object_allocator::{ObjectHeader, ObjectHeaderHelper};
+use crate::gc::gc::{GarbageCollector, SweepMode};
+use crate::gc::reachability::ObjectAllocatorExt;
+use crate::test::mocking::ObjectMocker;
+use crate::utils::io::format_read_object;
+use crate::vm_types::type_sig::TypeSig;
+
+// les objets rencontrés en parcourant les blocs du tas, sans les remplisseurs
+unsafe fn walk(obj_mocker: &ObjectMocker) -> HashSet<*mut ObjectHeader> {
+    let mut walked = HashSet::new();
+    for block in obj_mocker.allocator.borrow().heap.allocator.committed_regions.values() {
+        let mut cur = GarbageCollector::next_object(block, None);
+        while let Some(c) = cur {
+            if c.type_sig() != TypeSig::FILLER {
+                walked.insert(c);
+            }
+            cur = GarbageCollector::next_object(block, Some(c));
+        }
+    }
+    walked
+}
+
+#[test]
+fn test_sweep_and_reuse() {
+    unsafe {
+        let obj_mocker = &mut ObjectMocker::new();
+        let mut allocated_ptrs = vec![];
+        let mut mocked = vec![];
+        (0..1000).for_each(|_| {
+            let res = obj_mocker.mock_and_allocate_object().unwrap();
+            mocked.push(res.0);
+            allocated_ptrs.push(res.1);
+        });
+
+        let mut root_objects = (0..50).map(|_| rand::thread_rng().gen_range(0..1000)).map(|i| allocated_ptrs[i]).collect::<HashSet<*mut ObjectHeader>>().into_iter().collect::<Vec<*mut ObjectHeader>>();
+        let reachables = obj_mocker.allocator.borrow().heap.reachable(&root_objects).unwrap();
+        let allocated_before = obj_mocker.allocator.borrow().heap.allocator.allocated();
+        obj_mocker.allocator.borrow_mut().mark_living(&mut root_objects);
+        let reclaimed = obj_mocker.allocator.borrow_mut().sweep(SweepMode::FreeList);
+        assert!(reclaimed > 0 && reclaimed < allocated_before, "Une partie du tas est récupérée");
+
+        // on ne doit plus mocker des références vers les objets morts
+        obj_mocker.mocked_objects_ptrs.retain(|(_, ptr)| reachables.contains(ptr));
+        let intact = |obj_mocker: &ObjectMocker| allocated_ptrs.iter().zip(mocked.iter())
+            .filter(|(ptr, _)| reachables.contains(*ptr))
+            .all(|(ptr, res)| format_read_object(&obj_mocker.allocator.borrow_mut().heap.read_obj(*ptr).unwrap()) == format_read_object(res));
+        assert!(intact(obj_mocker), "Tous les objets accessibles sont intacts après le balayage");
+        assert_eq!(walk(obj_mocker), reachables, "Le tas reste parcourable après le balayage");
+
+        let holes = obj_mocker.allocator.borrow().heap.allocator.free_lists.chunks().map(|chunk| (chunk.start as usize, chunk.start as usize + chunk.size)).collect::<Vec<_>>();
+        let mut living = reachables.clone();
+        let reused = (0..200).filter(|_| {
+            let ptr = obj_mocker.mock_and_allocate_object().unwrap().1;
+            living.insert(ptr);
+            holes.iter().any(|(start, end)| ptr as usize >= *start && (ptr as usize) < *end)
+        }).count();
+        assert!(reused > 0, "Des objets sont alloués dans les trous");
+        assert!(intact(obj_mocker), "Tous les objets accessibles sont intacts après la réutilisation");
+        assert_eq!(walk(obj_mocker), living, "Le tas reste parcourable après la réutilisation des trous");
+
+        // les autres modes n'utilisent plus les listes libres
+        let mut roots = living.into_iter().collect::<Vec<_>>();
+        obj_mocker.allocator.borrow_mut().mark_living(&mut roots);
+        obj_mocker.allocator.borrow_mut().sweep(SweepMode::Filler);
+        assert!(!obj_mocker.allocator.borrow().heap.allocator.use_free_lists, "Un balayage avec remplisseurs n'utilise pas les listes libres");
+        obj_mocker.allocator.borrow_mut().mark_living(&mut roots);
+        obj_mocker.allocator.borrow_mut().sweep(SweepMode::FreeList);
+        obj_mocker.allocator.borrow_mut().collect(&mut roots);
+        assert!(!obj_mocker.allocator.borrow().heap.allocator.use_free_lists, "Le compactage n'utilise pas les listes libres");
+    }
+}
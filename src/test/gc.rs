@@ -1,78 +1,116 @@
 use std::collections::HashSet;
 use rand::Rng;
-use crate::allocator::object_allocator::ObjectHeader;
+use crate::allocator::object_allocator::{ObjectHeader, ObjectHeaderHelper};
+use crate::gc::gc::{GarbageCollector, SweepMode};
 use crate::gc::reachability::ObjectAllocatorExt;
 use crate::test::mocking::ObjectMocker;
 use crate::utils::io::format_read_object;
+use crate::vm_types::type_sig::TypeSig;
 
-pub unsafe fn test_pointers() {
-    let mut obj_mocker = ObjectMocker::new();
-    // On sauvagarde deux copies des objets alloués, afin qu'on peut
-    // detecter si les allocations suivantes interférent avec les objets
-    // qui viennent d'être alloués, s'il est vrai, alors les données de
-    // l'objet alloué sera corrompu. Par conséquent, on verra que l'objet
-    // "mocked" et l'objet "read" sont différents.
-    let mut allocated_ptrs = vec![];
-    let mut mock_objects = vec![];
-    for _ in 1..=100 {
-        let res = obj_mocker.mock_and_allocate_object().unwrap();
-        mock_objects.push(res.0.clone());
-        allocated_ptrs.push(res.1);
-    }
-
-    // Récupérer les pointeurs dont chaque objet alloué contient.
-    let pointers = obj_mocker.allocator.borrow().heap.pointers_all(&allocated_ptrs).unwrap();
-    for (index, (pointer, _)) in pointers.iter().enumerate() {
-        println!("{:?}", allocated_ptrs.contains(pointer));
-        println!("--------------")
-    }
+unsafe fn random_roots(allocated_ptrs: &[*mut ObjectHeader]) -> Vec<*mut ObjectHeader> {
+    (0..50).map(|_| rand::thread_rng().gen_range(0..allocated_ptrs.len())).map(|i| allocated_ptrs[i])
+        .collect::<HashSet<*mut ObjectHeader>>().into_iter().collect()
 }
 
-pub unsafe fn test_reachability(obj_mocker: &mut ObjectMocker) {
-    let mut allocated_ptrs = vec![];
-    (0..1000).for_each(|_| {
-        let res = obj_mocker.mock_and_allocate_object().unwrap();
-        allocated_ptrs.push(res.1);
-    });
+#[test]
+fn test_pointers() {
+    unsafe {
+        let mut obj_mocker = ObjectMocker::new();
+        // On sauvagarde deux copies des objets alloués, afin qu'on peut
+        // detecter si les allocations suivantes interférent avec les objets
+        // qui viennent d'être alloués, s'il est vrai, alors les données de
+        // l'objet alloué sera corrompu. Par conséquent, on verra que l'objet
+        // "mocked" et l'objet "read" sont différents.
+        let mut allocated_ptrs = vec![];
+        let mut mock_objects = vec![];
+        for _ in 1..=100 {
+            let res = obj_mocker.mock_and_allocate_object().unwrap();
+            mock_objects.push(res.0.clone());
+            allocated_ptrs.push(res.1);
+        }
 
-    allocated_ptrs.iter().for_each(|x| {
-        println!("{}", format_read_object(&obj_mocker.allocator.borrow_mut().heap.read_obj(*x).unwrap()));
-    });
+        // Récupérer les pointeurs dont chaque objet alloué contient.
+        let pointers = obj_mocker.allocator.borrow().heap.pointers_all(&allocated_ptrs).unwrap();
+        assert!(pointers.iter().all(|(pointer, _)| allocated_ptrs.contains(pointer)), "Tous les pointeurs visent des objets alloués");
+        assert!(allocated_ptrs.iter().zip(mock_objects.iter())
+            .all(|(ptr, res)| format_read_object(&obj_mocker.allocator.borrow_mut().heap.read_obj(*ptr).unwrap()) == format_read_object(res)),
+            "Les objets alloués sont intacts");
+    }
+}
 
-    let pointers = obj_mocker.allocator.borrow().heap.pointers_all(&allocated_ptrs).unwrap();
-    let mut root_objects = (0..50).map(|_| rand::thread_rng().gen_range(0..1000)).map(|i| allocated_ptrs[i]).collect::<HashSet<*mut ObjectHeader>>().into_iter().collect::<Vec<*mut ObjectHeader>>();
-    let reachables = obj_mocker.allocator.borrow().heap.reachable(&root_objects).unwrap();
+#[test]
+fn test_reachability() {
+    unsafe {
+        let obj_mocker = &mut ObjectMocker::new();
+        let mut allocated_ptrs = vec![];
+        (0..1000).for_each(|_| {
+            let res = obj_mocker.mock_and_allocate_object().unwrap();
+            allocated_ptrs.push(res.1);
+        });
 
-    obj_mocker.allocator.borrow_mut().mark_living(&mut root_objects);
+        let pointers = obj_mocker.allocator.borrow().heap.pointers_all(&allocated_ptrs).unwrap();
+        let mut root_objects = random_roots(&allocated_ptrs);
+        let reachables = obj_mocker.allocator.borrow().heap.reachable(&root_objects).unwrap();
 
-    let set_bits = obj_mocker.allocator.borrow().all_marked_bits();
-    println!("bits met: {:x?}", set_bits);
-    println!("Tous les bits met: {}", set_bits.len());
-    // println!("Tous les bitmaps sont apparaîtent à pointeurs: {}", reachables.iter()
-    //    .filter(|x| !pointers.contains(&(**x as *mut ObjectHeader))).map(|x| *x as *mut ObjectHeader).collect::<Vec<*mut ObjectHeader>>().len());
-    println!("Tous les bitmaps sont apparaîtent à reachables: {:?}",reachables.iter().copied().collect::<HashSet<_>>()
-        .symmetric_difference(&set_bits.into_iter().collect::<_>()).collect::<HashSet<_>>());
-    println!("Tous les objets sont bien alignés: {}", obj_mocker.allocator.borrow().heap.allocated_objects.iter().all(|x| *x as usize % 8 == 0));
-    println!("Tous les pointeurs sont bien alignés: {}", pointers.iter().all(|(x, _)| *x as usize % 8 == 0));
-    println!("Objets accéssibilité: {}", reachables.len());
-    println!("Tous les objets: {}", pointers.len());
-    let new_roots = obj_mocker.allocator.borrow_mut().collect(&mut root_objects);
+        obj_mocker.allocator.borrow_mut().mark_living(&mut root_objects);
 
-    obj_mocker.allocator.borrow_mut().mark_living(&mut new_roots.values().copied().collect::<Vec<_>>());
-    let mut set_bits = obj_mocker.allocator.borrow().all_marked_bits();
+        let set_bits = obj_mocker.allocator.borrow().all_marked_bits();
+        assert_eq!(set_bits.into_iter().collect::<HashSet<_>>(), reachables, "Les bits mis sont exactement les objets accessibles");
+        assert!(obj_mocker.allocator.borrow().heap.allocated_objects.iter().all(|x| *x as usize % 8 == 0), "Tous les objets sont bien alignés");
+        assert!(pointers.iter().all(|(x, _)| *x as usize % 8 == 0), "Tous les pointeurs sont bien alignés");
+        let new_roots = obj_mocker.allocator.borrow_mut().collect(&mut root_objects);
+        let mut new_root_objects = new_roots.values().copied().collect::<Vec<_>>();
+        assert_eq!(obj_mocker.allocator.borrow().heap.allocated_objects.len(), reachables.len(), "Seuls les objets accessibles ont survécu");
 
-    set_bits.sort();
-    println!("bits met2: {:x?}", set_bits);
-    println!("Tous les bits met: {}", set_bits.len());
+        obj_mocker.allocator.borrow_mut().mark_living(&mut new_root_objects);
+        let set_bits = obj_mocker.allocator.borrow().all_marked_bits();
+        assert_eq!(set_bits.len(), reachables.len(), "Les objets déplacés sont tous marqués");
 
-    (0..1000).for_each(|_| {
-        let res = obj_mocker.mock_and_allocate_object().unwrap();
-        allocated_ptrs.push(res.1);
-    });
+        (0..1000).for_each(|_| {
+            let res = obj_mocker.mock_and_allocate_object().unwrap();
+            allocated_ptrs.push(res.1);
+        });
+        obj_mocker.allocator.borrow_mut().collect(&mut new_root_objects);
+        assert_eq!(obj_mocker.allocator.borrow().heap.allocated_objects.len(), reachables.len(), "Les nouveaux objets inaccessibles sont récupérés");
+    }
+}
 
-    obj_mocker.allocator.borrow_mut().mark_living(&mut new_roots.values().copied().collect::<Vec<_>>());
+#[test]
+fn test_non_moving() {
+    unsafe {
+        let obj_mocker = &mut ObjectMocker::new();
+        let mut allocated_ptrs = vec![];
+        let mut mocked = vec![];
+        (0..1000).for_each(|_| {
+            let res = obj_mocker.mock_and_allocate_object().unwrap();
+            mocked.push(res.0);
+            allocated_ptrs.push(res.1);
+        });
 
+        let mut root_objects = random_roots(&allocated_ptrs);
+        let reachables = obj_mocker.allocator.borrow().heap.reachable(&root_objects).unwrap();
+        let allocated_before = obj_mocker.allocator.borrow().heap.allocator.allocated();
+        let reclaimed = obj_mocker.allocator.borrow_mut().collect_non_moving(&mut root_objects, SweepMode::Filler);
+        assert!(reclaimed > 0 && reclaimed < allocated_before, "Une partie du tas est récupérée");
 
-    let new_roots = obj_mocker.allocator.borrow_mut().collect(&mut new_roots.values().copied().collect::<Vec<_>>());
-    println!();
-}
\ No newline at end of file
+        // le tas doit rester parcourable, on ne doit rencontrer que les objets accessibles et les remplisseurs
+        let mut walked = HashSet::new();
+        let mut fillers = 0;
+        for block in obj_mocker.allocator.borrow().heap.allocator.committed_regions.values() {
+            let mut cur = GarbageCollector::next_object(block, None);
+            while let Some(c) = cur {
+                if c.type_sig() == TypeSig::FILLER {
+                    fillers += 1;
+                } else {
+                    walked.insert(c);
+                }
+                cur = GarbageCollector::next_object(block, Some(c));
+            }
+        }
+        assert!(fillers > 0, "Les trous sont remplis");
+        assert_eq!(walked, reachables, "Les objets parcourus sont exactement les objets accessibles");
+        assert!(allocated_ptrs.iter().zip(mocked.iter())
+            .filter(|(ptr, _)| reachables.contains(*ptr))
+            .all(|(ptr, res)| format_read_object(&obj_mocker.allocator.borrow_mut().heap.read_obj(*ptr).unwrap()) == format_read_object(res)), "Tous les objets accessibles sont intacts et n'ont pas bougé");
+    }
+}
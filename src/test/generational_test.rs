@@ -0,0 +1,175 @@
+use std::any::Any;
+use std::collections::HashSet;
+use std::sync::Arc;
+use rand::Rng;
+use rand::seq::SliceRandom;
+use crate::allocator::object_allocator::{ObjectHeader, ObjectHeaderHelper};
+use crate::allocator::value::Value;
+use crate::gc::collector::{Collector, CollectorKind};
+use crate::gc::generational::GenerationalCollector;
+use crate::gc::reachability::ObjectAllocatorExt;
+use crate::heap::Heap;
+use crate::test::mocking::ObjectMocker;
+use crate::utils::errors::AllocatorError;
+use crate::vm_types::type_info::{ArrayType, ProductType, ReferenceType};
+use crate::vm_types::type_sig::TypeSig;
+use crate::vm_types::type_tokens;
+
+#[test]
+fn test_minor_collections() {
+    unsafe {
+        let obj_mocker = &mut ObjectMocker::new();
+        let mut gc = GenerationalCollector::new(16 * 1024, 3).unwrap();
+        let mut roots: Vec<*mut ObjectHeader> = vec![];
+        let mut minor_collections = 0;
+        let mut all_preserved = true;
+        for _ in 0..5000 {
+            let mut obj = obj_mocker.mock_object(0, false).unwrap();
+            let allocated = match gc.nursery.allocate_general(&obj) {
+                Err(AllocatorError::OutOfMemory) => {
+                    // la pépinière est pleine, on garde quelques racines au hasard
+                    roots.shuffle(&mut rand::thread_rng());
+                    roots.truncate(20);
+                    let before = gc.mark(&roots).len();
+                    let new_roots = gc.minor_collect(&mut roots).unwrap();
+                    roots = roots.iter().map(|root| new_roots[root]).collect();
+                    all_preserved &= gc.mark(&roots).len() == before;
+                    minor_collections += 1;
+                    // le mocker ne doit référencer que des objets vivants
+                    obj_mocker.mocked_objects_ptrs = roots.iter().map(|root| (TypeSig::to_type_kind(root.type_sig()), *root)).collect();
+                    // les références de l'objet mocké pointent peut-être vers des objets déplacés
+                    obj = obj_mocker.mock_object(0, false).unwrap();
+                    gc.nursery.allocate_general(&obj).unwrap()
+                }
+                res => res.unwrap()
+            };
+            obj_mocker.mocked_objects_ptrs.push((obj.0.kind(), allocated));
+            if rand::thread_rng().gen_bool(0.1) {
+                roots.push(allocated);
+            }
+        }
+        assert!(minor_collections > 0, "La pépinière a été collectée");
+        assert!(all_preserved, "Tous les objets accessibles ont survécu aux collections mineures");
+
+        let before = gc.mark(&roots).len();
+        let new_roots = gc.major_collect(&mut roots).unwrap();
+        roots = roots.iter().map(|root| new_roots[root]).collect();
+        assert_eq!(gc.mark(&roots).len(), before, "Tous les objets accessibles ont survécu à la collection majeure");
+        assert!(roots.iter().all(|root| !gc.in_nursery(*root)), "La pépinière est vide après la collection majeure");
+    }
+}
+
+#[test]
+fn test_remembered_set() {
+    unsafe {
+        let mut gc = GenerationalCollector::new(16 * 1024, 3).unwrap();
+        let young = gc.nursery.write_int(123456).unwrap();
+        // une référence mature -> jeune, enregistrée par l'allocateur du tas mature
+        let old = gc.mature.borrow_mut().heap.write_reference(young as usize, &ReferenceType(TypeSig::INT)).unwrap();
+        assert_eq!(gc.remembered_set_size(), 1, "La référence mature -> jeune est retenue");
+        // aucune racine: l'entier ne survit que grâce à l'ensemble retenu
+        for _ in 0..5 {
+            gc.minor_collect(&mut []).unwrap();
+        }
+        let target = gc.mature.borrow().heap.pointers(old).unwrap().iter().next().unwrap().0;
+        assert!(target != young, "La référence mature a été mise à jour");
+        assert!(!gc.in_nursery(target), "L'objet jeune a été promu");
+        assert_eq!(gc.remembered_set_size(), 0, "La référence est oubliée après la promotion");
+        assert_eq!(*target.to_data_start::<i64>(), 123456, "Données intactes");
+        assert!(gc.mark(&[old]).contains(&target), "Accessibles depuis l'objet mature");
+    }
+}
+
+#[test]
+fn test_write_barrier() {
+    unsafe {
+        let mut gc = GenerationalCollector::new(16 * 1024, 3).unwrap();
+        let mature_int = gc.mature.borrow_mut().heap.write_int(1).unwrap();
+        let old = gc.mature.borrow_mut().heap.write_reference(mature_int as usize, &ReferenceType(TypeSig::INT)).unwrap();
+        // la référence ne pointe pas vers la pépinière, elle est oubliée par la première collection mineure
+        gc.minor_collect(&mut []).unwrap();
+        assert_eq!(gc.remembered_set_size(), 0, "Aucun objet retenu avant l'écriture");
+
+        let young = gc.nursery.write_int(654321).unwrap();
+        gc.mature.borrow_mut().heap.set_reference(old, 0, young).unwrap();
+        assert_eq!(gc.remembered_set_size(), 1, "L'écriture a été retenue par la barrière");
+        let mismatch = gc.mature.borrow_mut().heap.set_reference(mature_int, 0, young);
+        assert!(matches!(mismatch, Err(AllocatorError::TypeMismatch(_))), "Écrire une référence dans un entier est refusé");
+
+        gc.minor_collect(&mut []).unwrap();
+        let target = gc.mature.borrow().heap.pointers(old).unwrap().iter().next().unwrap().0;
+        assert!(target != young, "La référence mature a été mise à jour");
+        assert_eq!(*target.to_data_start::<i64>(), 654321, "Données intactes");
+    }
+}
+
+// Le `Heap` écrit par la pépinière, une référence vers un objet jeune placée dans un objet mature par `set_field`
+// doit tout de même être retenue et suivie par la collection mineure suivante.
+#[test]
+fn test_heap_write_barrier() {
+    unsafe {
+        let mut heap = Heap::with_collector(CollectorKind::Generational { nursery_size: 16 * 1024, promotion_threshold: 1 }).unwrap();
+        let holder_type = ProductType(vec![Arc::new(ReferenceType(TypeSig::INT))]);
+        let first = heap.alloc(1i64).unwrap();
+        let mut holder = heap.alloc_product(&holder_type, &[Arc::new(first.erase())]).unwrap();
+        // le porteur est promu par la première collection mineure
+        heap.collect(&mut [&mut holder]).unwrap();
+
+        let young = heap.alloc(654321i64).unwrap();
+        heap.set_field(holder, 0, Arc::new(young.erase())).unwrap();
+        let Value::Ref(before) = heap.get_field(holder, 0).unwrap() else { panic!() };
+        heap.collect(&mut [&mut holder]).unwrap();
+        let Value::Ref(after) = heap.get_field(holder, 0).unwrap() else { panic!() };
+        assert!(after != before, "La référence écrite par le tas a été mise à jour par la collection mineure");
+        assert_eq!(*after.to_data_start::<i64>(), 654321, "La cible a survécu");
+    }
+}
+
+// Chaque espace est un seul bloc de la taille de la pépinière, aucun bloc n'est remplacé quand la pépinière se
+// remplit. Tous les objets alloués restent dans la pépinière et survivent à la collection mineure.
+#[test]
+fn test_fixed_nursery_blocks() {
+    unsafe {
+        for nursery_size in [4096, 8192] {
+            let mut gc = GenerationalCollector::new(nursery_size, 3).unwrap();
+            let mut roots = vec![];
+            loop {
+                match gc.nursery.write_int(roots.len() as i64) {
+                    Err(AllocatorError::OutOfMemory) => break,
+                    res => roots.push(res.unwrap())
+                }
+            }
+            let blocks = gc.nursery.allocator.committed_regions.values().map(|block| block.size).collect::<Vec<_>>();
+            assert_eq!(blocks, vec![nursery_size], "La pépinière est un seul bloc ({})", nursery_size);
+            assert!(roots.iter().all(|root| gc.in_nursery(*root)), "Tous les objets sont dans la pépinière ({})", nursery_size);
+            let new_roots = gc.minor_collect(&mut roots).unwrap();
+            let values = roots.iter().map(|root| *new_roots[root].to_data_start::<i64>()).collect::<Vec<_>>();
+            assert_eq!(values, (0..roots.len() as i64).collect::<Vec<_>>(), "Tous les objets ont survécu ({})", nursery_size);
+            assert!(roots.iter().all(|root| gc.in_nursery(new_roots[root])), "Les survivants sont dans la nouvelle pépinière ({})", nursery_size);
+        }
+    }
+}
+
+// Un tableau plus grand que la pépinière est alloué dans le tas mature, ses références vers la pépinière sont
+// suivies par les collections mineures comme celles des autres objets matures.
+#[test]
+fn test_large_objects() {
+    unsafe {
+        let mut heap = Heap::with_collector(CollectorKind::Generational { nursery_size: 4096, promotion_threshold: 3 }).unwrap();
+        let ref_array = ArrayType(Arc::new(ReferenceType(TypeSig::INT)));
+        let young = heap.alloc(42i64).unwrap();
+        let mut array = heap.alloc_array(&ref_array, &vec![Arc::new(young.erase()) as Arc<dyn Any>; 1000]).unwrap();
+        for _ in 0..5 {
+            // des grands déchets, le tas mature finit par déclencher une collection majeure
+            heap.alloc_array(&ArrayType(Arc::new(type_tokens::INT)), &vec![Arc::new(0i64) as Arc<dyn Any>; 1000]).unwrap();
+            heap.collect(&mut [&mut array]).unwrap();
+        }
+        assert_eq!(heap.array_length(array).unwrap(), 1000, "Le grand tableau a survécu");
+        let targets = (0..1000).map(|i| match heap.get_field(array, i).unwrap() {
+            Value::Ref(target) => *target.to_data_start::<i64>(),
+            _ => panic!()
+        }).collect::<HashSet<_>>();
+        assert_eq!(targets, HashSet::from([42]), "Les références du grand tableau suivent leur cible");
+        assert!(heap.allocated() < 3 * 8 * 1000, "Les grands déchets ont été récupérés");
+    }
+}
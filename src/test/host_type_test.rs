@@ -0,0 +1,74 @@
+use crate::allocator::host_type::HostType;
+use crate::allocator::object_allocator::ObjectHeaderHelper;
+use crate::allocator::value::Value;
+use crate::gc::gc::GarbageCollector;
+use crate::utils::errors::AllocatorError;
+
+#[derive(HostType, Clone, Debug, PartialEq)]
+struct Point(i64, i64);
+
+#[derive(HostType, Clone, Debug, PartialEq)]
+struct Marker;
+
+#[derive(HostType, Clone, Debug, PartialEq)]
+enum Shape {
+    Empty,
+    Circle(f64),
+    Rect { width: f64, height: f64, filled: bool }
+}
+
+#[derive(HostType, Clone, Debug, PartialEq)]
+struct Entity {
+    id: u64,
+    position: Point,
+    shape: Shape,
+    initial: char,
+    marker: Marker
+}
+
+#[derive(HostType, Clone, Debug, PartialEq)]
+struct Pair<T> {
+    first: T,
+    second: T
+}
+
+fn entity(id: u64, shape: Shape) -> Entity {
+    Entity { id, position: Point(-1, 2), shape, initial: 'é', marker: Marker }
+}
+
+// Les structures Rust sont écrites dans le tas et relues sans construire leurs types à la main.
+#[test]
+fn test_host_types() {
+    unsafe {
+        assert_eq!(Entity::type_info().name(), "{id: Nat, position: (Int, Int), shape: {Empty(), Circle(Double), Rect(Double, Double, Bool)}, initial: Char, marker: ()}", "Une structure est un record");
+        assert_eq!(Pair::<bool>::type_info().name(), "{first: Bool, second: Bool}", "Une structure générique prend le type de ses paramètres");
+        assert_eq!(Shape::Rect { width: 1.0, height: 2.0, filled: true }.to_value(), Value::Sum("Rect".to_string(), vec![Value::Double(1.0), Value::Double(2.0), Value::Bool(true)]), "Une énumération est une somme");
+
+        let gc = GarbageCollector::new();
+        let mut objects = {
+            let mut borrowed = gc.borrow_mut();
+            (0..10).for_each(|i| {
+                borrowed.heap.write_int(i).unwrap();
+            });
+            let circle = borrowed.heap.write_host(&entity(1, Shape::Circle(0.5))).unwrap();
+            let rect = borrowed.heap.write_host(&entity(2, Shape::Rect { width: 3.0, height: 4.0, filled: false })).unwrap();
+            let empty = borrowed.heap.write_host(&entity(3, Shape::Empty)).unwrap();
+            let pair = borrowed.heap.write_host(&Pair { first: 'a', second: 'b' }).unwrap();
+            assert!(circle.type_index() == rect.type_index() && rect.type_index() == empty.type_index(), "Les objets de même structure partagent leur type");
+            vec![circle, rect, empty, pair]
+        };
+        let forwarding = gc.borrow_mut().collect(&mut objects);
+        objects = objects.iter().map(|obj| forwarding[obj]).collect();
+
+        let mut borrowed = gc.borrow_mut();
+        assert!(borrowed.heap.read_host::<Entity>(objects[0]).unwrap() == entity(1, Shape::Circle(0.5))
+            && borrowed.heap.read_host::<Entity>(objects[1]).unwrap() == entity(2, Shape::Rect { width: 3.0, height: 4.0, filled: false })
+            && borrowed.heap.read_host::<Entity>(objects[2]).unwrap() == entity(3, Shape::Empty)
+            && borrowed.heap.read_host::<Pair<char>>(objects[3]).unwrap() == Pair { first: 'a', second: 'b' }, "Les structures déplacées sont relues");
+        assert!(matches!(borrowed.heap.read_host::<Pair<bool>>(objects[3]), Err(AllocatorError::TypeMismatch(_)))
+            && matches!(borrowed.heap.read_host::<Point>(objects[0]), Err(AllocatorError::TypeMismatch(_))), "Un objet d'un autre type est refusé");
+        assert!(matches!(Shape::from_value(&Value::Sum("Triangle".to_string(), vec![])), Err(AllocatorError::TypeMismatch(_)))
+            && matches!(Point::from_value(&Value::Product(vec![Value::Int(1)])), Err(AllocatorError::ProductSizeMismatch))
+            && matches!(Marker::from_value(&Value::Int(1)), Err(AllocatorError::TypeMismatch(_))), "Une valeur d'un autre type est refusée");
+    }
+}
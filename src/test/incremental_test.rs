@@ -0,0 +1,78 @@
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::mem::replace;
+use std::rc::Rc;
+use rand::Rng;
+use crate::allocator::barrier::NoBarrier;
+use crate::allocator::object_allocator::{ObjectHeader, ObjectHeaderHelper};
+use crate::gc::gc::GarbageCollector;
+use crate::gc::reachability::ObjectAllocatorExt;
+use crate::test::mocking::ObjectMocker;
+use crate::vm_types::type_sig::TypeSig;
+
+#[test]
+fn test_incremental_marking() {
+    unsafe {
+        let obj_mocker = &mut ObjectMocker::new();
+        let mut allocated_ptrs = vec![];
+        (0..1000).for_each(|_| {
+            let res = obj_mocker.mock_and_allocate_object().unwrap();
+            allocated_ptrs.push(res.1);
+        });
+        let mut root_objects = (0..50).map(|_| rand::thread_rng().gen_range(0..1000)).map(|i| allocated_ptrs[i]).collect::<HashSet<*mut ObjectHeader>>().into_iter().collect::<Vec<*mut ObjectHeader>>();
+        let snapshot = obj_mocker.allocator.borrow().heap.reachable(&root_objects).unwrap();
+
+        obj_mocker.allocator.borrow_mut().start_marking(&root_objects);
+        let mut steps = 0;
+        let mut allocated_during_marking = vec![];
+        let mut detached = HashSet::new();
+        while !obj_mocker.allocator.borrow_mut().mark_step(16) {
+            steps += 1;
+            // le mutateur continue entre deux étapes: il alloue, puis redirige une référence accessible vers le
+            // nouvel objet, l'ancienne cible n'est peut-être plus accessible que par le "snapshot"
+            let new = obj_mocker.mock_and_allocate_object().unwrap().1;
+            allocated_during_marking.push(new);
+            let reference = snapshot.iter().copied().find(|obj| obj.type_sig() == TypeSig::REFERENCE
+                && !detached.contains(obj)
+                && (*obj.to_data_start::<*mut ObjectHeader>()).type_sig() == new.type_sig());
+            if let Some(reference) = reference {
+                obj_mocker.allocator.borrow_mut().heap.set_reference(reference, 0, new).unwrap();
+                detached.insert(reference);
+            }
+        }
+        obj_mocker.allocator.borrow_mut().finish_marking();
+
+        let marked = obj_mocker.allocator.borrow().all_marked_bits().into_iter().collect::<HashSet<_>>();
+        let reachables = obj_mocker.allocator.borrow().heap.reachable(&root_objects).unwrap();
+        assert!(steps > 0, "Le marquage a pris plusieurs étapes");
+        assert!(snapshot.is_subset(&marked), "Tous les objets du snapshot sont marqués");
+        assert!(allocated_during_marking.iter().all(|obj| marked.contains(obj)), "Tous les objets alloués pendant le marquage sont marqués");
+        assert!(reachables.is_subset(&marked), "Tous les objets accessibles sont marqués");
+
+        let new_roots = obj_mocker.allocator.borrow_mut().finish_collection(&mut root_objects);
+        root_objects = root_objects.iter().map(|root| new_roots[root]).collect();
+        assert_eq!(obj_mocker.allocator.borrow().heap.reachable(&root_objects).unwrap().len(), reachables.len(), "Tous les objets accessibles ont survécu au compactage");
+        assert!(!obj_mocker.allocator.borrow().is_marking(), "La barrière a été retirée");
+    }
+}
+
+// La barrière du cycle peut être retenue ailleurs, e.g. enveloppée par une autre barrière. La fin du marquage remet
+// quand même en place la barrière et le callback d'allocation d'avant le cycle.
+#[test]
+fn test_shared_marking_hooks() {
+    unsafe {
+        let gc = GarbageCollector::new();
+        let allocations = Rc::new(Cell::new(0));
+        let cloned = allocations.clone();
+        gc.borrow_mut().heap.allocation_callback = Box::new(move |_| cloned.set(cloned.get() + 1));
+        let root = gc.borrow_mut().heap.write_int(1).unwrap();
+        gc.borrow_mut().start_marking(&[root]);
+        let snapshot_barrier = replace(&mut gc.borrow_mut().heap.barrier, Box::new(NoBarrier));
+        gc.borrow_mut().heap.write_int(2).unwrap();
+        gc.borrow_mut().finish_marking();
+        assert!(!gc.borrow().is_marking(), "Le marquage est terminé");
+        gc.borrow_mut().heap.write_int(3).unwrap();
+        assert_eq!(allocations.get(), 3, "Le callback d'avant le cycle a été remis en place");
+        drop(snapshot_barrier);
+    }
+}
@@ -0,0 +1,127 @@
+use std::any::Any;
+use std::mem::{offset_of, size_of};
+use std::sync::Arc;
+use linked_hash_map::LinkedHashMap;
+use crate::allocator::object_allocator::{ObjectAllocator, ObjectHeaderHelper};
+use crate::gc::collector::{Collector, CollectorKind};
+use crate::gc::gc::{GarbageCollector, SweepMode};
+use crate::gc::generational::GenerationalCollector;
+use crate::gc::reachability::ObjectAllocatorExt;
+use crate::heap::Heap;
+use crate::test::collector_test::run_workload;
+use crate::test::mocking::ObjectMocker;
+use crate::utils::errors::{AllocatorError, HeapError};
+use crate::utils::io::format_read_object;
+use crate::vm_types::layout::Layout;
+use crate::vm_types::type_info::{ArrayType, ProductType, RecordType, ReferenceType, TypeInfo};
+use crate::vm_types::type_sig::TypeSig;
+use crate::vm_types::type_tokens;
+
+#[repr(C)]
+struct Cell {
+    flag: bool,
+    count: i64,
+    letter: char
+}
+
+fn cell() -> RecordType {
+    let mut fields = LinkedHashMap::<String, Arc<dyn TypeInfo>>::new();
+    fields.insert("flag".to_string(), Arc::new(type_tokens::BOOL));
+    fields.insert("count".to_string(), Arc::new(type_tokens::INT));
+    fields.insert("letter".to_string(), Arc::new(type_tokens::CHAR));
+    RecordType(Arc::new(fields))
+}
+
+// La disposition est choisie par tas, les objets sont relus, parcourus et déplacés selon la disposition de leur tas.
+#[test]
+fn test_layouts() {
+    unsafe {
+        let obj_mocker = &mut ObjectMocker::new();
+        let flags = ProductType(vec![Arc::new(type_tokens::BOOL), Arc::new(type_tokens::INT), Arc::new(type_tokens::BOOL)]);
+        assert!(type_tokens::BOOL.size(Layout::Padded) == 8 && flags.alignment_table(Layout::Padded) == vec![0, 8, 16]
+            && flags.size(Layout::Padded) == 24, "Les scalaires alignés occupent un mot");
+        assert!(type_tokens::BOOL.size(Layout::Compact) == 1 && type_tokens::CHAR.size(Layout::Compact) == 4
+            && flags.alignment_table(Layout::Compact) == vec![8, 0, 9] && flags.size(Layout::Compact) == 16, "La disposition compacte réordonne les champs");
+        let table = cell().alignment_table(Layout::CCompatible);
+        assert!(table["flag"] == offset_of!(Cell, flag) && table["count"] == offset_of!(Cell, count)
+            && table["letter"] == offset_of!(Cell, letter) && cell().size(Layout::CCompatible) == size_of::<Cell>(), "La disposition C est celle de #[repr(C)]");
+        let table = cell().alignment_table(Layout::Compact);
+        assert!(table["count"] == 0 && table["letter"] == 8 && table["flag"] == 12
+            && cell().size(Layout::Compact) == 16, "Le record compact place les petits champs à la fin");
+        assert_eq!(ArrayType(Arc::new(type_tokens::BOOL)).data_size(10, Layout::Compact), 18, "Un tableau compact de booléens n'a pas de remplissage");
+
+        // les champs compacts sont suivis et relus après le compactage
+        let gc = GarbageCollector::new();
+        gc.borrow_mut().heap.set_layout(Layout::Compact).unwrap();
+        let letters = ProductType(vec![Arc::new(type_tokens::CHAR), Arc::new(ReferenceType(TypeSig::INT)), Arc::new(type_tokens::BOOL), Arc::new(type_tokens::CHAR)]);
+        let mut objects = {
+            let mut borrowed = gc.borrow_mut();
+            (0..10).for_each(|i| {
+                borrowed.heap.write_bool(i % 2 == 0).unwrap();
+            });
+            let target = borrowed.heap.write_int(3).unwrap();
+            let product = borrowed.heap.write_product(&[Arc::new('é'), Arc::new(target as usize), Arc::new(true), Arc::new('z')], &letters).unwrap();
+            let elements = (0..10).map(|i| Arc::new(i % 3 == 0) as Arc<dyn Any>).collect::<Vec<_>>();
+            let array = borrowed.heap.write_array(&elements, &ArrayType(Arc::new(type_tokens::BOOL))).unwrap();
+            assert!(product.size() == 24 + 24 && array.size() == 24 + 18
+                && borrowed.heap.write_char('x').unwrap().size() == 24 + 4, "Les objets compacts sont plus petits");
+            vec![product, array, borrowed.heap.write_char('ß').unwrap()]
+        };
+        let forwarding = gc.borrow_mut().collect(&mut objects);
+        objects = objects.iter().map(|obj| forwarding[obj]).collect();
+        {
+            let mut borrowed = gc.borrow_mut();
+            let (target, offset) = borrowed.heap.pointers(objects[0]).unwrap().into_iter().next().unwrap();
+            assert!(offset == 0 && *target.to_data_start::<i64>() == 3, "Les références compactes suivent leur cible");
+            assert!(format_read_object(&borrowed.heap.read_obj(objects[0]).unwrap())
+                == format!("Type: (Char, &Int, Bool, Char), données: [é, {:x?}, true, z]", target as usize)
+                && format_read_object(&borrowed.heap.read_obj(objects[2]).unwrap()) == "Type: Char, données: ß"
+                && borrowed.heap.read_array(objects[1]).unwrap().iter().map(|element| *element.downcast_ref::<bool>().unwrap()).collect::<Vec<_>>()
+                    == (0..10).map(|i| i % 3 == 0).collect::<Vec<_>>(), "Les objets compacts sont relus");
+            assert!(matches!(borrowed.heap.set_layout(Layout::Padded), Err(AllocatorError::HeapNotEmpty))
+                && borrowed.heap.layout() == Layout::Compact, "Seul un tas vide change de disposition");
+        }
+
+        let mut allocator = ObjectAllocator::new();
+        allocator.set_layout(Layout::CCompatible).unwrap();
+        let mut data = LinkedHashMap::<String, Arc<dyn Any>>::new();
+        data.insert("flag".to_string(), Arc::new(true));
+        data.insert("count".to_string(), Arc::new(-7i64));
+        data.insert("letter".to_string(), Arc::new('q'));
+        let record = allocator.write_record(&data, &cell()).unwrap();
+        let native = &*record.to_data_start::<Cell>();
+        assert!(native.flag && native.count == -7 && native.letter == 'q', "Un record C se lit comme une structure Rust");
+
+        // la pépinière et le tas mature gardent la même disposition
+        let mut generational = GenerationalCollector::new(16 * 1024, 1).unwrap();
+        generational.set_layout(Layout::Compact).unwrap();
+        let mut roots = vec![generational.nursery.write_product(&[Arc::new(false), Arc::new(5i64), Arc::new(true)], &flags).unwrap()];
+        for _ in 0..2 {
+            let forwarding = generational.minor_collect(&mut roots).unwrap();
+            roots = roots.iter().map(|root| forwarding[root]).collect();
+        }
+        assert!(!generational.in_nursery(roots[0]) && generational.mature.borrow().heap.layout() == Layout::Compact
+            && format_read_object(&generational.mature.borrow_mut().heap.read_obj(roots[0]).unwrap()) == "Type: (Bool, Int, Bool), données: [false, 5, true]", "Les objets promus gardent leur disposition");
+
+        let kinds = [
+            CollectorKind::MarkCompact,
+            CollectorKind::MarkSweep(SweepMode::FreeList),
+            CollectorKind::MarkSweep(SweepMode::Filler),
+            CollectorKind::Semispace { semispace_size: 64 * 1024 },
+            CollectorKind::Generational { nursery_size: 16 * 1024, promotion_threshold: 3 }
+        ];
+        for layout in [Layout::Compact, Layout::CCompatible] {
+            for kind in kinds {
+                let mut collector = kind.build().unwrap();
+                collector.set_layout(layout).unwrap();
+                assert!(run_workload(collector.as_mut(), obj_mocker, 5000), "Tous les objets accessibles ont survécu ({:?}, {:?})", layout, kind);
+            }
+        }
+
+        let mut heap = Heap::new();
+        heap.set_layout(Layout::Compact).unwrap();
+        let mut flag = heap.alloc(true).unwrap();
+        heap.collect(&mut [&mut flag]).unwrap();
+        assert!(heap.get(flag).unwrap() && matches!(heap.set_layout(Layout::Padded), Err(HeapError::Allocator(AllocatorError::HeapNotEmpty))), "Le tas choisit sa disposition");
+    }
+}
@@ -5,8 +5,8 @@ use std::sync::Arc;
 use linked_hash_map::LinkedHashMap;
 use rand::distributions::{Alphanumeric, DistString};
 use rand::Rng;
-use rand::seq::IteratorRandom;
-use crate::allocator::object_allocator::{ObjectHeader};
+use rand::seq::{IteratorRandom, SliceRandom};
+use crate::allocator::object_allocator::{ObjectHeader, Variant};
 use crate::gc::gc::GarbageCollector;
 use crate::vm_types::type_info::*;
 use crate::vm_types::type_kind::TypeKind;
@@ -43,6 +43,13 @@ impl ObjectMocker {
     fn gen_random(&self, recursion_depth: u32, is_in_complex_type: bool) -> usize {
         loop {
             let random = if recursion_depth <= 3 && !is_in_complex_type {
+                // les chaînes ont une taille variable, elles ne peuvent pas être des champs
+                match rand::thread_rng().gen_range(1..=10) {
+                    10 => TypeSig::STRING,
+                    sig => sig
+                }
+            } else if recursion_depth <= 1 {
+                // les champs directs d'un objet peuvent être des agrégats, placés dans l'objet lui-même
                 rand::thread_rng().gen_range(1..=9)
             } else {
                 rand::thread_rng().gen_range(1..=6)
@@ -62,6 +69,7 @@ impl ObjectMocker {
         Ok((Arc::new(ty), Arc::new(*ptr as usize)))
     }
 
+    #[allow(clippy::type_complexity, clippy::result_unit_err)]
     pub unsafe fn mock_object(&self, recursion_depth: u32, is_in_complex_type: bool) -> Result<(Arc<dyn TypeInfo>, Arc<dyn Any>), ()> {
         let random = self.gen_random(recursion_depth, is_in_complex_type);
         let mock_product = || -> (ProductType, Vec<Arc<dyn Any>>) {
@@ -87,7 +95,7 @@ impl ObjectMocker {
             }
             (RecordType(Arc::new(type_map)), data_map)
         };
-        let mock_sum = || -> (SumType, Vec<Arc<dyn Any>>) {
+        let mock_sum = || -> (SumType, Variant) {
             let size = rand::thread_rng().gen_range(1..=10);
             let mut type_map = LinkedHashMap::new();
             let mut data_map = LinkedHashMap::new();
@@ -98,7 +106,7 @@ impl ObjectMocker {
                 data_map.insert(case_name.clone(), data);
             }
             let selected = type_map.keys().choose(&mut rand::thread_rng()).unwrap();
-            (SumType(type_map.clone(), selected.clone()), data_map.get(selected).unwrap().clone())
+            (SumType(type_map.clone()), Variant::new(selected.clone(), data_map.get(selected).unwrap().clone()))
         };
 
         match random {
@@ -112,6 +120,13 @@ impl ObjectMocker {
                 Ok((Arc::new(type_tokens::CHAR), Arc::new(rand::thread_rng().gen_range('a'..='z')))),
             TypeSig::BOOL =>
                 Ok((Arc::new(type_tokens::BOOL), Arc::new(rand::thread_rng().gen_bool(0.5)))),
+            TypeSig::STRING => {
+                // des caractères de plusieurs bytes, afin que la longueur en bytes diffère du nombre de caractères
+                let string = (0..rand::thread_rng().gen_range(0..=40))
+                    .map(|_| *['a', 'z', '0', 'é', 'ß', '中', '🦀'].choose(&mut rand::thread_rng()).unwrap())
+                    .collect::<String>();
+                Ok((Arc::new(type_tokens::STRING), Arc::new(string)))
+            },
             TypeSig::REFERENCE =>
                 self.mock_reference(),
             TypeSig::PRODUCT => {
@@ -123,8 +138,8 @@ impl ObjectMocker {
                 Ok((Arc::new(ty), Arc::new(map)))
             },
             TypeSig::SUM => {
-                let (ty, list) = mock_sum();
-                Ok((Arc::new(ty), Arc::new(list)))
+                let (ty, variant) = mock_sum();
+                Ok((Arc::new(ty), Arc::new(variant)))
             }
             _ => Err(())
         }
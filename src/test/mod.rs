@@ -1,3 +1,25 @@
-pub mod mocking;
-pub mod object_allocator_test;
-pub mod gc;
\ No newline at end of file
+mod mocking;
+mod object_allocator_test;
+mod gc;
+mod free_list_test;
+mod generational_test;
+mod incremental_test;
+mod parallel_test;
+mod semispace_test;
+mod collector_test;
+mod roots_test;
+mod conservative_test;
+mod pinning_test;
+mod weak_test;
+mod finalizer_test;
+mod ephemeron_test;
+mod array_test;
+mod string_test;
+mod nested_test;
+mod sum_test;
+mod type_registry_test;
+mod compact_header_test;
+mod layout_test;
+mod value_test;
+mod host_type_test;
+mod field_test;
\ No newline at end of file
@@ -0,0 +1,87 @@
+use std::any::Any;
+use std::sync::Arc;
+use linked_hash_map::LinkedHashMap;
+use crate::allocator::object_allocator::{ObjectHeader, ObjectHeaderHelper};
+use crate::gc::gc::GarbageCollector;
+use crate::gc::reachability::ObjectAllocatorExt;
+use crate::heap::Heap;
+use crate::utils::errors::AllocatorError;
+use crate::utils::io::format_read_object;
+use crate::vm_types::layout::Layout;
+use crate::vm_types::type_info::{ArrayType, ProductType, RecordType, SumType, ReferenceType, TypeInfo};
+use crate::vm_types::type_sig::TypeSig;
+use crate::vm_types::type_tokens;
+
+unsafe fn target(obj: *mut ObjectHeader, offset: usize) -> *mut ObjectHeader {
+    *obj.to_data_start::<u8>().add(offset).cast::<*mut ObjectHeader>()
+}
+
+// Un point (x, &y) placé dans un produit, dans un record et dans le cas choisi d'une somme. Les cibles des
+// références sont des entiers alloués après des déchets, afin que le compactage les déplace.
+#[test]
+fn test_nested_aggregates() {
+    unsafe {
+        let point = ProductType(vec![Arc::new(type_tokens::INT), Arc::new(ReferenceType(TypeSig::INT))]);
+        let outer = ProductType(vec![Arc::new(type_tokens::BOOL), Arc::new(point.clone()), Arc::new(type_tokens::NAT)]);
+        let mut fields = LinkedHashMap::<String, Arc<dyn TypeInfo>>::new();
+        fields.insert("origin".to_string(), Arc::new(point.clone()));
+        fields.insert("weight".to_string(), Arc::new(type_tokens::DOUBLE));
+        let record = RecordType(Arc::new(fields));
+        let mut cases = LinkedHashMap::new();
+        cases.insert("Empty".to_string(), Arc::new(ProductType(vec![])));
+        cases.insert("Shifted".to_string(), Arc::new(ProductType(vec![Arc::new(type_tokens::NAT), Arc::new(point.clone())])));
+        let sum = SumType(cases);
+
+        assert!(outer.alignment_table(Layout::Padded) == vec![0, 8, 24] && outer.size(Layout::Padded) == 32, "Le point est placé en entier dans le produit");
+        assert!(record.alignment_table(Layout::Padded).values().copied().collect::<Vec<_>>() == vec![0, 16] && record.size(Layout::Padded) == 24, "Le point est placé en entier dans le record");
+        assert_eq!(ProductType(vec![Arc::new(type_tokens::NAT), Arc::new(point.clone())]).size(Layout::Padded), 24, "La taille d'un produit compte la taille du dernier champ");
+
+        let gc = GarbageCollector::new();
+        let (mut objects, offsets) = {
+            let mut borrowed = gc.borrow_mut();
+            let mut targets = vec![];
+            for i in 0..3 {
+                (0..10).for_each(|j| {
+                    borrowed.heap.write_int(-j).unwrap();
+                });
+                targets.push(borrowed.heap.write_int(100 + i).unwrap() as usize);
+            }
+            let inner = |x: i64, y: usize| Arc::new(vec![Arc::new(x) as Arc<dyn Any>, Arc::new(y)]) as Arc<dyn Any>;
+            let product = borrowed.heap.write_product(&[Arc::new(true), inner(1, targets[0]), Arc::new(7u64)], &outer).unwrap();
+            let mut data = LinkedHashMap::<String, Arc<dyn Any>>::new();
+            data.insert("origin".to_string(), inner(2, targets[1]));
+            data.insert("weight".to_string(), Arc::new(0.5f64));
+            let record_obj = borrowed.heap.write_record(&data, &record).unwrap();
+            let sum_obj = borrowed.heap.write_sum("Shifted", &[Arc::new(9u64), inner(3, targets[2])], &sum).unwrap();
+            let objects = vec![product, record_obj, sum_obj];
+            let offsets = objects.iter().map(|obj| borrowed.heap.pointers(*obj).unwrap().iter().map(|(_, offset)| *offset).collect::<Vec<_>>()).collect::<Vec<_>>();
+            assert_eq!(offsets, vec![vec![16], vec![8], vec![24]], "Les références des agrégats placés dans les champs sont trouvées");
+            assert_eq!(format_read_object(&borrowed.heap.read_obj(product).unwrap()), format!("Type: (Bool, (Int, &Int), Nat), données: [true, [1, {:x?}], 7]", targets[0]), "Les agrégats placés dans les champs sont relus");
+            let array_field = ProductType(vec![Arc::new(ArrayType(Arc::new(type_tokens::INT)))]);
+            let refused = borrowed.heap.write_product(&[Arc::new(Vec::<Arc<dyn Any>>::new())], &array_field);
+            assert!(matches!(refused, Err(AllocatorError::ObjectAllocationFailed(_))), "Les tableaux ne sont pas placés dans les champs");
+            (objects, offsets)
+        };
+
+        let forwarding = gc.borrow_mut().collect(&mut objects);
+        objects = objects.iter().map(|obj| forwarding[obj]).collect();
+        let mut borrowed = gc.borrow_mut();
+        assert!(objects.iter().zip(offsets.iter()).enumerate()
+            .all(|(i, (obj, offsets))| *target(*obj, offsets[0]).to_data_start::<i64>() == 100 + i as i64), "Les références placées dans les champs suivent leurs cibles");
+        assert_eq!(borrowed.heap.allocated_objects.len(), 6, "Seuls les objets accessibles ont survécu");
+        let read = borrowed.heap.read_obj(objects[1]).unwrap();
+        let map = read.1.downcast_ref::<LinkedHashMap<String, Arc<dyn Any>>>().unwrap();
+        let origin = map["origin"].downcast_ref::<Vec<Arc<dyn Any>>>().unwrap();
+        assert!(*origin[0].downcast_ref::<i64>().unwrap() == 2 && *map["weight"].downcast_ref::<f64>().unwrap() == 0.5, "Le record déplacé est intact");
+        drop(borrowed);
+
+        let mut heap = Heap::new();
+        let y = heap.alloc(42i64).unwrap();
+        let mut product = heap.alloc_product(&outer, &[Arc::new(false), Arc::new(vec![Arc::new(5i64) as Arc<dyn Any>, Arc::new(y.erase())]), Arc::new(1u64)]).unwrap();
+        heap.collect(&mut [&mut product]).unwrap();
+        let (_, data) = heap.read(product).unwrap();
+        let inner = data.downcast_ref::<Vec<Arc<dyn Any>>>().unwrap()[1].downcast_ref::<Vec<Arc<dyn Any>>>().unwrap().clone();
+        let reference = *inner[1].downcast_ref::<usize>().unwrap() as *mut ObjectHeader;
+        assert!(*inner[0].downcast_ref::<i64>().unwrap() == 5 && *reference.to_data_start::<i64>() == 42, "Le tas abaisse les handles placés dans les agrégats");
+    }
+}
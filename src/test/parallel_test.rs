@@ -0,0 +1,39 @@
+use std::collections::HashSet;
+use rand::Rng;
+use crate::allocator::object_allocator::ObjectHeader;
+use crate::gc::reachability::ObjectAllocatorExt;
+use crate::test::mocking::ObjectMocker;
+
+#[test]
+fn test_parallel_marking() {
+    unsafe {
+        let obj_mocker = &mut ObjectMocker::new();
+        let mut allocated_ptrs = vec![];
+        (0..20000).for_each(|_| {
+            let res = obj_mocker.mock_and_allocate_object().unwrap();
+            allocated_ptrs.push(res.1);
+        });
+        let mut root_objects = (0..500).map(|_| rand::thread_rng().gen_range(0..20000)).map(|i| allocated_ptrs[i]).collect::<HashSet<*mut ObjectHeader>>().into_iter().collect::<Vec<*mut ObjectHeader>>();
+        let reachables = obj_mocker.allocator.borrow().heap.reachable(&root_objects).unwrap();
+
+        obj_mocker.allocator.borrow_mut().mark_living(&mut root_objects);
+        let sequential = obj_mocker.allocator.borrow().all_marked_bits().into_iter().collect::<HashSet<_>>();
+
+        obj_mocker.allocator.borrow_mut().mark_threads = 4;
+        obj_mocker.allocator.borrow_mut().mark_living(&mut root_objects);
+        let parallel = obj_mocker.allocator.borrow().all_marked_bits().into_iter().collect::<HashSet<_>>();
+        assert_eq!(sequential, parallel, "Les deux marquages sont identiques");
+        assert_eq!(parallel, reachables, "Tous les bits mis sont accessibles");
+
+        // un second marquage ne recalcule aucune table de décalages
+        let (cached, hits) = (obj_mocker.allocator.borrow().heap.types.cached_offsets(), obj_mocker.allocator.borrow().heap.types.cache_hits());
+        obj_mocker.allocator.borrow_mut().mark_living(&mut root_objects);
+        assert!(obj_mocker.allocator.borrow().heap.types.cached_offsets() == cached && obj_mocker.allocator.borrow().heap.types.cache_hits() > hits,
+                "Le parcours des objets est servi par les tables du registre");
+
+        // la compaction s'appuie sur les tailles fusionnées des threads
+        let new_roots = obj_mocker.allocator.borrow_mut().collect(&mut root_objects);
+        root_objects = root_objects.iter().map(|root| new_roots[root]).collect();
+        assert_eq!(obj_mocker.allocator.borrow().heap.reachable(&root_objects).unwrap().len(), reachables.len(), "Tous les objets accessibles ont survécu au compactage");
+    }
+}
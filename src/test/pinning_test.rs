@@ -0,0 +1,56 @@
+use std::collections::HashSet;
+use rand::Rng;
+use crate::allocator::heap_allocator::HeapSpan;
+use crate::allocator::object_allocator::{ObjectHeader, ObjectHeaderHelper};
+use crate::gc::gc::GarbageCollector;
+use crate::gc::reachability::ObjectAllocatorExt;
+use crate::test::mocking::ObjectMocker;
+use crate::utils::io::format_read_object;
+use crate::vm_types::type_sig::TypeSig;
+
+#[test]
+fn test_pinning() {
+    unsafe {
+        let obj_mocker = &mut ObjectMocker::new();
+        let mut allocated_ptrs = vec![];
+        (0..1000).for_each(|_| {
+            let res = obj_mocker.mock_and_allocate_object().unwrap();
+            allocated_ptrs.push(res.1);
+        });
+        // les objets scalaires ne changent pas quand les objets qu'ils référencent sont déplacés
+        let scalars = allocated_ptrs.iter().copied().filter(|obj| (TypeSig::NAT..=TypeSig::BOOL).contains(&obj.type_sig())).collect::<Vec<_>>();
+        let pinned = (0..10).map(|_| scalars[rand::thread_rng().gen_range(0..scalars.len())]).collect::<HashSet<*mut ObjectHeader>>();
+        let mut roots = (0..50).map(|_| allocated_ptrs[rand::thread_rng().gen_range(0..1000)]).collect::<HashSet<*mut ObjectHeader>>().into_iter().collect::<Vec<_>>();
+        pinned.iter().for_each(|obj| obj_mocker.allocator.borrow_mut().pin(*obj));
+        let before = pinned.iter().map(|obj| (*obj, format_read_object(&obj_mocker.allocator.borrow_mut().heap.read_obj(*obj).unwrap()))).collect::<Vec<_>>();
+        let reachables = obj_mocker.allocator.borrow().heap.reachable(&roots.iter().chain(pinned.iter()).copied().collect::<Vec<_>>()).unwrap().len();
+        let allocated_before = obj_mocker.allocator.borrow().heap.allocator.allocated();
+
+        // les objets épinglés sont des racines, ils n'ont pas besoin d'être passés à la collection
+        let forwarding = obj_mocker.allocator.borrow_mut().collect(&mut roots);
+        roots = roots.iter().map(|root| forwarding[root]).collect();
+        let after = before.iter().map(|(obj, _)| (*obj, format_read_object(&obj_mocker.allocator.borrow_mut().heap.read_obj(*obj).unwrap()))).collect::<Vec<_>>();
+        assert_eq!(before, after, "Les objets épinglés n'ont pas bougé et sont intacts");
+        assert!(obj_mocker.allocator.borrow().heap.allocator.allocated() < allocated_before, "Les objets non épinglés sont compactés");
+        assert_eq!(obj_mocker.allocator.borrow().heap.reachable(&roots.iter().chain(pinned.iter()).copied().collect::<Vec<_>>()).unwrap().len(), reachables, "Tous les objets accessibles ont survécu");
+        assert!(obj_mocker.allocator.borrow().heap.allocated_objects.iter().all(|x| *x as usize % 8 == 0), "Tous les objets sont bien alignés");
+
+        // le tas doit rester parcourable malgré les trous laissés devant les objets épinglés
+        let mut walked = HashSet::new();
+        for block in obj_mocker.allocator.borrow().heap.allocator.committed_regions.values() {
+            let mut cur = GarbageCollector::next_object(block, None);
+            while let Some(c) = cur && block.contains(c.cast()) {
+                if c.type_sig() != TypeSig::FILLER {
+                    walked.insert(c);
+                }
+                cur = GarbageCollector::next_object(block, Some(c));
+            }
+        }
+        assert_eq!(walked, obj_mocker.allocator.borrow().heap.allocated_objects.iter().copied().collect::<HashSet<_>>(), "Le tas est parcourable");
+
+        assert!(pinned.iter().all(|obj| obj_mocker.allocator.borrow_mut().unpin(*obj)), "Tous les objets étaient épinglés");
+        let forwarding = obj_mocker.allocator.borrow_mut().collect(&mut roots);
+        roots = roots.iter().map(|root| forwarding[root]).collect();
+        assert_eq!(obj_mocker.allocator.borrow().heap.allocated_objects.len(), obj_mocker.allocator.borrow().heap.reachable(&roots).unwrap().len(), "Seuls les objets accessibles depuis les racines ont survécu après le désépinglage");
+    }
+}
@@ -0,0 +1,58 @@
+use rand::Rng;
+use crate::allocator::object_allocator::ObjectHeaderHelper;
+use crate::gc::reachability::ObjectAllocatorExt;
+use crate::gc::roots::{Global, HandleScope};
+use crate::test::mocking::ObjectMocker;
+use crate::utils::io::format_read_object;
+use crate::vm_types::type_sig::TypeSig;
+
+#[test]
+fn test_root_registry() {
+    unsafe {
+        let obj_mocker = &mut ObjectMocker::new();
+        let mut allocated_ptrs = vec![];
+        (0..1000).for_each(|_| {
+            let res = obj_mocker.mock_and_allocate_object().unwrap();
+            allocated_ptrs.push(res.1);
+        });
+        let registry = obj_mocker.allocator.borrow().roots.clone();
+        // les objets scalaires gardent leur représentation en étant déplacés, contrairement aux références
+        let scalars = allocated_ptrs.iter().copied().filter(|obj| (TypeSig::NAT..=TypeSig::BOOL).contains(&obj.type_sig())).collect::<Vec<_>>();
+        let pick = || scalars[rand::thread_rng().gen_range(0..scalars.len())];
+
+        let global = Global::new(&registry, pick());
+        let global_before = format_read_object(&obj_mocker.allocator.borrow_mut().heap.read_obj(global.get()).unwrap());
+        let scope = HandleScope::new(&registry);
+        let locals = (0..20).map(|_| scope.root(pick())).collect::<Vec<_>>();
+        let locals_before = locals.iter().map(|local| format_read_object(&obj_mocker.allocator.borrow_mut().heap.read_obj(local.get()).unwrap())).collect::<Vec<_>>();
+        // les objets composés sont accessibles par les références depuis les racines
+        let references = allocated_ptrs.iter().copied().filter(|obj| obj.type_sig() == TypeSig::REFERENCE).take(20).collect::<Vec<_>>();
+        let reference_locals = references.iter().map(|obj| scope.root(*obj)).collect::<Vec<_>>();
+        let reachables = obj_mocker.allocator.borrow().heap.reachable(&registry.borrow().roots()).unwrap().len();
+        {
+            // une portée imbriquée, ses racines disparaissent avec elle
+            let inner = HandleScope::new(&registry);
+            (0..20).for_each(|_| {
+                inner.root(pick());
+            });
+            assert_eq!(registry.borrow().roots().len(), 61, "Racines pendant la portée imbriquée");
+        }
+        assert_eq!(registry.borrow().roots().len(), 41, "Racines après la portée imbriquée");
+
+        // aucune racine n'est passée à la collection, les racines enregistrées suffisent
+        obj_mocker.allocator.borrow_mut().collect(&mut []);
+        let locals_after = locals.iter().map(|local| format_read_object(&obj_mocker.allocator.borrow_mut().heap.read_obj(local.get()).unwrap())).collect::<Vec<_>>();
+        let global_after = format_read_object(&obj_mocker.allocator.borrow_mut().heap.read_obj(global.get()).unwrap());
+        assert_eq!(locals_before, locals_after, "Les racines locales suivent leurs objets");
+        assert_eq!(global_before, global_after, "La racine globale suit son objet");
+        assert_eq!(obj_mocker.allocator.borrow().heap.reachable(&registry.borrow().roots()).unwrap().len(), reachables, "Tous les objets accessibles ont survécu");
+
+        drop(locals);
+        drop(reference_locals);
+        drop(scope);
+        drop(global);
+        assert!(registry.borrow().roots().is_empty(), "Aucune racine après la destruction des portées");
+        obj_mocker.allocator.borrow_mut().collect(&mut []);
+        assert_eq!(obj_mocker.allocator.borrow().heap.allocator.allocated(), 0, "Le tas est vide");
+    }
+}
@@ -0,0 +1,66 @@
+use rand::Rng;
+use rand::seq::SliceRandom;
+use crate::allocator::object_allocator::{ObjectHeader, ObjectHeaderHelper};
+use crate::gc::collector::CollectorKind;
+use crate::gc::reachability::ObjectAllocatorExt;
+use crate::gc::semispace::SemispaceCollector;
+use crate::heap::Heap;
+use crate::test::mocking::ObjectMocker;
+use crate::utils::errors::{AllocatorError, HeapError};
+use crate::utils::io::format_read_object;
+use crate::vm_types::type_sig::TypeSig;
+
+#[test]
+fn test_semispace() {
+    unsafe {
+        let obj_mocker = &mut ObjectMocker::new();
+        let mut gc = SemispaceCollector::new(64 * 1024).unwrap();
+        let mut roots: Vec<*mut ObjectHeader> = vec![];
+        let mut collections = 0;
+        let mut all_preserved = true;
+        let mut all_intact = true;
+        for _ in 0..10000 {
+            let mut obj = obj_mocker.mock_object(0, false).unwrap();
+            let allocated = match gc.heap.allocate_general(&obj) {
+                Err(AllocatorError::OutOfMemory) => {
+                    roots.shuffle(&mut rand::thread_rng());
+                    roots.truncate(20);
+                    let before = gc.heap.reachable(&roots).unwrap().len();
+                    // les objets scalaires ne changent pas en étant copiés
+                    let scalars = roots.iter().filter(|root| (TypeSig::NAT..=TypeSig::BOOL).contains(&root.type_sig()))
+                        .map(|root| (*root, format_read_object(&gc.heap.read_obj(*root).unwrap()))).collect::<Vec<_>>();
+                    let new_roots = gc.collect(&mut roots);
+                    roots = roots.iter().map(|root| new_roots[root]).collect();
+                    all_preserved &= gc.heap.reachable(&roots).unwrap().len() == before;
+                    all_intact &= scalars.into_iter().all(|(old, formatted)| format_read_object(&gc.heap.read_obj(new_roots[&old]).unwrap()) == formatted);
+                    collections += 1;
+                    obj_mocker.mocked_objects_ptrs = roots.iter().map(|root| (TypeSig::to_type_kind(root.type_sig()), *root)).collect();
+                    obj = obj_mocker.mock_object(0, false).unwrap();
+                    gc.heap.allocate_general(&obj).unwrap()
+                }
+                res => res.unwrap()
+            };
+            obj_mocker.mocked_objects_ptrs.push((obj.0.kind(), allocated));
+            if rand::thread_rng().gen_bool(0.1) {
+                roots.push(allocated);
+            }
+        }
+        assert!(collections > 0, "Les demi-espaces ont été échangés");
+        assert!(all_preserved, "Tous les objets accessibles ont survécu aux collections");
+        assert!(all_intact, "Les objets copiés sont intacts");
+        assert!(gc.heap.allocated_objects.iter().all(|obj| gc.heap.allocator.get_block(obj.cast()).is_some()), "Tous les objets sont dans l'espace d'origine");
+    }
+}
+
+// Un espace vide ne peut pas être réservé, la construction du tas échoue au lieu d'allouer un bloc de taille nulle.
+#[test]
+fn test_zero_sized_spaces() {
+    let kinds = [
+        CollectorKind::Semispace { semispace_size: 0 },
+        CollectorKind::Generational { nursery_size: 0, promotion_threshold: 3 }
+    ];
+    for kind in kinds {
+        let heap = Heap::with_collector(kind);
+        assert!(matches!(heap, Err(HeapError::Allocator(AllocatorError::FailedToCreateLayout))), "Un espace vide est refusé ({:?})", kind);
+    }
+}
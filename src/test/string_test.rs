@@ -0,0 +1,53 @@
+use crate::gc::gc::GarbageCollector;
+use crate::gc::reachability::ObjectAllocatorExt;
+use crate::heap::Heap;
+use crate::test::mocking::ObjectMocker;
+use crate::utils::errors::AllocatorError;
+use crate::utils::io::format_read_object;
+use crate::vm_types::type_kind::TypeKind;
+
+// Des chaînes vides, ASCII et de plusieurs bytes par caractère, intercalées avec des déchets afin que le compactage
+// les déplace.
+#[test]
+fn test_strings() {
+    unsafe {
+        let obj_mocker = &mut ObjectMocker::new();
+        let strings = ["", "bonjour", "déjà vu", "Straße", "中文字符", "🦀 crabe 🦀"];
+        let gc = GarbageCollector::new();
+        let mut objects = {
+            let mut borrowed = gc.borrow_mut();
+            strings.iter().map(|string| {
+                borrowed.heap.write_string(&string.repeat(3)).unwrap();
+                borrowed.heap.write_string(string).unwrap()
+            }).collect::<Vec<_>>()
+        };
+        {
+            let mut borrowed = gc.borrow_mut();
+            assert!(objects.iter().zip(strings).all(|(obj, string)| borrowed.heap.read_string(*obj).unwrap() == string), "Les chaînes sont relues");
+            assert!(objects.iter().all(|obj| borrowed.heap.pointers(*obj).unwrap().is_empty()), "Les chaînes ne contiennent aucun pointeur");
+            assert_eq!(format_read_object(&borrowed.heap.read_obj(objects[2]).unwrap()), "Type: String, données: \"déjà vu\"", "Les chaînes sont formatées");
+            let int = borrowed.heap.write_int(1).unwrap();
+            assert!(matches!(borrowed.heap.read_string(int), Err(AllocatorError::TypeMismatch(_))), "Seules les chaînes sont lues comme des chaînes");
+        }
+
+        let forwarding = gc.borrow_mut().collect(&mut objects);
+        objects = objects.iter().map(|obj| forwarding[obj]).collect();
+        let borrowed = gc.borrow();
+        assert!(objects.iter().zip(strings).all(|(obj, string)| borrowed.heap.read_string(*obj).unwrap() == string), "Les chaînes déplacées sont intactes");
+        assert_eq!(borrowed.heap.allocated_objects.len(), strings.len(), "Les chaînes mortes ont été récupérées");
+        drop(borrowed);
+
+        // le mocker génère des chaînes parmi les autres objets
+        let mocked = (0..500).map(|_| obj_mocker.mock_and_allocate_object().unwrap())
+            .filter(|result| result.0.0.kind() == TypeKind::String)
+            .collect::<Vec<_>>();
+        assert!(!mocked.is_empty() && mocked.iter().all(|result|
+            obj_mocker.allocator.borrow().heap.read_string(result.1).unwrap() == result.0.1.downcast_ref::<String>().unwrap()), "Le mocker génère des chaînes");
+
+        let mut heap = Heap::new();
+        heap.alloc_str("déchet").unwrap();
+        let mut string = heap.alloc_str("été 🦀").unwrap();
+        heap.collect(&mut [&mut string]).unwrap();
+        assert_eq!(heap.get_str(string).unwrap(), "été 🦀", "Le tas alloue des chaînes");
+    }
+}
@@ -0,0 +1,92 @@
+use std::any::Any;
+use std::sync::Arc;
+use linked_hash_map::LinkedHashMap;
+use crate::allocator::object_allocator::{ObjectHeader, ObjectHeaderHelper, Variant};
+use crate::gc::gc::GarbageCollector;
+use crate::gc::generational::GenerationalCollector;
+use crate::gc::reachability::ObjectAllocatorExt;
+use crate::heap::Heap;
+use crate::utils::errors::AllocatorError;
+use crate::utils::io::format_read_object;
+use crate::vm_types::layout::Layout;
+use crate::vm_types::type_info::{ProductType, ReferenceType, SumType, TypeInfo};
+use crate::vm_types::type_sig::TypeSig;
+use crate::vm_types::type_tokens;
+
+fn shape() -> SumType {
+    let mut cases = LinkedHashMap::new();
+    cases.insert("Point".to_string(), Arc::new(ProductType(vec![])));
+    cases.insert("Circle".to_string(), Arc::new(ProductType(vec![Arc::new(type_tokens::DOUBLE)])));
+    cases.insert("Rect".to_string(), Arc::new(ProductType(vec![Arc::new(type_tokens::DOUBLE), Arc::new(type_tokens::DOUBLE), Arc::new(ReferenceType(TypeSig::INT))])));
+    SumType(cases)
+}
+
+// Une forme change de cas sans être déplacée, les références du cas courant sont les seules à être suivies.
+#[test]
+fn test_sums() {
+    unsafe {
+        let shape = shape();
+        assert!(shape.size(Layout::Padded) == 8 + 24 && shape.alignment_table(2, Layout::Padded) == vec![8, 16, 24], "La taille d'une somme est celle de son plus grand cas");
+
+        let gc = GarbageCollector::new();
+        let (mut objects, target) = {
+            let mut borrowed = gc.borrow_mut();
+            (0..10).for_each(|i| {
+                borrowed.heap.write_int(-i).unwrap();
+            });
+            let target = borrowed.heap.write_int(42).unwrap();
+            let circle = borrowed.heap.write_sum("Circle", &[Arc::new(1.5f64)], &shape).unwrap();
+            assert_eq!(borrowed.heap.read_tag(circle).unwrap(), 1, "L'étiquette est lue dans l'objet");
+            let count = borrowed.heap.allocated_objects.len();
+            borrowed.heap.set_variant(circle, "Rect", &[Arc::new(2.0f64), Arc::new(3.0f64), Arc::new(target as usize)]).unwrap();
+            assert!(borrowed.heap.read_tag(circle).unwrap() == 2 && borrowed.heap.allocated_objects.len() == count, "Le changement de cas se fait sur place");
+            assert_eq!(borrowed.heap.pointers(circle).unwrap().into_iter().collect::<Vec<_>>(), vec![(target, 24)], "Les références du nouveau cas sont trouvées");
+            assert!(matches!(borrowed.heap.set_variant(circle, "Triangle", &[]), Err(AllocatorError::ObjectAllocationFailed(_))), "Les cas inconnus sont refusés");
+            assert!(matches!(borrowed.heap.set_variant(circle, "Point", &[Arc::new(1.0f64)]), Err(AllocatorError::ProductSizeMismatch))
+                && borrowed.heap.read_tag(circle).unwrap() == 2, "Les champs du cas sont vérifiés");
+            let int = borrowed.heap.write_int(1).unwrap();
+            assert!(matches!(borrowed.heap.read_tag(int), Err(AllocatorError::TypeMismatch(_))), "Seules les sommes ont une étiquette");
+            (vec![circle], target)
+        };
+
+        let forwarding = gc.borrow_mut().collect(&mut objects);
+        objects = objects.iter().map(|obj| forwarding[obj]).collect();
+        {
+            let mut borrowed = gc.borrow_mut();
+            let (moved, offset) = borrowed.heap.pointers(objects[0]).unwrap().into_iter().next().unwrap();
+            assert!(offset == 24 && moved != target && *moved.to_data_start::<i64>() == 42, "La référence du cas courant suit sa cible");
+            assert_eq!(format_read_object(&borrowed.heap.read_obj(objects[0]).unwrap()), format!("Type: {}, choisi: Rect, données: [2, 3, {:x?}]", shape.name(), moved as usize), "La somme déplacée est relue");
+            borrowed.heap.set_variant(objects[0], "Point", &[]).unwrap();
+            assert!(borrowed.heap.pointers(objects[0]).unwrap().is_empty(), "Les références de l'ancien cas ne sont plus suivies");
+        }
+        let forwarding = gc.borrow_mut().collect(&mut objects);
+        objects = objects.iter().map(|obj| forwarding[obj]).collect();
+        let mut borrowed = gc.borrow_mut();
+        assert_eq!(borrowed.heap.allocated_objects.len(), 1, "La cible de l'ancien cas a été récupérée");
+        let read = borrowed.heap.read_obj(objects[0]).unwrap();
+        assert_eq!(read.1.downcast_ref::<Variant>().unwrap().case, "Point", "Le cas est relu depuis les données");
+        drop(borrowed);
+
+        // une somme mature qui passe à un cas référençant un objet jeune est retenue par la barrière d'écriture
+        let mut generational = GenerationalCollector::new(16 * 1024, 3).unwrap();
+        let old = generational.mature.borrow_mut().heap.write_sum("Point", &[], &shape).unwrap();
+        // la somme ne contient aucune référence, elle est oubliée par la première collection mineure
+        generational.minor_collect(&mut []).unwrap();
+        let young = generational.nursery.write_int(7).unwrap();
+        generational.mature.borrow_mut().heap.set_variant(old, "Rect", &[Arc::new(0.0f64), Arc::new(0.0f64), Arc::new(young as usize)]).unwrap();
+        assert_eq!(generational.remembered_set_size(), 1, "Le changement de cas passe par la barrière d'écriture");
+        generational.minor_collect(&mut []).unwrap();
+        let promoted = generational.mature.borrow().heap.pointers(old).unwrap().into_iter().next().unwrap().0;
+        assert!(promoted != young && *promoted.to_data_start::<i64>() == 7, "L'objet jeune référencé par le nouveau cas survit");
+
+        let mut heap = Heap::new();
+        let y = heap.alloc(5i64).unwrap();
+        let mut sum = heap.alloc_sum(&shape, "Circle", &[Arc::new(0.5f64)]).unwrap();
+        heap.set_variant(sum, "Rect", &[Arc::new(1.0f64), Arc::new(1.0f64), Arc::new(y.erase()) as Arc<dyn Any>]).unwrap();
+        heap.collect(&mut [&mut sum]).unwrap();
+        let (_, data) = heap.read(sum).unwrap();
+        let variant = data.downcast_ref::<Variant>().unwrap();
+        let reference = *variant.fields[2].downcast_ref::<usize>().unwrap() as *mut ObjectHeader;
+        assert!(heap.sum_case(sum).unwrap() == "Rect" && *reference.to_data_start::<i64>() == 5, "Le tas change le cas d'une somme");
+    }
+}
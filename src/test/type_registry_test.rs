@@ -0,0 +1,53 @@
+use std::mem::size_of;
+use std::sync::Arc;
+use crate::allocator::object_allocator::{ObjectHeader, ObjectHeaderHelper};
+use crate::gc::gc::GarbageCollector;
+use crate::gc::generational::GenerationalCollector;
+use crate::utils::io::format_read_object;
+use crate::vm_types::type_info::{ProductType, ReferenceType, TypeInfo};
+use crate::vm_types::type_registry::TypeRegistry;
+use crate::vm_types::type_sig::TypeSig;
+use crate::vm_types::type_tokens;
+
+// Les objets de types structurellement égaux partagent le même type, le registre est libéré avec le tas.
+#[test]
+fn test_type_registry() {
+    unsafe {
+        assert_eq!(size_of::<ObjectHeader>(), 3 * size_of::<usize>(), "L'en-tête ne contient plus de pointeur large");
+
+        let gc = GarbageCollector::new();
+        let registry = Arc::downgrade(&gc.borrow().heap.types);
+        {
+            let mut borrowed = gc.borrow_mut();
+            let int = borrowed.heap.write_int(1).unwrap();
+            let string = borrowed.heap.write_string("abc").unwrap();
+            assert!(int.type_index() == TypeRegistry::INT && string.type_index() == TypeRegistry::STRING, "Les scalaires ont des indices fixes");
+
+            let before = borrowed.heap.types.len();
+            // deux copies du même type, construites séparément
+            let point = || ProductType(vec![Arc::new(type_tokens::INT), Arc::new(ReferenceType(TypeSig::INT))]);
+            let objects = (0..100).map(|i| borrowed.heap.write_product(&[Arc::new(i as i64), Arc::new(int as usize)], &point()).unwrap()).collect::<Vec<_>>();
+            assert!(borrowed.heap.types.len() == before + 1
+                && objects.iter().all(|obj| obj.type_index() == objects[0].type_index()), "Les types égaux ne sont enregistrés qu'une seule fois");
+            let other = borrowed.heap.write_product(&[Arc::new(1i64)], &ProductType(vec![Arc::new(type_tokens::INT)])).unwrap();
+            let reference = borrowed.heap.write_reference(int as usize, &ReferenceType(TypeSig::INT)).unwrap();
+            assert!(borrowed.heap.types.len() == before + 3
+                && [other.type_index(), reference.type_index()].iter().all(|id| *id != objects[0].type_index()), "Les types différents ont des indices différents");
+            assert!(borrowed.heap.type_of(objects[0]).unwrap().name() == point().name()
+                && format_read_object(&borrowed.heap.read_obj(objects[99]).unwrap()) == format!("Type: (Int, &Int), données: [99, {:x?}]", int as usize), "Le type est retrouvé par son indice");
+        }
+        drop(gc);
+        assert!(registry.upgrade().is_none(), "Le registre est libéré avec le tas");
+
+        // les objets promus gardent l'indice de leur type, les deux tas partagent le même registre
+        let mut generational = GenerationalCollector::new(16 * 1024, 1).unwrap();
+        let product = generational.nursery.write_product(&[Arc::new(5i64)], &ProductType(vec![Arc::new(type_tokens::INT)])).unwrap();
+        let mut roots = vec![product];
+        for _ in 0..3 {
+            let forwarding = generational.minor_collect(&mut roots).unwrap();
+            roots = roots.iter().map(|root| forwarding[root]).collect();
+        }
+        assert!(!generational.in_nursery(roots[0])
+            && format_read_object(&generational.mature.borrow_mut().heap.read_obj(roots[0]).unwrap()) == "Type: (Int), données: [5]", "Les objets promus gardent leur type");
+    }
+}
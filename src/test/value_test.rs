@@ -0,0 +1,100 @@
+use std::any::Any;
+use std::sync::Arc;
+use linked_hash_map::LinkedHashMap;
+use crate::allocator::object_allocator::ObjectAllocator;
+use crate::allocator::value::Value;
+use crate::gc::gc::GarbageCollector;
+use crate::utils::errors::AllocatorError;
+use crate::vm_types::layout::Layout;
+use crate::vm_types::type_info::{ArrayType, ProductType, RecordType, ReferenceType, SumType, TypeInfo};
+use crate::vm_types::type_sig::TypeSig;
+use crate::vm_types::type_tokens;
+
+fn shape() -> Arc<dyn TypeInfo> {
+    let mut cases = LinkedHashMap::new();
+    cases.insert("Point".to_string(), Arc::new(ProductType(vec![])));
+    cases.insert("Circle".to_string(), Arc::new(ProductType(vec![Arc::new(type_tokens::DOUBLE)])));
+    Arc::new(SumType(cases))
+}
+
+fn entity() -> Arc<dyn TypeInfo> {
+    let mut fields = LinkedHashMap::<String, Arc<dyn TypeInfo>>::new();
+    fields.insert("id".to_string(), Arc::new(type_tokens::NAT));
+    fields.insert("position".to_string(), Arc::new(ProductType(vec![Arc::new(type_tokens::INT), Arc::new(type_tokens::INT)])));
+    fields.insert("shape".to_string(), shape());
+    fields.insert("visible".to_string(), Arc::new(type_tokens::BOOL));
+    fields.insert("label".to_string(), Arc::new(ReferenceType(TypeSig::STRING)));
+    Arc::new(RecordType(Arc::new(fields)))
+}
+
+fn entity_value(id: u64, label: Value) -> Value {
+    let mut fields = LinkedHashMap::new();
+    fields.insert("id".to_string(), Value::Nat(id));
+    fields.insert("position".to_string(), Value::Product(vec![Value::Int(-3), Value::Int(4)]));
+    fields.insert("shape".to_string(), Value::Sum("Circle".to_string(), vec![Value::Double(1.5)]));
+    fields.insert("visible".to_string(), Value::Bool(true));
+    fields.insert("label".to_string(), label);
+    Value::Record(fields)
+}
+
+// Les objets sont écrits et relus comme des valeurs typées, les valeurs qui ne correspondent pas à leur type sont
+// refusées avant toute allocation.
+#[test]
+fn test_values() {
+    unsafe {
+        let gc = GarbageCollector::new();
+        let mut objects = {
+            let mut borrowed = gc.borrow_mut();
+            (0..10).for_each(|i| {
+                borrowed.heap.write_int(i).unwrap();
+            });
+            let label = borrowed.heap.write_value(&Value::Str("étiquette".to_string()), &(Arc::new(type_tokens::STRING) as Arc<dyn TypeInfo>)).unwrap();
+            let entity = borrowed.heap.write_value(&entity_value(7, Value::Ref(label)), &entity()).unwrap();
+            assert_eq!(borrowed.heap.read_value(entity).unwrap(), entity_value(7, Value::Ref(label)), "Une valeur est relue telle qu'elle a été écrite");
+            let letters = Value::Array("abc".chars().map(Value::Char).collect());
+            let array = borrowed.heap.write_value(&letters, &(Arc::new(ArrayType(Arc::new(type_tokens::CHAR))) as Arc<dyn TypeInfo>)).unwrap();
+            assert_eq!(borrowed.heap.read_value(array).unwrap(), letters, "Les tableaux sont des valeurs");
+            let double = borrowed.heap.write_double(0.25).unwrap();
+            assert_eq!(borrowed.heap.read_value(double).unwrap(), Value::Double(0.25), "Les scalaires sont des valeurs");
+            vec![entity]
+        };
+
+        let forwarding = gc.borrow_mut().collect(&mut objects);
+        objects = objects.iter().map(|obj| forwarding[obj]).collect();
+        {
+            let mut borrowed = gc.borrow_mut();
+            let Value::Record(fields) = borrowed.heap.read_value(objects[0]).unwrap() else { panic!() };
+            let Value::Ref(label) = fields["label"] else { panic!() };
+            assert!(borrowed.heap.read_value(label).unwrap() == Value::Str("étiquette".to_string())
+                && borrowed.heap.read_value(objects[0]).unwrap() == entity_value(7, Value::Ref(label)), "Les références relues suivent leur cible");
+
+            let count = borrowed.heap.allocated_objects.len();
+            let int: Arc<dyn TypeInfo> = Arc::new(type_tokens::INT);
+            assert!(matches!(borrowed.heap.write_value(&Value::Double(1.0), &int), Err(AllocatorError::TypeMismatch(_))), "Un scalaire du mauvais type est refusé");
+            let mut wrong_field = entity_value(8, Value::Ref(label));
+            if let Value::Record(fields) = &mut wrong_field {
+                fields.insert("position".to_string(), Value::Product(vec![Value::Int(1), Value::Bool(false)]));
+            }
+            assert!(matches!(borrowed.heap.write_value(&wrong_field, &entity()), Err(AllocatorError::TypeMismatch(_))), "Un champ imbriqué du mauvais type est refusé");
+            if let Value::Record(fields) = &mut wrong_field {
+                fields.insert("position".to_string(), Value::Product(vec![Value::Int(1)]));
+            }
+            assert!(matches!(borrowed.heap.write_value(&wrong_field, &entity()), Err(AllocatorError::ProductSizeMismatch)), "Un produit incomplet est refusé");
+            if let Value::Record(fields) = &mut wrong_field {
+                fields.insert("position".to_string(), Value::Product(vec![Value::Int(1), Value::Int(2)]));
+                fields.insert("color".to_string(), Value::Nat(0));
+            }
+            assert!(matches!(borrowed.heap.write_value(&wrong_field, &entity()), Err(AllocatorError::UnknownField(_))), "Un champ inconnu est refusé");
+            assert!(matches!(borrowed.heap.write_value(&Value::Sum("Square".to_string(), vec![]), &shape()), Err(AllocatorError::TypeMismatch(_))), "Un cas inconnu est refusé");
+            assert_eq!(borrowed.heap.allocated_objects.len(), count, "Une valeur refusée n'alloue rien");
+            let nat: Arc<dyn TypeInfo> = Arc::new(type_tokens::NAT);
+            assert!(matches!(borrowed.heap.allocate_general(&(nat, Arc::new(1.0f64) as Arc<dyn Any>)), Err(AllocatorError::FailedToReadData(_))), "Les données du mauvais type sont refusées par allocate_general");
+        }
+
+        let mut compact = ObjectAllocator::new();
+        compact.set_layout(Layout::Compact).unwrap();
+        let label = compact.write_string("abc").unwrap();
+        let entity = compact.write_value(&entity_value(9, Value::Ref(label)), &entity()).unwrap();
+        assert_eq!(compact.read_value(entity).unwrap(), entity_value(9, Value::Ref(label)), "Les valeurs suivent la disposition du tas");
+    }
+}
@@ -0,0 +1,80 @@
+use std::sync::Arc;
+use crate::allocator::object_allocator::{ObjectHeader, ObjectHeaderHelper};
+use crate::gc::collector::CollectorKind;
+use crate::gc::gc::SweepMode;
+use crate::gc::generational::GenerationalCollector;
+use crate::gc::reachability::ObjectAllocatorExt;
+use crate::heap::Heap;
+use crate::vm_types::type_info::{ProductType, WeakReferenceType};
+use crate::vm_types::type_sig::TypeSig;
+use crate::vm_types::type_tokens;
+
+// la cible de la référence faible que `obj` contient à `offset`
+unsafe fn weak_target(obj: *mut ObjectHeader, offset: usize) -> *mut ObjectHeader {
+    *obj.to_data_start::<u8>().add(offset).cast::<*mut ObjectHeader>()
+}
+
+// Des références faibles, seules ou dans un produit, vers des entiers dont seuls les pairs restent accessibles.
+#[test]
+fn test_weak_references() {
+    unsafe {
+        let kinds = [
+            CollectorKind::MarkCompact,
+            CollectorKind::MarkSweep(SweepMode::FreeList),
+            CollectorKind::Semispace { semispace_size: 64 * 1024 },
+            CollectorKind::Generational { nursery_size: 16 * 1024, promotion_threshold: 2 }
+        ];
+        let weak_type = WeakReferenceType(TypeSig::INT);
+        let holder_type = ProductType(vec![Arc::new(type_tokens::INT), Arc::new(weak_type)]);
+        for kind in kinds {
+            let mut collector = kind.build().unwrap();
+            // des déchets devant les cibles, afin que le compactage les déplace
+            (0..50).for_each(|i| {
+                collector.heap().write_int(-i).unwrap();
+            });
+            let mut targets = (0..20).map(|i| collector.heap().write_int(i).unwrap()).collect::<Vec<_>>();
+            let mut weak_refs = targets.iter().map(|target| collector.heap().write_weak_reference(*target as usize, &weak_type).unwrap()).collect::<Vec<_>>();
+            let mut holders = targets.iter().enumerate().map(|(i, target)| collector.heap().write_product(&[Arc::new(i as i64), Arc::new(*target as usize)], &holder_type).unwrap()).collect::<Vec<_>>();
+            let offset = collector.heap().field_slot(holders[0], 1.into()).unwrap().1;
+            assert!(collector.heap().pointers(weak_refs[0]).unwrap().is_empty(), "Les références faibles ne sont pas des pointeurs ({:?})", kind);
+
+            let (mut followed, mut cleared) = (true, true);
+            // plusieurs collections, les objets de la pépinière sont promus au fil des collections mineures
+            for _ in 0..4 {
+                let mut roots = weak_refs.iter().chain(holders.iter()).chain(targets.iter().step_by(2)).copied().collect::<Vec<_>>();
+                let forwarding = collector.collect(&mut roots).unwrap();
+                weak_refs = weak_refs.iter().map(|obj| forwarding[obj]).collect();
+                holders = holders.iter().map(|obj| forwarding[obj]).collect();
+                targets = targets.iter().enumerate().map(|(i, obj)| if i % 2 == 0 { forwarding[obj] } else { *obj }).collect();
+                for i in 0..20 {
+                    let (weak, held) = (weak_target(weak_refs[i], 0), weak_target(holders[i], offset));
+                    if i % 2 == 0 {
+                        followed &= weak == targets[i] && held == targets[i] && *weak.to_data_start::<i64>() == i as i64;
+                    } else {
+                        cleared &= weak.is_null() && held.is_null();
+                    }
+                }
+            }
+            assert!(followed, "Les références faibles suivent les cibles vivantes ({:?})", kind);
+            assert!(cleared, "Les références faibles vers les cibles mortes sont nulles ({:?})", kind);
+        }
+
+        // une référence faible mature vers la pépinière, écrite après coup: la barrière la retient
+        let mut gc = GenerationalCollector::new(16 * 1024, 3).unwrap();
+        let old = gc.mature.borrow_mut().heap.write_weak_reference(0, &weak_type).unwrap();
+        gc.minor_collect(&mut []).unwrap();
+        let young = gc.nursery.write_int(7).unwrap();
+        gc.mature.borrow_mut().heap.set_reference(old, 0, young).unwrap();
+        let forwarding = gc.minor_collect(&mut [young]).unwrap();
+        assert_eq!(weak_target(old, 0), forwarding[&young], "La référence faible mature suit sa cible");
+        gc.minor_collect(&mut []).unwrap();
+        assert!(weak_target(old, 0).is_null(), "La référence faible mature est mise à nul");
+
+        let mut heap = Heap::new();
+        let target = heap.alloc(42i64).unwrap();
+        let mut weak = heap.alloc_weak_reference(target).unwrap();
+        assert!(heap.deref_weak(weak).unwrap().is_some(), "La cible est lisible avant la collection");
+        heap.collect(&mut [&mut weak]).unwrap();
+        assert!(heap.deref_weak(weak).unwrap().is_none(), "La cible a été récupérée");
+    }
+}
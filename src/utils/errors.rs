@@ -7,7 +7,11 @@ pub enum AllocatorError {
     ProductSizeMismatch,
     ObjectAllocationFailed(String),
     ReadObjectFailed(String),
-    FailedToReadData(String)
+    FailedToReadData(String),
+    UnknownField(String),
+    TypeMismatch(String),
+    // the layout of a heap can only change while it holds no object
+    HeapNotEmpty
 }
 
 #[derive(Debug)]
@@ -15,4 +19,28 @@ pub enum GCError {
     FailedToReadObjectAt(*const usize),
     InvalidRoots,
     InvalidAddress
+}
+
+#[derive(Debug)]
+pub enum HeapError {
+    Allocator(AllocatorError),
+    GC(GCError),
+    // the handle was not passed as a root to the last collection, its object may have moved or died
+    StaleHandle,
+    // the handle was issued by another heap
+    ForeignHandle,
+    // the operation needs the bitmap collector, but the heap was built with another one
+    UnsupportedByCollector
+}
+
+impl From<AllocatorError> for HeapError {
+    fn from(value: AllocatorError) -> Self {
+        HeapError::Allocator(value)
+    }
+}
+
+impl From<GCError> for HeapError {
+    fn from(value: GCError) -> Self {
+        HeapError::GC(value)
+    }
 }
\ No newline at end of file
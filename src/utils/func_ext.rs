@@ -1,10 +1,9 @@
-use std::any::Any;
-
 pub fn identity_once<T>(x: T) -> T {
     x
 }
 
 pub trait FuncExt: Sized {
+    #[allow(dead_code)]
     fn ignore(&self);
 
     fn apply<F: FnOnce(&mut Self)>(&mut self, f: F) -> &Self;
@@ -2,8 +2,8 @@ use std::any::Any;
 use std::mem::size_of;
 use std::sync::Arc;
 use linked_hash_map::LinkedHashMap;
-use crate::allocator::object_allocator::ObjectHeader;
-use crate::vm_types::type_info::{SumType, TypeInfo};
+use crate::allocator::object_allocator::{ObjectHeader, Variant};
+use crate::vm_types::type_info::TypeInfo;
 use crate::vm_types::type_kind::TypeKind;
 
 pub fn format_heterogeneous_list(list: &Vec<Arc<dyn Any>>) -> String {
@@ -21,6 +21,12 @@ pub fn format_heterogeneous_list(list: &Vec<Arc<dyn Any>>) -> String {
             vec.push(character.to_string());
         } else if let Some(boolean) = item.downcast_ref::<bool>() {
             vec.push(boolean.to_string());
+        } else if let Some(list) = item.downcast_ref::<Vec<Arc<dyn Any>>>() {
+            vec.push(format_heterogeneous_list(list));
+        } else if let Some(map) = item.downcast_ref::<LinkedHashMap<String, Arc<dyn Any>>>() {
+            vec.push(format_heterogeneous_map(map));
+        } else if let Some(variant) = item.downcast_ref::<Variant>() {
+            vec.push(format_variant(variant));
         } else {
             vec.push("Unknown type".to_string());
         }
@@ -43,6 +49,12 @@ pub fn format_heterogeneous_map(map: &LinkedHashMap<String, Arc<dyn Any>>) -> St
             vec.push(format!("{}: {}", name, character));
         } else if let Some(boolean) = item.downcast_ref::<bool>() {
             vec.push(format!("{}: {}", name, boolean));
+        } else if let Some(list) = item.downcast_ref::<Vec<Arc<dyn Any>>>() {
+            vec.push(format!("{}: {}", name, format_heterogeneous_list(list)));
+        } else if let Some(map) = item.downcast_ref::<LinkedHashMap<String, Arc<dyn Any>>>() {
+            vec.push(format!("{}: {}", name, format_heterogeneous_map(map)));
+        } else if let Some(variant) = item.downcast_ref::<Variant>() {
+            vec.push(format!("{}: {}", name, format_variant(variant)));
         } else {
             vec.push("Unknown type".to_string());
         }
@@ -50,12 +62,16 @@ pub fn format_heterogeneous_map(map: &LinkedHashMap<String, Arc<dyn Any>>) -> St
     format!("[{}]", vec.join(", "))
 }
 
+pub fn format_variant(variant: &Variant) -> String {
+    format!("{}{}", variant.case, format_heterogeneous_list(&variant.fields))
+}
+
 pub unsafe fn format_read_object(tuple: &(Arc<dyn TypeInfo>, Arc<dyn Any>)) -> String {
     let (ty, data) = tuple;
     match ty.kind() {
         TypeKind::Nat =>
             format!("Type: {}, données: {}", ty.name(), data.downcast_ref_unchecked::<u64>()),
-        TypeKind::Reference =>
+        TypeKind::Reference | TypeKind::WeakReference =>
             format!("Type: {}, données: {:x?}", ty.name(), data.downcast_ref_unchecked::<usize>()),
         TypeKind::Int =>
             format!("Type: {}, données: {}", ty.name(), data.downcast_ref_unchecked::<i64>()),
@@ -65,15 +81,18 @@ pub unsafe fn format_read_object(tuple: &(Arc<dyn TypeInfo>, Arc<dyn Any>)) -> S
             format!("Type: {}, données: {}", ty.name(), data.downcast_ref_unchecked::<char>()),
         TypeKind::Bool =>
             format!("Type: {}, données: {}", ty.name(), data.downcast_ref_unchecked::<bool>()),
+        TypeKind::String =>
+            format!("Type: {}, données: {:?}", ty.name(), data.downcast_ref::<String>().unwrap()),
         TypeKind::Product =>
             format!("Type: {}, données: {}", ty.name(), format_heterogeneous_list(data.downcast_ref::<Vec<Arc<dyn Any>>>().unwrap())),
         TypeKind::Record =>
             format!("Type: {}, données: {}", ty.name(), format_heterogeneous_map(data.downcast_ref::<LinkedHashMap<String, Arc<dyn Any>>>().unwrap())),
-        TypeKind::Sum =>
-            format!("Type: {}, choisi: {}, données: {}",
-                    ty.name(),
-                    ty.as_any().downcast_ref_unchecked::<SumType>().1,
-                    format_heterogeneous_list(data.downcast_ref::<Vec<Arc<dyn Any>>>().unwrap())),
+        TypeKind::Sum => {
+            let variant = data.downcast_ref::<Variant>().unwrap();
+            format!("Type: {}, choisi: {}, données: {}", ty.name(), variant.case, format_heterogeneous_list(&variant.fields))
+        },
+        TypeKind::Array =>
+            format!("Type: {}, données: {}", ty.name(), format_heterogeneous_list(data.downcast_ref::<Vec<Arc<dyn Any>>>().unwrap())),
     }
 }
 
@@ -85,7 +104,7 @@ pub fn count_bits_set_range(i: u8, lo_include: usize, hi: usize) -> Vec<usize> {
     let mut set_bits = vec![];
     let mut bit = lo_include;
     while bit < hi {
-        if i >> bit & 1 == 1 {
+        if (i >> bit) & 1 == 1 {
             set_bits.push(bit);
         }
         bit += 1;
@@ -97,7 +116,7 @@ pub fn count_bits_set(i: u8) -> Vec<usize> {
     let mut set_bits = vec![];
     let mut bit = 0;
     while bit < 8 {
-        if i >> bit & 1 == 1 {
+        if (i >> bit) & 1 == 1 {
             set_bits.push(bit);
         }
         bit += 1;
@@ -106,5 +125,5 @@ pub fn count_bits_set(i: u8) -> Vec<usize> {
 }
 
 pub fn bit_set(i: u8, bit: usize) -> bool {
-    i >> bit & 1 == 1
+    (i >> bit) & 1 == 1
 }
\ No newline at end of file
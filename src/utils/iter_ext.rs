@@ -2,6 +2,7 @@ use std::collections::{BTreeMap, HashMap};
 use std::hash::Hash;
 
 pub trait IterExt<T> {
+    #[allow(dead_code)]
     fn group_by<K, F>(self, key_selector: F) -> HashMap<K, Vec<T>>
         where K: Eq + Hash,
               F: Fn(&T) -> K;
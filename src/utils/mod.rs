@@ -1,4 +1,4 @@
 pub(crate) mod iter_ext;
-pub(crate) mod errors;
+pub mod errors;
 pub(crate) mod func_ext;
 pub mod io;
\ No newline at end of file
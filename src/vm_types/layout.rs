@@ -0,0 +1,34 @@
+use std::mem::size_of;
+
+// La disposition des données des objets d'un tas. Elle est choisie par tas et gardée dans son registre des types,
+// les tas qui partagent un registre partagent donc aussi la disposition de leurs objets.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Layout {
+    // chaque scalaire occupe au moins un mot, les champs d'un record sont placés par alignement décroissant
+    #[default]
+    Padded,
+    // les caractères et les booléens n'occupent que leur taille, les champs des produits et des records sont
+    // placés par alignement décroissant afin de limiter le remplissage
+    Compact,
+    // la disposition de `#[repr(C)]`: les champs gardent l'ordre de leur déclaration, chacun à son alignement
+    CCompatible
+}
+
+impl Layout {
+    // La place d'un caractère ou d'un booléen de taille naturelle `size`, leur alignement est le même. Un caractère
+    // garde ses quatre bytes même dans la disposition compacte, afin de pouvoir contenir n'importe quel `char`.
+    pub fn small_scalar_size(self, size: usize) -> usize {
+        match self {
+            Layout::Padded => size_of::<usize>(),
+            Layout::Compact | Layout::CCompatible => size
+        }
+    }
+
+    pub fn reorders_products(self) -> bool {
+        self == Layout::Compact
+    }
+
+    pub fn reorders_records(self) -> bool {
+        self != Layout::CCompatible
+    }
+}
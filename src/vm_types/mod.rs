@@ -1,4 +1,6 @@
-pub(crate) mod type_tokens;
-pub(crate) mod type_info;
-pub(crate) mod type_sig;
-pub(crate) mod type_kind;
\ No newline at end of file
+pub mod type_tokens;
+pub mod type_info;
+pub mod type_sig;
+pub mod type_kind;
+pub mod type_registry;
+pub mod layout;
\ No newline at end of file
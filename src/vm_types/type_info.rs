@@ -2,23 +2,44 @@ use std::any::Any;
 use std::mem::{align_of, size_of};
 use std::sync::Arc;
 use linked_hash_map::LinkedHashMap;
-use crate::allocator::object_allocator;
 use crate::utils::iter_ext::IterExt;
+use crate::vm_types::layout::Layout;
 use crate::vm_types::type_kind::TypeKind;
 use crate::vm_types::type_sig::TypeSig;
 
+// La taille et l'alignement d'un type dépendent de la disposition choisie par le tas, voir `Layout`.
 pub trait TypeInfo : Send + Sync {
-    fn size(&self) -> usize;
+    fn size(&self, layout: Layout) -> usize;
     fn name(&self) -> String;
     fn kind(&self) -> TypeKind;
-    fn alignment(&self) -> usize;
+    fn alignment(&self, layout: Layout) -> usize;
     fn as_any(&self) -> &dyn Any;
 }
 
+// Les décalages des champs, dans l'ordre de leur déclaration, et la fin du dernier champ placé. Chaque champ est
+// placé au premier décalage multiple de son alignement, par alignement décroissant si `reorder` est vrai.
+fn lay_out<'a>(fields: impl Iterator<Item = &'a Arc<dyn TypeInfo>>, layout: Layout, reorder: bool) -> (Vec<usize>, usize) {
+    let fields = fields.enumerate().collect::<Vec<_>>();
+    let order = if reorder {
+        fields.iter().group_by_sorted(|(_, info)| info.alignment(layout)).into_values().rev().flatten().collect::<Vec<_>>()
+    } else {
+        fields.iter().collect()
+    };
+    let mut offsets = vec![0; fields.len()];
+    let mut end = 0usize;
+    for (index, info) in order {
+        // un champ composé est plus grand que son alignement, il est placé en entier
+        let offset = end.next_multiple_of(info.alignment(layout).max(1));
+        offsets[*index] = offset;
+        end = offset + info.size(layout);
+    }
+    (offsets, end)
+}
+
 pub struct TypeDeclaration(pub String, pub Box<dyn TypeInfo>);
 impl TypeInfo for TypeDeclaration {
-    fn size(&self) -> usize {
-        self.1.size()
+    fn size(&self, layout: Layout) -> usize {
+        self.1.size(layout)
     }
 
     fn name(&self) -> String {
@@ -29,8 +50,8 @@ impl TypeInfo for TypeDeclaration {
         self.1.kind()
     }
 
-    fn alignment(&self) -> usize {
-        self.1.alignment()
+    fn alignment(&self, layout: Layout) -> usize {
+        self.1.alignment(layout)
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -38,18 +59,37 @@ impl TypeInfo for TypeDeclaration {
     }
 }
 
+// Une union étiquetée: le type est partagé par tous les objets, le cas choisi est écrit dans l'objet lui-même.
+// L'étiquette est la position du cas dans la déclaration, les champs du cas la suivent. La taille d'une somme est
+// celle de son plus grand cas, un objet peut donc changer de cas sans être déplacé.
 #[derive(Clone)]
-pub struct SumType(pub LinkedHashMap<String, Arc<ProductType>>, pub String);
+pub struct SumType(pub LinkedHashMap<String, Arc<ProductType>>);
 
 impl SumType {
-    pub fn alignment_table(&self) -> Vec<usize> {
-        self.0.get(&self.1).unwrap().alignment_table()
+    pub fn tag_of(&self, case: &str) -> Option<usize> {
+        self.0.keys().position(|name| name == case)
+    }
+
+    pub fn case(&self, tag: usize) -> Option<(&String, &Arc<ProductType>)> {
+        self.0.iter().nth(tag)
+    }
+
+    // le décalage des champs de chaque cas, ils suivent l'étiquette
+    pub fn payload_offset(&self, layout: Layout) -> usize {
+        size_of::<usize>().next_multiple_of(self.alignment(layout))
+    }
+
+    // les décalages des champs du cas `tag`, à partir du début de la somme
+    pub fn alignment_table(&self, tag: usize, layout: Layout) -> Vec<usize> {
+        let payload_offset = self.payload_offset(layout);
+        self.case(tag).unwrap().1.alignment_table(layout).into_iter().map(|offset| payload_offset + offset).collect()
     }
 }
 
 impl TypeInfo for SumType {
-    fn size(&self) -> usize {
-        self.0.get(&self.1).unwrap().size()
+    fn size(&self, layout: Layout) -> usize {
+        let largest = self.0.values().map(|case| case.size(layout)).max().unwrap_or(0);
+        (self.payload_offset(layout) + largest).next_multiple_of(self.alignment(layout))
     }
 
     fn name(&self) -> String {
@@ -64,8 +104,8 @@ impl TypeInfo for SumType {
         TypeKind::Sum
     }
 
-    fn alignment(&self) -> usize {
-        self.0.get(&self.1).unwrap().alignment()
+    fn alignment(&self, layout: Layout) -> usize {
+        self.0.values().map(|case| case.alignment(layout)).fold(align_of::<usize>(), usize::max)
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -77,25 +117,18 @@ impl TypeInfo for SumType {
 #[derive(Clone)]
 pub struct RecordType(pub Arc<LinkedHashMap<String, Arc<dyn TypeInfo>>>);
 impl RecordType {
-    pub(crate) fn alignment_table(&self) -> LinkedHashMap<String, usize> {
-        let mut offset = 0;
-        let mut alignment_table =  LinkedHashMap::<String, usize>::new();
-        let grouped_by_alignment = (*(self.0)).iter().group_by_sorted(|(_, a)| a.alignment());
-        let iter = grouped_by_alignment.iter().rev();
-        for (_, items) in iter {
-            for (name, info) in items {
-                alignment_table.insert((*name).clone(), offset);
-                offset += info.alignment();
-            }
-        }
-        alignment_table
+    // les décalages des champs, dans l'ordre de leur déclaration
+    pub(crate) fn alignment_table(&self, layout: Layout) -> LinkedHashMap<String, usize> {
+        let (offsets, _) = lay_out(self.0.values(), layout, layout.reorders_records());
+        self.0.keys().cloned().zip(offsets).collect()
     }
 }
 
 impl TypeInfo for RecordType {
-    fn size(&self) -> usize {
-        let table = self.alignment_table();
-        (table.values().last().unwrap()) + self.0.get(table.keys().last().unwrap()).unwrap().size()
+    fn size(&self, layout: Layout) -> usize {
+        let (_, end) = lay_out(self.0.values(), layout, layout.reorders_records());
+        // arrondie à l'alignement, afin que le record puisse être placé dans un autre objet
+        end.next_multiple_of(self.alignment(layout).max(1))
     }
 
     fn name(&self) -> String {
@@ -110,8 +143,8 @@ impl TypeInfo for RecordType {
         TypeKind::Record
     }
 
-    fn alignment(&self) -> usize {
-        self.0.iter().map(|(_, info)| info.alignment()).max().unwrap()
+    fn alignment(&self, layout: Layout) -> usize {
+        self.0.iter().map(|(_, info)| info.alignment(layout)).max().unwrap_or(0)
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -123,28 +156,17 @@ impl TypeInfo for RecordType {
 pub struct ProductType(pub Vec<Arc<dyn TypeInfo>>);
 impl ProductType {
 
-    // this rearranges fields to make it more compact
-    pub fn alignment_table(&self) -> Vec<usize> {
-        if self.0.is_empty() {
-            return vec![];
-        }
-        let mut offset = 0;
-        let mut alignment_table: Vec<usize> =  vec![offset];
-        offset += self.0.first().unwrap().size();
-        for field in self.0.iter().skip(1) {
-            let padding_discriminant = offset % field.alignment();
-            let padding = if padding_discriminant == 0 { 0 } else { field.alignment() - padding_discriminant };
-            offset += padding;
-            alignment_table.push(offset);
-            offset += field.size();
-        }
-        alignment_table
+    // the offsets of the fields in declaration order, the compact layout rearranges them to save padding
+    pub fn alignment_table(&self, layout: Layout) -> Vec<usize> {
+        lay_out(self.0.iter(), layout, layout.reorders_products()).0
     }
 }
 
 impl TypeInfo for ProductType {
-    fn size(&self) -> usize {
-        (*self.alignment_table().last().unwrap_or(&0)) + self.0.last().map(|x| x.alignment()).unwrap_or(0) // the last field's start + the last field's size
+    fn size(&self, layout: Layout) -> usize {
+        let (_, end) = lay_out(self.0.iter(), layout, layout.reorders_products()); // the end of the field placed last
+        // rounded up to the alignment, so that the product can be inlined into another object
+        end.next_multiple_of(self.alignment(layout).max(1))
     }
 
     fn name(&self) -> String {
@@ -155,8 +177,52 @@ impl TypeInfo for ProductType {
         TypeKind::Product
     }
 
-    fn alignment(&self) -> usize {
-        self.0.iter().map(|info| info.alignment()).max().unwrap_or(0)
+    fn alignment(&self, layout: Layout) -> usize {
+        self.0.iter().map(|info| info.alignment(layout)).max().unwrap_or(0)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// Le type d'un tableau: seul le type des éléments est connu, la longueur est écrite dans l'objet lui-même,
+// juste avant les éléments. `size` ne compte donc que la longueur, la taille des données d'un tableau de
+// longueur donnée est `data_size`.
+#[derive(Clone)]
+pub struct ArrayType(pub Arc<dyn TypeInfo>);
+impl ArrayType {
+    // la distance entre deux éléments consécutifs, chaque élément reste aligné
+    pub fn stride(&self, layout: Layout) -> usize {
+        let alignment = self.0.alignment(layout).max(1);
+        self.0.size(layout).div_ceil(alignment) * alignment
+    }
+
+    // l'offset d'un élément par rapport au début des données, les éléments suivent la longueur
+    pub fn element_offset(&self, index: usize, layout: Layout) -> usize {
+        size_of::<usize>() + index * self.stride(layout)
+    }
+
+    pub fn data_size(&self, length: usize, layout: Layout) -> usize {
+        self.element_offset(length, layout)
+    }
+}
+
+impl TypeInfo for ArrayType {
+    fn size(&self, _layout: Layout) -> usize {
+        size_of::<usize>()
+    }
+
+    fn name(&self) -> String {
+        format!("[{}]", self.0.name())
+    }
+
+    fn kind(&self) -> TypeKind {
+        TypeKind::Array
+    }
+
+    fn alignment(&self, _layout: Layout) -> usize {
+        align_of::<usize>()
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -167,7 +233,7 @@ impl TypeInfo for ProductType {
 #[derive(Copy, Clone)]
 pub struct NatType;
 impl TypeInfo for NatType {
-    fn size(&self) -> usize {
+    fn size(&self, _layout: Layout) -> usize {
         size_of::<u64>()
     }
 
@@ -179,7 +245,7 @@ impl TypeInfo for NatType {
         TypeKind::Nat
     }
 
-    fn alignment(&self) -> usize {
+    fn alignment(&self, _layout: Layout) -> usize {
         size_of::<u64>()
     }
 
@@ -191,7 +257,7 @@ impl TypeInfo for NatType {
 #[derive(Copy, Clone)]
 pub struct IntType;
 impl TypeInfo for IntType {
-    fn size(&self) -> usize {
+    fn size(&self, _layout: Layout) -> usize {
         size_of::<i64>()
     }
 
@@ -203,7 +269,7 @@ impl TypeInfo for IntType {
         TypeKind::Int
     }
 
-    fn alignment(&self) -> usize {
+    fn alignment(&self, _layout: Layout) -> usize {
         size_of::<i64>()
     }
 
@@ -215,7 +281,7 @@ impl TypeInfo for IntType {
 #[derive(Clone, Copy)]
 pub struct ReferenceType(pub usize);
 impl TypeInfo for ReferenceType {
-    fn size(&self) -> usize {
+    fn size(&self, _layout: Layout) -> usize {
         size_of::<usize>()
     }
 
@@ -227,7 +293,32 @@ impl TypeInfo for ReferenceType {
         TypeKind::Reference
     }
 
-    fn alignment(&self) -> usize {
+    fn alignment(&self, _layout: Layout) -> usize {
+        align_of::<usize>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// la référence faible, comme `ReferenceType` elle retient la signature de sa cible
+#[derive(Clone, Copy)]
+pub struct WeakReferenceType(pub usize);
+impl TypeInfo for WeakReferenceType {
+    fn size(&self, _layout: Layout) -> usize {
+        size_of::<usize>()
+    }
+
+    fn name(&self) -> String {
+        format!("&weak {}", TypeSig::type_sig_to_string(self.0))
+    }
+
+    fn kind(&self) -> TypeKind {
+        TypeKind::WeakReference
+    }
+
+    fn alignment(&self, _layout: Layout) -> usize {
         align_of::<usize>()
     }
 
@@ -239,7 +330,7 @@ impl TypeInfo for ReferenceType {
 #[derive(Copy, Clone)]
 pub struct DoubleType;
 impl TypeInfo for DoubleType {
-    fn size(&self) -> usize {
+    fn size(&self, _layout: Layout) -> usize {
         size_of::<f64>()
     }
 
@@ -251,7 +342,7 @@ impl TypeInfo for DoubleType {
         TypeKind::Double
     }
 
-    fn alignment(&self) -> usize {
+    fn alignment(&self, _layout: Layout) -> usize {
         size_of::<f64>()
     }
 
@@ -263,12 +354,8 @@ impl TypeInfo for DoubleType {
 #[derive(Copy, Clone)]
 pub struct CharType;
 impl TypeInfo for CharType {
-    fn size(&self) -> usize {
-        if object_allocator::USE_COMPACT_LAYOUT {
-            1
-        } else {
-            8
-        }
+    fn size(&self, layout: Layout) -> usize {
+        layout.small_scalar_size(size_of::<char>())
     }
 
     fn name(&self) -> String {
@@ -279,12 +366,33 @@ impl TypeInfo for CharType {
         TypeKind::Char
     }
 
-    fn alignment(&self) -> usize {
-        if object_allocator::USE_COMPACT_LAYOUT {
-            1
-        } else {
-            size_of::<usize>()
-        }
+    fn alignment(&self, layout: Layout) -> usize {
+        layout.small_scalar_size(align_of::<char>())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// Comme pour un tableau, `size` ne compte que la longueur, les bytes de la chaîne suivent dans l'objet
+#[derive(Copy, Clone)]
+pub struct StringType;
+impl TypeInfo for StringType {
+    fn size(&self, _layout: Layout) -> usize {
+        size_of::<usize>()
+    }
+
+    fn name(&self) -> String {
+        String::from("String")
+    }
+
+    fn kind(&self) -> TypeKind {
+        TypeKind::String
+    }
+
+    fn alignment(&self, _layout: Layout) -> usize {
+        align_of::<usize>()
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -295,12 +403,8 @@ impl TypeInfo for CharType {
 #[derive(Copy, Clone)]
 pub struct BoolType;
 impl TypeInfo for BoolType {
-    fn size(&self) -> usize {
-        if object_allocator::USE_COMPACT_LAYOUT {
-            1
-        } else {
-            8
-        }
+    fn size(&self, layout: Layout) -> usize {
+        layout.small_scalar_size(size_of::<bool>())
     }
 
     fn name(&self) -> String {
@@ -311,12 +415,8 @@ impl TypeInfo for BoolType {
         TypeKind::Bool
     }
 
-    fn alignment(&self) -> usize {
-        if object_allocator::USE_COMPACT_LAYOUT {
-            1
-        } else {
-            size_of::<usize>()
-        }
+    fn alignment(&self, layout: Layout) -> usize {
+        layout.small_scalar_size(align_of::<bool>())
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -1,16 +1,19 @@
 use crate::vm_types::type_sig::TypeSig;
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
 pub enum TypeKind {
     Nat,
     Int,
     Reference,
+    WeakReference,
     Double,
     Char,
     Bool,
     Product,
     Record,
-    Sum
+    Sum,
+    Array,
+    String
 }
 
 impl TypeKind {
@@ -19,12 +22,20 @@ impl TypeKind {
             TypeKind::Nat => TypeSig::NAT,
             TypeKind::Int => TypeSig::INT,
             TypeKind::Reference => TypeSig::REFERENCE,
+            TypeKind::WeakReference => TypeSig::WEAK_REFERENCE,
             TypeKind::Double => TypeSig::DOUBLE,
             TypeKind::Char => TypeSig::CHAR,
             TypeKind::Bool => TypeSig::BOOL,
             TypeKind::Product => TypeSig::PRODUCT,
             TypeKind::Record => TypeSig::RECORD,
-            TypeKind::Sum => TypeSig::SUM
+            TypeKind::Sum => TypeSig::SUM,
+            TypeKind::Array => TypeSig::ARRAY,
+            TypeKind::String => TypeSig::STRING
         }
     }
+
+    // les références fortes et faibles ont la même représentation, une adresse
+    pub fn is_reference(&self) -> bool {
+        matches!(self, TypeKind::Reference | TypeKind::WeakReference)
+    }
 }
\ No newline at end of file
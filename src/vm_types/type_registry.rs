@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use crate::vm_types::layout::Layout;
+use crate::vm_types::type_info::{ProductType, RecordType, SumType, TypeInfo};
+use crate::vm_types::type_kind::TypeKind;
+use crate::vm_types::type_tokens;
+
+// Les types des objets d'un tas, chacun n'est stocké qu'une seule fois. Les en-têtes des objets ne contiennent que
+// l'indice de leur type dans le registre, les types structurellement égaux (i.e. de même genre et de même nom, le
+// nom décrivant toute la structure du type) partagent le même indice. Les indices ne changent jamais, de sorte que
+// les ramasse-miettes à copie peuvent copier un objet d'un tas à l'autre tant que les deux tas partagent le même
+// registre. Les types sont libérés avec le dernier tas qui utilise le registre. Le registre fixe aussi la disposition
+// des données, qui donne la taille et les décalages des champs de chacun de ses types.
+pub struct TypeRegistry {
+    interned: RwLock<Interned>,
+    layout: Layout,
+    // les décalages des champs par indice de type et étiquette, voir `field_offsets`
+    offsets: RwLock<FieldOffsets>,
+    // les décalages des types placés dans un champ, qui n'ont pas d'indice, par genre, nom et étiquette
+    nested_offsets: RwLock<NestedOffsets>,
+    // la position de chaque champ d'un record d'après son nom, par indice de type
+    field_indices: RwLock<HashMap<usize, Arc<HashMap<String, usize>>>>,
+    // le nombre de consultations servies par l'un des caches ci-dessus
+    hits: AtomicUsize
+}
+
+type FieldOffsets = HashMap<(usize, usize), Arc<[usize]>>;
+type NestedOffsets = HashMap<(TypeKind, String, usize), Arc<[usize]>>;
+
+struct Interned {
+    types: Vec<Arc<dyn TypeInfo>>,
+    ids: HashMap<(TypeKind, String), usize>
+}
+
+impl TypeRegistry {
+    // les scalaires sont enregistrés à la création du registre, leurs indices sont fixes
+    pub const NAT: usize = 0;
+    pub const INT: usize = 1;
+    pub const DOUBLE: usize = 2;
+    pub const CHAR: usize = 3;
+    pub const BOOL: usize = 4;
+    pub const STRING: usize = 5;
+    // l'indice des en-têtes qui ne décrivent pas un objet, e.g. un bouche-trou ou un objet déjà copié
+    pub const UNTYPED: usize = usize::MAX;
+
+    pub fn new() -> Arc<TypeRegistry> {
+        Self::with_layout(Layout::default())
+    }
+
+    pub fn with_layout(layout: Layout) -> Arc<TypeRegistry> {
+        let registry = TypeRegistry {
+            interned: RwLock::new(Interned { types: vec![], ids: HashMap::new() }),
+            layout,
+            offsets: RwLock::new(HashMap::new()),
+            nested_offsets: RwLock::new(HashMap::new()),
+            field_indices: RwLock::new(HashMap::new()),
+            hits: AtomicUsize::new(0)
+        };
+        registry.intern_arc(Arc::new(type_tokens::NAT));
+        registry.intern_arc(Arc::new(type_tokens::INT));
+        registry.intern_arc(Arc::new(type_tokens::DOUBLE));
+        registry.intern_arc(Arc::new(type_tokens::CHAR));
+        registry.intern_arc(Arc::new(type_tokens::BOOL));
+        registry.intern_arc(Arc::new(type_tokens::STRING));
+        Arc::new(registry)
+    }
+
+    pub fn intern<T: TypeInfo + Clone + 'static>(&self, type_info: &T) -> usize {
+        let key = (type_info.kind(), type_info.name());
+        if let Some(id) = self.interned.read().unwrap().ids.get(&key) {
+            return *id;
+        }
+        self.intern_arc(Arc::new(type_info.clone()))
+    }
+
+    pub fn intern_arc(&self, type_info: Arc<dyn TypeInfo>) -> usize {
+        let mut interned = self.interned.write().unwrap();
+        let key = (type_info.kind(), type_info.name());
+        if let Some(id) = interned.ids.get(&key) {
+            return *id;
+        }
+        interned.types.push(type_info);
+        let id = interned.types.len() - 1;
+        interned.ids.insert(key, id);
+        id
+    }
+
+    pub fn get(&self, id: usize) -> Option<Arc<dyn TypeInfo>> {
+        self.interned.read().unwrap().types.get(id).cloned()
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    // Les décalages des champs d'un produit, d'un record ou du cas `tag` d'une somme, dans l'ordre de leur
+    // déclaration. La table d'alignement n'est calculée qu'au premier accès, les types et la disposition d'un
+    // registre ne changeant jamais. `tag` est ignoré pour les produits et les records.
+    pub fn field_offsets(&self, id: usize, tag: usize) -> Option<Arc<[usize]>> {
+        let type_info = self.get(id)?;
+        let tag = if type_info.as_any().is::<SumType>() { tag } else { 0 };
+        if let Some(offsets) = self.offsets.read().unwrap().get(&(id, tag)) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Some(offsets.clone());
+        }
+        let offsets = self.alignment_table(type_info.as_ref(), tag)?;
+        self.offsets.write().unwrap().insert((id, tag), offsets.clone());
+        Some(offsets)
+    }
+
+    // Comme `field_offsets`, pour un produit, un record ou une somme placé dans un champ d'un autre type. Ces types
+    // ne sont pas enregistrés, leur table est retenue d'après leur nom, qui décrit toute leur structure.
+    pub fn nested_offsets(&self, type_info: &dyn TypeInfo, tag: usize) -> Option<Arc<[usize]>> {
+        let tag = if type_info.as_any().is::<SumType>() { tag } else { 0 };
+        let key = (type_info.kind(), type_info.name(), tag);
+        if let Some(offsets) = self.nested_offsets.read().unwrap().get(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Some(offsets.clone());
+        }
+        let offsets = self.alignment_table(type_info, tag)?;
+        self.nested_offsets.write().unwrap().insert(key, offsets.clone());
+        Some(offsets)
+    }
+
+    fn alignment_table(&self, type_info: &dyn TypeInfo, tag: usize) -> Option<Arc<[usize]>> {
+        let any = type_info.as_any();
+        if let Some(product_type) = any.downcast_ref::<ProductType>() {
+            Some(product_type.alignment_table(self.layout).into())
+        } else if let Some(record_type) = any.downcast_ref::<RecordType>() {
+            Some(record_type.alignment_table(self.layout).values().copied().collect())
+        } else {
+            let sum_type = any.downcast_ref::<SumType>()?;
+            sum_type.case(tag)?;
+            Some(sum_type.alignment_table(tag, self.layout).into())
+        }
+    }
+
+    // La position du champ `name` du record d'indice `id`, la table des noms est construite au premier accès.
+    pub fn field_index(&self, id: usize, name: &str) -> Option<usize> {
+        if let Some(indices) = self.field_indices.read().unwrap().get(&id) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return indices.get(name).copied();
+        }
+        let type_info = self.get(id)?;
+        let record_type = type_info.as_any().downcast_ref::<RecordType>()?;
+        let indices = Arc::new(record_type.0.keys().enumerate().map(|(index, key)| (key.clone(), index)).collect::<HashMap<_, _>>());
+        self.field_indices.write().unwrap().insert(id, indices.clone());
+        indices.get(name).copied()
+    }
+
+    // le nombre de tables de décalages déjà calculées, imbriquées comprises
+    pub fn cached_offsets(&self) -> usize {
+        self.offsets.read().unwrap().len() + self.nested_offsets.read().unwrap().len()
+    }
+
+    // le nombre de consultations des tables de décalages et de noms qui n'ont rien eu à calculer
+    pub fn cache_hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn len(&self) -> usize {
+        self.interned.read().unwrap().types.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
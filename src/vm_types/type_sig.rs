@@ -31,6 +31,18 @@ impl TypeSig {
     pub const PRODUCT: usize = 7;
     pub const RECORD: usize = 8;
     pub const SUM: usize = 9;
+    // un objet sans données qui ne fait que remplir l'espace d'un objet mort, afin que le tas reste
+    // parcourable par `GarbageCollector::next_object` après une collection sans déplacement
+    pub const FILLER: usize = 10;
+    // l'en-tête d'un objet déjà copié par le ramasse-miettes à copie, le champ `size` de l'en-tête contient
+    // alors la nouvelle adresse de l'objet
+    pub const FORWARDED: usize = 11;
+    // une référence qui ne garde pas sa cible en vie, elle est mise à nul quand sa cible est récupérée
+    pub const WEAK_REFERENCE: usize = 12;
+    // une suite d'éléments du même type, la longueur est le premier mot des données
+    pub const ARRAY: usize = 13;
+    // une chaîne UTF-8, sa longueur en bytes est le premier mot des données, suivie des bytes eux-mêmes
+    pub const STRING: usize = 14;
 
     pub fn type_sig_to_string(sig: usize) -> &'static str {
         match sig {
@@ -43,6 +55,11 @@ impl TypeSig {
             Self::PRODUCT => "$Product",
             Self::RECORD => "$Record",
             Self::SUM => "$Sum",
+            Self::FILLER => "$Filler",
+            Self::FORWARDED => "$Forwarded",
+            Self::WEAK_REFERENCE => "WeakReference",
+            Self::ARRAY => "$Array",
+            Self::STRING => "String",
             _ => unreachable!()
         }
     }
@@ -56,9 +73,12 @@ impl TypeSig {
             Self::CHAR => TypeKind::Char,
             Self::BOOL => TypeKind::Bool,
             Self::REFERENCE => TypeKind::Reference,
+            Self::WEAK_REFERENCE => TypeKind::WeakReference,
             Self::PRODUCT => TypeKind::Product,
             Self::RECORD => TypeKind::Record,
             Self::SUM => TypeKind::Sum,
+            Self::ARRAY => TypeKind::Array,
+            Self::STRING => TypeKind::String,
             _ => unreachable!()
         }
     }
@@ -7,4 +7,5 @@ pub static INT: IntType = IntType{};
 pub static NAT: NatType = NatType{};
 pub static DOUBLE: DoubleType = DoubleType{};
 pub static CHAR: CharType = CharType{};
-pub static BOOL: BoolType = BoolType{};
\ No newline at end of file
+pub static BOOL: BoolType = BoolType{};
+pub static STRING: StringType = StringType{};
\ No newline at end of file